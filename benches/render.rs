@@ -0,0 +1,65 @@
+// Run with: cargo bench
+// Compare with an earlier run using cargo bench -- --save-baseline main and --baseline main.
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use image::{Rgba, RgbaImage};
+use minecraft_render::{Layer, MaskMode, RenderContext, RenderOptions};
+
+fn sample_skin() -> RgbaImage {
+    image::open("sample.png").unwrap().into_rgba8()
+}
+
+fn bench_render(c: &mut Criterion) {
+    let skin_texture = sample_skin();
+
+    // Creating a new context decodes the UV maps for every render.
+    c.bench_function("create_render", |b| {
+        b.iter(|| minecraft_render::create_render(black_box(&skin_texture)))
+    });
+
+    let context = RenderContext::new();
+    let options = RenderOptions::default();
+    c.bench_function("RenderContext::render", |b| {
+        b.iter(|| context.render(black_box(&skin_texture), &options))
+    });
+
+    // A single call to blend_layer_with_base for the largest layer.
+    c.bench_function("RenderContext::render_layer chest", |b| {
+        b.iter(|| context.render_layer(black_box(&skin_texture), Layer::Chest, &options))
+    });
+}
+
+fn bench_chara(c: &mut Criterion) {
+    let render = minecraft_render::create_render(&sample_skin());
+    let slots = minecraft_render::chara_slots(minecraft_render::Interpolation::Bilinear);
+
+    for slot in &slots {
+        c.bench_function(&format!("create_chara_image {}", slot.name), |b| {
+            b.iter(|| {
+                minecraft_render::create_chara_image(
+                    black_box(&render),
+                    &slot.mask,
+                    slot.scale,
+                    slot.translate_x,
+                    slot.translate_y,
+                    Rgba([0u8, 0u8, 0u8, 0u8]),
+                    MaskMode::Min,
+                )
+            })
+        });
+    }
+}
+
+fn bench_color_correct(c: &mut Criterion) {
+    let render = minecraft_render::create_render(&sample_skin());
+
+    c.bench_function("color_correct_image", |b| {
+        b.iter_batched_ref(
+            || render.clone(),
+            minecraft_render::color_correct_image,
+            criterion::BatchSize::LargeInput,
+        )
+    });
+}
+
+criterion_group!(benches, bench_render, bench_chara, bench_color_correct);
+criterion_main!(benches);
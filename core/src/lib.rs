@@ -0,0 +1,215 @@
+//! The pixel math for blending and sampling renders without any image or file IO.
+//! This crate only requires `core`, so it can be used without the standard library.
+//! Floating point functions use libm unless the `std` feature is enabled.
+#![cfg_attr(not(feature = "std"), no_std)]
+
+/// Blends `val1` and `val2` using `alpha` as the weight for `val2`.
+/// The values are linearized using `gamma` before blending and encoded again afterwards.
+/// This avoids darkening the edges between colors when blending gamma encoded values.
+#[inline]
+pub fn alpha_blend(val1: f32, val2: f32, alpha: f32, gamma: f32) -> f32 {
+    // Gamma correct to ensure the blending result is more accurate.
+    // A gamma of 1.0 blends the values linearly without any correction.
+    let val1_gamma_corrected = powf(val1, gamma);
+    let val2_gamma_corrected = powf(val2, gamma);
+    let result = val1_gamma_corrected * (1f32 - alpha) + val2_gamma_corrected * alpha;
+    powf(result, 1.0f32 / gamma)
+}
+
+/// How coordinates outside the range 0.0 to 1.0 are handled when sampling an image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WrapMode {
+    /// Use the nearest pixel on the edge of the image.
+    #[default]
+    Clamp,
+    /// Tile the image by only using the fractional part of the coordinates.
+    Repeat,
+}
+
+/// Converts normalized coordinates to the nearest pixel for an image with the given dimensions.
+/// The coordinates use the image's origin at the top left, and out of bounds coordinates are clamped to the edges.
+#[inline]
+pub fn interpolate_nearest(x: f32, y: f32, width: u32, height: u32) -> (u32, u32) {
+    interpolate_nearest_with(x, y, width, height, WrapMode::Clamp)
+}
+
+/// The default offset in pixels subtracted before rounding in [interpolate_nearest].
+/// An offset of 0.5 treats the center of each texel as half a pixel from its top left corner.
+pub const DEFAULT_TEXEL_OFFSET: f32 = 0.5f32;
+
+/// Converts normalized coordinates to the nearest pixel like [interpolate_nearest]
+/// using `wrap` for out of bounds coordinates.
+#[inline]
+pub fn interpolate_nearest_with(
+    x: f32,
+    y: f32,
+    width: u32,
+    height: u32,
+    wrap: WrapMode,
+) -> (u32, u32) {
+    interpolate_nearest_with_offset(x, y, width, height, wrap, DEFAULT_TEXEL_OFFSET)
+}
+
+/// Converts normalized coordinates to the nearest pixel like [interpolate_nearest_with]
+/// after subtracting `texel_offset` pixels.
+/// Use an offset of 0.0 for UV maps that place texel centers on the pixel corners.
+#[inline]
+pub fn interpolate_nearest_with_offset(
+    x: f32,
+    y: f32,
+    width: u32,
+    height: u32,
+    wrap: WrapMode,
+    texel_offset: f32,
+) -> (u32, u32) {
+    let (x, y) = match wrap {
+        WrapMode::Clamp => (x, y),
+        // Wrap before finding the nearest pixel, so the result is still clamped to the last pixel.
+        WrapMode::Repeat => (x - floor(x), y - floor(y)),
+    };
+
+    // Clamp to the edges for out of bounds indices.
+    // Clamp to 0.0f32 before casting to avoid underflow.
+    let nearest = |f: f32, max_val: u32| {
+        let val = round(f * max_val as f32 - texel_offset);
+        if val < 0f32 {
+            0u32
+        } else {
+            core::cmp::min(val as u32, max_val - 1)
+        }
+    };
+
+    (nearest(x, width), nearest(y, height))
+}
+
+/// Converts the channels of `pixel` to the range 0.0 to 1.0.
+#[inline]
+pub fn normalize_rgba_u8(pixel: [u8; 4]) -> (f32, f32, f32, f32) {
+    // 0u8 -> 0.0f32, 255u8 -> 1.0f32
+    let normalize = |u| u as f32 / 255f32;
+    (
+        normalize(pixel[0]),
+        normalize(pixel[1]),
+        normalize(pixel[2]),
+        normalize(pixel[3]),
+    )
+}
+
+/// Converts the channels of `pixel` to the range 0.0 to 1.0.
+#[inline]
+pub fn normalize_rgba_u16(pixel: [u16; 4]) -> (f32, f32, f32, f32) {
+    // 0u16 -> 0.0f32, 65535u16 -> 1.0f32
+    let normalize = |u| u as f32 / 65535f32;
+    (
+        normalize(pixel[0]),
+        normalize(pixel[1]),
+        normalize(pixel[2]),
+        normalize(pixel[3]),
+    )
+}
+
+/// Converts a value from 0.0 to 1.0 to the nearest `u8` and clamps out of range values.
+#[inline]
+pub fn to_u8_clamped(x: f32) -> u8 {
+    // Pick the nearest integer so values close to 1.0 are still converted to 255u8.
+    let result = round(x * 255f32);
+    if result < 0.0f32 {
+        0u8
+    } else if result > 255f32 {
+        255u8
+    } else {
+        result as u8
+    }
+}
+
+/// Converts a value from 0.0 to 1.0 to the nearest `u16` and clamps out of range values.
+#[inline]
+pub fn to_u16_clamped(x: f32) -> u16 {
+    let result = round(x * 65535f32);
+    if result < 0.0f32 {
+        0u16
+    } else if result > 65535f32 {
+        65535u16
+    } else {
+        result as u16
+    }
+}
+
+#[cfg(feature = "std")]
+#[inline]
+fn powf(x: f32, y: f32) -> f32 {
+    x.powf(y)
+}
+
+#[cfg(not(feature = "std"))]
+#[inline]
+fn powf(x: f32, y: f32) -> f32 {
+    libm::powf(x, y)
+}
+
+#[cfg(feature = "std")]
+#[inline]
+fn round(x: f32) -> f32 {
+    x.round()
+}
+
+#[cfg(not(feature = "std"))]
+#[inline]
+fn round(x: f32) -> f32 {
+    libm::roundf(x)
+}
+
+#[cfg(feature = "std")]
+#[inline]
+fn floor(x: f32) -> f32 {
+    x.floor()
+}
+
+#[cfg(not(feature = "std"))]
+#[inline]
+fn floor(x: f32) -> f32 {
+    libm::floorf(x)
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+
+    #[test]
+    fn test_alpha_blend_matches_std() {
+        for i in 0..=16 {
+            for j in 0..=16 {
+                let val1 = i as f32 / 16f32;
+                let val2 = j as f32 / 16f32;
+                let expected = (val1.powf(2.2f32) * 0.25f32 + val2.powf(2.2f32) * 0.75f32)
+                    .powf(1.0f32 / 2.2f32);
+                let result = alpha_blend(val1, val2, 0.75f32, 2.2f32);
+                assert!((result - expected).abs() < 1e-6f32, "{} {}", val1, val2);
+            }
+        }
+    }
+
+    #[test]
+    fn test_libm_matches_std() {
+        // Workspace builds enable the std feature, so check the libm functions directly.
+        for i in 0..=64 {
+            let x = i as f32 / 64f32;
+            for gamma in [1.0f32, 2.2f32, 1.0f32 / 2.2f32] {
+                assert!((libm::powf(x, gamma) - x.powf(gamma)).abs() < 1e-6f32);
+            }
+            assert_eq!(libm::roundf(x * 255f32), (x * 255f32).round());
+            assert_eq!(libm::floorf(x * 4f32 - 2f32), (x * 4f32 - 2f32).floor());
+        }
+    }
+
+    #[test]
+    fn test_to_u8_clamped_matches_std() {
+        for i in -10..=270 {
+            let x = i as f32 / 255f32 + 0.5f32 / 255f32;
+            let expected = (x * 255f32).round().clamp(0f32, 255f32) as u8;
+            assert_eq!(to_u8_clamped(x), expected);
+        }
+    }
+}
@@ -0,0 +1,114 @@
+//! C bindings for rendering skins from other languages like C# with P/Invoke or Python with ctypes.
+//! This requires the `capi` feature.
+//!
+//! Buffers passed to the library are only borrowed for the duration of the call.
+//! Buffers returned by the library are owned by the caller and must be freed with [mcr_free]
+//! instead of the caller's own allocator.
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use crate::{RenderContext, RenderOptions};
+
+/// The render was created successfully.
+pub const MCR_OK: i32 = 0;
+/// A required pointer argument was null.
+pub const MCR_NULL_POINTER: i32 = 1;
+/// The input bytes couldn't be decoded as an image.
+pub const MCR_INVALID_SKIN: i32 = 2;
+/// The render couldn't be encoded as a PNG.
+pub const MCR_ENCODE_FAILED: i32 = 3;
+/// Rendering failed unexpectedly.
+pub const MCR_PANIC: i32 = 4;
+
+thread_local! {
+    // Reuse the decoded UV maps across calls since decoding is the most expensive step.
+    static CONTEXT: RenderContext = RenderContext::new();
+}
+
+/// Creates a render from the `len` PNG bytes of a Minecraft skin texture at `skin_ptr`.
+/// On success, `out_ptr` and `out_len` are set to the PNG bytes of the render and [MCR_OK] is returned.
+/// Otherwise, an error code is returned and the outputs are set to null and 0.
+///
+/// # Safety
+/// `skin_ptr` must point to `len` readable bytes, which are not modified or freed by the library.
+/// `out_ptr` and `out_len` must be valid for writes.
+/// The caller owns the returned buffer and must free it with [mcr_free] using the returned length.
+#[no_mangle]
+pub unsafe extern "C" fn mcr_render(
+    skin_ptr: *const u8,
+    len: usize,
+    out_ptr: *mut *mut u8,
+    out_len: *mut usize,
+) -> i32 {
+    if skin_ptr.is_null() || out_ptr.is_null() || out_len.is_null() {
+        return MCR_NULL_POINTER;
+    }
+    *out_ptr = std::ptr::null_mut();
+    *out_len = 0;
+
+    let bytes = std::slice::from_raw_parts(skin_ptr, len);
+    // Unwinding across the C boundary is undefined behavior, so report panics as an error instead.
+    let result = catch_unwind(AssertUnwindSafe(|| render_png(bytes))).unwrap_or(Err(MCR_PANIC));
+    match result {
+        Ok(png) => {
+            let png = png.into_boxed_slice();
+            *out_len = png.len();
+            *out_ptr = Box::into_raw(png) as *mut u8;
+            MCR_OK
+        }
+        Err(code) => code,
+    }
+}
+
+/// Frees a buffer returned by [mcr_render]. Null pointers are ignored.
+///
+/// # Safety
+/// `ptr` and `len` must be the exact values returned by a single call to [mcr_render],
+/// and the buffer must not be used or freed again afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn mcr_free(ptr: *mut u8, len: usize) {
+    if !ptr.is_null() {
+        drop(Box::from_raw(std::ptr::slice_from_raw_parts_mut(ptr, len)));
+    }
+}
+
+fn render_png(bytes: &[u8]) -> Result<Vec<u8>, i32> {
+    let skin_texture = image::load_from_memory(bytes)
+        .map_err(|_| MCR_INVALID_SKIN)?
+        .into_rgba8();
+
+    let render = CONTEXT.with(|context| context.render(&skin_texture, &RenderOptions::default()));
+
+    let mut png = Vec::new();
+    image::DynamicImage::ImageRgba8(render)
+        .write_to(&mut png, image::ImageOutputFormat::Png)
+        .map_err(|_| MCR_ENCODE_FAILED)?;
+    Ok(png)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mcr_render() {
+        let bytes = include_bytes!("../sample.png");
+        let mut out_ptr = std::ptr::null_mut();
+        let mut out_len = 0;
+        let code = unsafe { mcr_render(bytes.as_ptr(), bytes.len(), &mut out_ptr, &mut out_len) };
+        assert_eq!(code, MCR_OK);
+
+        let png = unsafe { std::slice::from_raw_parts(out_ptr, out_len) };
+        let render = image::load_from_memory(png).unwrap();
+        let skin_texture = image::load_from_memory(bytes).unwrap().into_rgba8();
+        assert_eq!(
+            render.into_rgba8().dimensions(),
+            crate::create_render(&skin_texture).dimensions()
+        );
+        unsafe { mcr_free(out_ptr, out_len) };
+
+        let code = unsafe { mcr_render(b"not a png".as_ptr(), 9, &mut out_ptr, &mut out_len) };
+        assert_eq!(code, MCR_INVALID_SKIN);
+        assert!(out_ptr.is_null());
+        assert_eq!(out_len, 0);
+    }
+}
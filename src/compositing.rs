@@ -0,0 +1,318 @@
+//! Functions for blending colors in the render and combining rendered images.
+use image::math::Rect;
+use image::{Rgba, RgbaImage};
+
+use crate::sampling::{normalize_rgba_u8, to_u8_clamped};
+
+pub use minecraft_render_core::alpha_blend;
+
+const SHEET_PADDING: u32 = 16;
+const SHEET_BACKGROUND: Rgba<u8> = Rgba([224u8, 224u8, 224u8, 255u8]);
+const LABEL_COLOR: Rgba<u8> = Rgba([0u8, 0u8, 0u8, 255u8]);
+// Each glyph is 3x5 pixels drawn at a larger scale to be readable next to full size renders.
+const LABEL_SCALE: u32 = 4;
+const LABEL_HEIGHT: u32 = 5 * LABEL_SCALE;
+
+/// Arranges `images` in a grid with each image labeled by its name.
+/// Each grid cell is the size of the largest image, and transparent areas show the gray background.
+pub fn make_contact_sheet(images: &[(&str, &RgbaImage)]) -> RgbaImage {
+    // Use a roughly square grid.
+    let columns = (images.len() as f32).sqrt().ceil().max(1f32) as u32;
+    let rows = (images.len() as u32).div_ceil(columns);
+
+    let cell_width = images.iter().map(|(_, i)| i.width()).max().unwrap_or(0);
+    let cell_height =
+        images.iter().map(|(_, i)| i.height()).max().unwrap_or(0) + LABEL_HEIGHT + SHEET_PADDING;
+
+    let mut sheet = RgbaImage::from_pixel(
+        columns * (cell_width + SHEET_PADDING) + SHEET_PADDING,
+        rows * (cell_height + SHEET_PADDING) + SHEET_PADDING,
+        SHEET_BACKGROUND,
+    );
+
+    for (i, (name, image)) in images.iter().enumerate() {
+        let x = SHEET_PADDING + (i as u32 % columns) * (cell_width + SHEET_PADDING);
+        let y = SHEET_PADDING + (i as u32 / columns) * (cell_height + SHEET_PADDING);
+        draw_label(&mut sheet, name, x, y);
+        image::imageops::overlay(&mut sheet, *image, x, y + LABEL_HEIGHT + SHEET_PADDING);
+    }
+
+    sheet
+}
+
+/// Draws `layer` over `base` with straight alpha, blending colors with `gamma` like the render layers.
+/// Both images must have the same dimensions.
+pub fn composite_over(base: &mut RgbaImage, layer: &RgbaImage, gamma: f32) {
+    for (base_pixel, layer_pixel) in base.pixels_mut().zip(layer.pixels()) {
+        blend_pixel_over(base_pixel, layer_pixel, gamma);
+    }
+}
+
+/// Draws `render` over a copy of `background` with its top left corner at (`x`, `y`).
+/// Parts of the render outside the background are clipped, so the offset may be negative.
+/// Colors are blended with the default gamma of 2.2.
+pub fn composite_onto(render: &RgbaImage, background: &RgbaImage, x: i32, y: i32) -> RgbaImage {
+    let mut output = background.clone();
+
+    // Only iterate over the overlapping region.
+    let overlap = |offset: i32, render_size: u32, background_size: u32| {
+        let start = offset.max(0) as i64;
+        let end = (offset as i64 + render_size as i64).min(background_size as i64);
+        start..end.max(start)
+    };
+    for output_y in overlap(y, render.height(), background.height()) {
+        for output_x in overlap(x, render.width(), background.width()) {
+            let render_pixel =
+                render.get_pixel((output_x - x as i64) as u32, (output_y - y as i64) as u32);
+            blend_pixel_over(
+                output.get_pixel_mut(output_x as u32, output_y as u32),
+                render_pixel,
+                crate::DEFAULT_GAMMA,
+            );
+        }
+    }
+
+    output
+}
+
+/// Packs `renders` into rows of a sprite sheet with power of two dimensions.
+/// The rectangles give the placement of each render in the same order as `renders`.
+/// The layout only depends on the dimensions of the renders, so the same inputs always produce the same sheet.
+/// An empty slice produces a 1x1 transparent sheet.
+pub fn pack_sprites(renders: &[RgbaImage]) -> (RgbaImage, Vec<Rect>) {
+    // Place the tallest renders first to waste less space in each row.
+    // The sort is stable, so renders with the same dimensions keep their order.
+    let mut order: Vec<_> = (0..renders.len()).collect();
+    order.sort_by_key(|i| std::cmp::Reverse((renders[*i].height(), renders[*i].width())));
+
+    // Aim for a roughly square sheet that fits the widest render.
+    let max_width = renders.iter().map(|r| r.width()).max().unwrap_or(1);
+    let area: u64 = renders
+        .iter()
+        .map(|r| r.width() as u64 * r.height() as u64)
+        .sum();
+    let width = max_width
+        .max((area as f64).sqrt().ceil() as u32)
+        .next_power_of_two();
+
+    let mut rects = vec![
+        Rect {
+            x: 0,
+            y: 0,
+            width: 0,
+            height: 0
+        };
+        renders.len()
+    ];
+    let (mut x, mut y, mut row_height) = (0, 0, 0);
+    for i in order {
+        let render = &renders[i];
+        if x + render.width() > width {
+            x = 0;
+            y += row_height;
+            row_height = 0;
+        }
+        rects[i] = Rect {
+            x,
+            y,
+            width: render.width(),
+            height: render.height(),
+        };
+        x += render.width();
+        row_height = row_height.max(render.height());
+    }
+
+    let height = (y + row_height).max(1).next_power_of_two();
+    let mut sheet = RgbaImage::new(width, height);
+    for (render, rect) in renders.iter().zip(&rects) {
+        image::imageops::replace(&mut sheet, render, rect.x, rect.y);
+    }
+    (sheet, rects)
+}
+
+fn blend_pixel_over(base_pixel: &mut Rgba<u8>, layer_pixel: &Rgba<u8>, gamma: f32) {
+    if layer_pixel[3] == 0u8 {
+        return;
+    }
+
+    let (base_r, base_g, base_b, base_a) = normalize_rgba_u8(base_pixel);
+    let (layer_r, layer_g, layer_b, layer_a) = normalize_rgba_u8(layer_pixel);
+
+    // Weight the layer by its contribution to the combined coverage
+    // to avoid blending with the color of transparent pixels.
+    let alpha = layer_a + base_a * (1f32 - layer_a);
+    let layer_weight = layer_a / alpha;
+    *base_pixel = Rgba([
+        to_u8_clamped(alpha_blend(base_r, layer_r, layer_weight, gamma)),
+        to_u8_clamped(alpha_blend(base_g, layer_g, layer_weight, gamma)),
+        to_u8_clamped(alpha_blend(base_b, layer_b, layer_weight, gamma)),
+        to_u8_clamped(alpha),
+    ]);
+}
+
+fn draw_label(image: &mut RgbaImage, text: &str, x: u32, y: u32) {
+    for (i, c) in text.chars().enumerate() {
+        // Leave a 1 pixel gap between glyphs.
+        let glyph_x = x + i as u32 * 4 * LABEL_SCALE;
+        for (row, bits) in glyph(c).iter().enumerate() {
+            for column in 0..3 {
+                if bits & (0b100 >> column) == 0 {
+                    continue;
+                }
+                for dx in 0..LABEL_SCALE {
+                    for dy in 0..LABEL_SCALE {
+                        let px = glyph_x + column * LABEL_SCALE + dx;
+                        let py = y + row as u32 * LABEL_SCALE + dy;
+                        if px < image.width() && py < image.height() {
+                            image.put_pixel(px, py, LABEL_COLOR);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn glyph(c: char) -> [u8; 5] {
+    // The rows of a 3x5 glyph from top to bottom with the leftmost pixel in the highest bit.
+    // Unsupported characters are left blank.
+    match c.to_ascii_lowercase() {
+        'a' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'b' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'c' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'd' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'e' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'f' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'g' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'h' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'i' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'j' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'k' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'l' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'm' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'n' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'o' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'p' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'r' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        's' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        't' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'u' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'v' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'w' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'x' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b110, 0b001, 0b010, 0b100, 0b111],
+        '3' => [0b110, 0b001, 0b010, 0b001, 0b110],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b110, 0b001, 0b110],
+        '6' => [0b011, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b010, 0b010, 0b010],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b110],
+        '_' => [0b000, 0b000, 0b000, 0b000, 0b111],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        _ => [0b000; 5],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alpha_blend_gamma() {
+        // Linear blending weights the values directly.
+        assert_eq!(alpha_blend(0f32, 0.5f32, 0.5f32, 1.0f32), 0.25f32);
+        // Gamma correct blending gives a brighter result.
+        let result = alpha_blend(0f32, 0.5f32, 0.5f32, 2.2f32);
+        assert!((result - 0.5f32 * 0.5f32.powf(1.0f32 / 2.2f32)).abs() < 0.0001f32);
+    }
+
+    #[test]
+    fn test_make_contact_sheet_dimensions() {
+        let render = RgbaImage::new(100, 200);
+        let chara = RgbaImage::new(50, 40);
+        let sheet = make_contact_sheet(&[
+            ("output", &render),
+            ("chara_3", &chara),
+            ("chara_4", &chara),
+        ]);
+
+        // 3 images use 2 rows of 2 columns with cells for the largest image and its label.
+        let cell_height = 200 + LABEL_HEIGHT + SHEET_PADDING;
+        assert_eq!(
+            sheet.dimensions(),
+            (
+                2 * (100 + SHEET_PADDING) + SHEET_PADDING,
+                2 * (cell_height + SHEET_PADDING) + SHEET_PADDING
+            )
+        );
+
+        // The label is drawn above the image.
+        assert_eq!(
+            sheet.get_pixel(SHEET_PADDING + LABEL_SCALE, SHEET_PADDING),
+            &LABEL_COLOR
+        );
+    }
+
+    #[test]
+    fn test_pack_sprites() {
+        let renders = [
+            RgbaImage::from_pixel(100, 150, Rgba([255u8, 0u8, 0u8, 255u8])),
+            RgbaImage::from_pixel(100, 150, Rgba([0u8, 255u8, 0u8, 255u8])),
+            RgbaImage::from_pixel(100, 150, Rgba([0u8, 0u8, 255u8, 255u8])),
+        ];
+        let (sheet, rects) = pack_sprites(&renders);
+        assert!(sheet.width().is_power_of_two() && sheet.height().is_power_of_two());
+        assert_eq!(rects.len(), 3);
+
+        for (i, (render, rect)) in renders.iter().zip(&rects).enumerate() {
+            assert_eq!((rect.width, rect.height), render.dimensions());
+            assert!(rect.x + rect.width <= sheet.width());
+            assert!(rect.y + rect.height <= sheet.height());
+            assert_eq!(
+                &image::imageops::crop_imm(&sheet, rect.x, rect.y, rect.width, rect.height)
+                    .to_image(),
+                render
+            );
+
+            for other in &rects[i + 1..] {
+                let overlaps_x = rect.x < other.x + other.width && other.x < rect.x + rect.width;
+                let overlaps_y = rect.y < other.y + other.height && other.y < rect.y + rect.height;
+                assert!(!(overlaps_x && overlaps_y));
+            }
+        }
+
+        // The same inputs always produce the same sheet.
+        assert_eq!(pack_sprites(&renders), (sheet, rects));
+    }
+
+    #[test]
+    fn test_composite_onto_clipped() {
+        let background = RgbaImage::from_pixel(8, 8, Rgba([0u8, 0u8, 255u8, 255u8]));
+        let mut render = RgbaImage::new(4, 4);
+        render.put_pixel(3, 3, Rgba([255u8, 0u8, 0u8, 255u8]));
+        render.put_pixel(1, 1, Rgba([255u8, 0u8, 0u8, 255u8]));
+
+        // Only the bottom right pixel of the render is on the background.
+        let output = composite_onto(&render, &background, -3, -3);
+        assert_eq!(output.dimensions(), (8, 8));
+        assert_eq!(output.get_pixel(0, 0), &Rgba([255u8, 0u8, 0u8, 255u8]));
+        assert_eq!(
+            output
+                .pixels()
+                .filter(|p| **p != background[(0, 0)])
+                .count(),
+            1
+        );
+
+        // Renders entirely outside the background are ignored.
+        assert_eq!(composite_onto(&render, &background, -4, 2), background);
+        assert_eq!(composite_onto(&render, &background, 8, 8), background);
+    }
+}
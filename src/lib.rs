@@ -1,377 +1,5180 @@
+use image::math::Rect;
 use image::DynamicImage;
+use image::GrayImage;
 use image::ImageBuffer;
+use image::Luma;
+use image::Pixel;
 use image::Rgba;
 use image::RgbaImage;
 use imageproc::geometric_transformations::warp_into_with;
-use imageproc::geometric_transformations::Interpolation;
-use std::cmp::min;
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+use std::borrow::Cow;
+use std::cmp::{max, min};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
 
+use compositing::alpha_blend;
+use sampling::{
+    normalize_rgba_u16, normalize_rgba_u8, sample_texture_with_flip, sample_uv_map_bilinear,
+    to_u16_clamped, to_u8_clamped, WrapMode, DEFAULT_TEXEL_OFFSET,
+};
+
+#[cfg(feature = "capi")]
+pub mod capi;
+pub mod compositing;
 pub mod modern_skin;
+#[cfg(feature = "online")]
+pub mod online;
+pub mod output;
+#[cfg(feature = "pack")]
+pub mod pack;
+pub mod sampling;
+
+pub use imageproc::geometric_transformations::Interpolation;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+/// Errors for skin textures that can't be rendered correctly.
+#[derive(Debug)]
+pub enum RenderError {
+    /// The skin texture has no visible pixels, so the render would be empty.
+    EmptySkin,
+    /// The visible pixels don't fit in a skin layout anchored at the top left of the image.
+    NoSkinRegion,
+    /// The skin file could not be read or decoded.
+    Image(image::ImageError),
+}
+
+impl std::fmt::Display for RenderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RenderError::EmptySkin => write!(f, "the skin texture is fully transparent"),
+            RenderError::NoSkinRegion => write!(
+                f,
+                "the skin texture has no 64x64 or 64x32 region anchored at the top left"
+            ),
+            RenderError::Image(e) => write!(f, "failed to load the skin texture: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for RenderError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RenderError::Image(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<image::ImageError> for RenderError {
+    fn from(e: image::ImageError) -> Self {
+        RenderError::Image(e)
+    }
+}
+
+/// Errors while loading UV maps with [RenderContext::from_dir].
+#[derive(Debug)]
+pub enum UvMapError {
+    /// The file could not be read or decoded.
+    Image(PathBuf, image::ImageError),
+    /// The file was decoded but isn't 16 bit RGBA or grayscale for lighting maps.
+    Format(PathBuf, image::ColorType),
+    /// The map doesn't have the same dimensions as the embedded map or the lighting map doesn't match its UV map.
+    /// The dimensions are the expected dimensions followed by the dimensions of the file.
+    Dimensions(PathBuf, (u32, u32), (u32, u32)),
+    /// Only one of the UV map and lighting map was found.
+    MissingSplitMap(PathBuf),
+}
+
+impl std::fmt::Display for UvMapError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UvMapError::Image(path, e) => {
+                write!(f, "failed to load UV map {:?}: {}", path, e)
+            }
+            UvMapError::Format(path, color_type) => write!(
+                f,
+                "UV map {:?} uses {:?} instead of 16 bit RGBA",
+                path, color_type
+            ),
+            UvMapError::Dimensions(path, expected, actual) => write!(
+                f,
+                "map {:?} has dimensions {:?} instead of {:?}",
+                path, actual, expected
+            ),
+            UvMapError::MissingSplitMap(path) => {
+                write!(f, "missing split UV or lighting map {:?}", path)
+            }
+        }
+    }
+}
+
+impl std::error::Error for UvMapError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            UvMapError::Image(_, e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Additional settings for customizing the output of [create_render_with_options].
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    /// Render using the slim Alex arms instead of the classic Steve arms.
+    pub slim: bool,
+    /// An optional cape texture using the standard 64x32 Minecraft cape layout.
+    /// The cape is drawn behind the body and is not visible for most of the render.
+    pub cape: Option<RgbaImage>,
+    /// The factor for the output resolution relative to the UV maps.
+    /// The output uses `4 * scale^2` bytes per pixel of the UV maps,
+    /// so a scale of 4 requires roughly 100 MB for the output alone.
+    pub scale: u32,
+    /// Render at `supersample` times the output resolution and average down to the output resolution.
+    /// A value of 1 disables supersampling.
+    pub supersample: u32,
+    /// The gamma used to linearize colors before alpha blending.
+    /// Defaults to 2.2, and a value of 1.0 blends without any gamma correction.
+    pub blend_gamma: f32,
+    /// An optional color for the background instead of leaving it transparent.
+    pub background: Option<Rgba<u8>>,
+    /// Skip all of the second skin layers unless overridden for a specific layer.
+    pub disable_overlays: bool,
+    /// Flip the render horizontally to face the opposite direction.
+    /// The left arm is drawn in front of the body instead of the right arm.
+    pub mirror: bool,
+    /// Output colors premultiplied by alpha instead of straight alpha.
+    /// PNG files and most image editors expect straight alpha, so this should usually be `false`.
+    /// Enable this for compositing programs and game engines that expect premultiplied textures
+    /// to avoid dark fringes around the edges of the render.
+    pub premultiply: bool,
+    /// Override detecting the head overlay from the skin.
+    /// `Some(true)` always draws the layer, `Some(false)` never draws the layer,
+    /// and `None` only draws the layer if the skin has pixels in the layer's region.
+    pub hat: Option<bool>,
+    /// Override detecting the chest overlay from the skin.
+    pub jacket: Option<bool>,
+    /// Override detecting the left arm overlay from the skin.
+    pub left_sleeve: Option<bool>,
+    /// Override detecting the right arm overlay from the skin.
+    pub right_sleeve: Option<bool>,
+    /// Override detecting the left leg overlay from the skin.
+    pub left_pants: Option<bool>,
+    /// Override detecting the right leg overlay from the skin.
+    pub right_pants: Option<bool>,
+    /// The minimum alpha for a pixel to count when detecting overlays.
+    pub overlay_min_alpha: u8,
+    /// The number of pixels with at least [RenderOptions::overlay_min_alpha]
+    /// required to draw an overlay layer.
+    /// Higher values ignore stray pixels in the second skin layer.
+    pub overlay_min_count: u32,
+    /// The side of the model facing the camera.
+    pub pose: Pose,
+    /// Bilinearly interpolate the UV maps for renders with a [RenderOptions::scale]
+    /// or [RenderOptions::supersample] above 1 instead of repeating each texel of the UV map.
+    /// This reduces stair stepping along diagonal edges between skin pixels.
+    pub smooth_uvs: bool,
+    /// The pose of the arms and legs.
+    pub limb_pose: LimbPose,
+    /// The factor for the alpha of the overlay layers like the hat and jacket.
+    /// Values below 1.0 make the overlays more transparent, and values above 1.0 make partially transparent overlays more opaque.
+    /// The base layers and cape are unaffected.
+    pub overlay_opacity: f32,
+    /// A custom back to front draw order instead of [DRAW_ORDER] or [MIRRORED_DRAW_ORDER].
+    /// Mirrored renders are flipped after drawing, so a custom order is drawn as is for mirrored renders.
+    pub draw_order: Option<Vec<LayerDraw>>,
+    /// Additional layers like ears drawn in order after the layers in the draw order.
+    pub extras: Vec<ExtraLayer>,
+    /// Draw the texture coordinates from the UV maps as red and green instead of sampling the skin.
+    /// The lighting isn't applied, so the colors show where each pixel samples the skin texture
+    /// for debugging custom UV maps.
+    pub debug_uvs: bool,
+    /// An optional outline color and thickness in pixels of the output drawn behind the model.
+    /// The outline extends the alpha of the render, so it isn't visible with an opaque [RenderOptions::background].
+    pub outline: Option<(Rgba<u8>, u32)>,
+    /// Flip the v texture coordinate from the UV maps, so a v of 0.0 samples the bottom of the skin like OpenGL.
+    /// Disabling this samples the top of the skin for a v of 0.0 like the image's pixel coordinates.
+    /// Defaults to `true`.
+    pub flip_v: bool,
+    /// How texture coordinates from the UV maps outside 0.0 to 1.0 sample the skin texture.
+    /// Defaults to [WrapMode::Clamp].
+    pub wrap_mode: WrapMode,
+    /// The offset in texels subtracted from the texture coordinates before rounding to the nearest texel of the skin.
+    /// An offset of 0.0 rounds to the texel whose top left corner is closest instead of its center.
+    /// Defaults to [DEFAULT_TEXEL_OFFSET].
+    pub texel_offset: f32,
+    /// How the lighting from the UV maps combines with the colors sampled from the skin.
+    pub lighting_mode: LightingMode,
+    /// Only draw these body parts and their overlays like [Layer::Head] for the head and hat.
+    /// Other layers are skipped without checking the skin for overlay pixels.
+    /// `None` draws every layer, and the cape and [RenderOptions::extras] are unaffected.
+    pub only_layers: Option<Vec<Layer>>,
+}
+
+/// Information about a render from [RenderContext::render_with_stats].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RenderStats {
+    /// The number of layers including the cape that drew at least one pixel.
+    pub layers_blended: usize,
+    /// The overlay layers that drew at least one pixel.
+    /// Bit `i` is set for the layer at index `i` in [OVERLAY_REGIONS].
+    pub overlays_present: u8,
+    /// The time to create the render.
+    pub elapsed: Duration,
+}
+
+/// A description of a render for saving alongside the output in asset pipelines.
+/// Serializing the manifest requires the `serde` feature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct RenderManifest {
+    pub skin_width: u32,
+    pub skin_height: u32,
+    /// The model used for the render.
+    pub model: ModelType,
+    /// The overlay layers that drew at least one pixel.
+    pub overlays: OverlayFlags,
+    /// The skin was converted from the legacy 64x32 layout before rendering.
+    pub legacy_converted: bool,
+    /// The skin was color corrected before rendering.
+    pub color_corrected: bool,
+    pub output_width: u32,
+    pub output_height: u32,
+}
+
+/// A value for each of the overlay layers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct OverlayFlags {
+    pub hat: bool,
+    pub jacket: bool,
+    pub left_sleeve: bool,
+    pub right_sleeve: bool,
+    pub left_pants: bool,
+    pub right_pants: bool,
+}
+
+impl RenderManifest {
+    /// Describes the render `output` of `skin_texture` from [RenderContext::render_with_stats].
+    /// Preprocessing isn't part of rendering, so callers should set
+    /// [RenderManifest::legacy_converted] and [RenderManifest::color_corrected]
+    /// and the dimensions of the original skin if the skin was modified.
+    pub fn new(
+        skin_texture: &RgbaImage,
+        output: &RgbaImage,
+        options: &RenderOptions,
+        stats: &RenderStats,
+    ) -> Self {
+        // The bits use the order of the layers in OVERLAY_REGIONS.
+        let present = |i: usize| stats.overlays_present & (1 << i) != 0;
+        Self {
+            skin_width: skin_texture.width(),
+            skin_height: skin_texture.height(),
+            model: if options.slim {
+                ModelType::Slim
+            } else {
+                ModelType::Classic
+            },
+            overlays: OverlayFlags {
+                hat: present(0),
+                jacket: present(1),
+                left_sleeve: present(2),
+                right_sleeve: present(3),
+                left_pants: present(4),
+                right_pants: present(5),
+            },
+            legacy_converted: false,
+            color_corrected: false,
+            output_width: output.width(),
+            output_height: output.height(),
+        }
+    }
+}
+
+/// The side of the model shown in the render.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Pose {
+    /// The Smash Ultimate render pose.
+    #[default]
+    Front,
+    /// A straight on view of the back of the model that samples the back faces of the skin.
+    /// The cape is drawn in front of the body.
+    /// The slim model shares the classic maps for every layer except the arms.
+    Back,
+}
+
+/// The pose of the arms and legs.
+/// The head and chest are the same for each pose.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LimbPose {
+    /// The arms hang straight down like the Smash Ultimate render.
+    #[default]
+    Smash,
+    /// The arms are rotated 12 degrees away from the body at the shoulders,
+    /// and the legs are rotated 4 degrees apart at the hips.
+    Spread,
+}
+
+/// How the lighting from the UV maps combines with the colors sampled from the skin.
+/// The lighting after applying [LayerDraw::lighting_scale] is 1.0 for unchanged colors,
+/// below 1.0 for shadows, and above 1.0 for highlights.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LightingMode {
+    /// Multiply the color by the lighting like the Smash Ultimate render.
+    /// Shadows and highlights are stronger for brighter colors.
+    #[default]
+    Multiply,
+    /// Add the difference of the lighting from 1.0 to the color.
+    /// Shadows and highlights change every color by the same amount.
+    Additive,
+    /// Screen the color with the difference of the highlights from 1.0 and ignore shadows.
+    /// Highlights brighten darker colors more without exceeding white.
+    Screen,
+}
+
+impl LightingMode {
+    fn apply(self, color: f32, lighting: f32) -> f32 {
+        match self {
+            LightingMode::Multiply => color * lighting,
+            LightingMode::Additive => (color + lighting - 1f32).max(0f32),
+            LightingMode::Screen => {
+                let highlight = (lighting - 1f32).max(0f32);
+                1f32 - (1f32 - color) * (1f32 - highlight)
+            }
+        }
+    }
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            slim: false,
+            cape: None,
+            scale: 1,
+            supersample: 1,
+            blend_gamma: DEFAULT_GAMMA,
+            background: None,
+            disable_overlays: false,
+            mirror: false,
+            premultiply: false,
+            hat: None,
+            jacket: None,
+            left_sleeve: None,
+            right_sleeve: None,
+            left_pants: None,
+            right_pants: None,
+            overlay_min_alpha: 1,
+            overlay_min_count: 1,
+            pose: Pose::Front,
+            smooth_uvs: false,
+            limb_pose: LimbPose::Smash,
+            draw_order: None,
+            overlay_opacity: 1.0f32,
+            extras: Vec::new(),
+            debug_uvs: false,
+            outline: None,
+            flip_v: true,
+            wrap_mode: WrapMode::Clamp,
+            texel_offset: DEFAULT_TEXEL_OFFSET,
+            lighting_mode: LightingMode::Multiply,
+            only_layers: None,
+        }
+    }
+}
+
+impl RenderOptions {
+    /// Creates a builder starting from the default options.
+    pub fn builder() -> RenderOptionsBuilder {
+        RenderOptionsBuilder::default()
+    }
+
+    /// The layers to draw from back to front.
+    fn layers_to_draw(&self, skin_texture: &RgbaImage) -> Vec<LayerDraw> {
+        self.draw_order()
+            .iter()
+            .filter(|draw| self.includes_layer(draw.layer))
+            .filter(|draw| self.should_draw(draw, skin_texture))
+            .copied()
+            .collect()
+    }
+
+    fn draw_order(&self) -> &[LayerDraw] {
+        // Mirrored renders are flipped afterwards, so draw the left arm in front instead.
+        match &self.draw_order {
+            Some(draw_order) => draw_order,
+            None if self.mirror => &MIRRORED_DRAW_ORDER,
+            None => &DRAW_ORDER,
+        }
+    }
+
+    fn includes_layer(&self, layer: Layer) -> bool {
+        // Overlays share the region id of their base layer.
+        self.only_layers.as_ref().is_none_or(|only_layers| {
+            only_layers
+                .iter()
+                .any(|only| only.region_id() == layer.region_id())
+        })
+    }
+
+    fn should_draw(&self, draw: &LayerDraw, skin_texture: &RgbaImage) -> bool {
+        // Skip costly image loading and blending for regions with fully transparent pixels.
+        // The overlay detection can be overridden for skins with stray overlay pixels.
+        // Overrides for a specific layer take priority over disabling all overlays.
+        match (self.overlay_override(draw.layer), draw.region) {
+            (Some(should_draw), _) => {
+                log::debug!(
+                    "{:?}: {} by override",
+                    draw.layer,
+                    if should_draw { "drawn" } else { "skipped" }
+                );
+                should_draw
+            }
+            (None, Some(_)) if self.disable_overlays => {
+                log::debug!("{:?}: skipped with overlays disabled", draw.layer);
+                false
+            }
+            (None, Some(region)) => {
+                let has_pixels = has_pixels_in_region(
+                    skin_texture,
+                    region,
+                    self.overlay_min_alpha,
+                    self.overlay_min_count,
+                );
+                log::debug!(
+                    "{:?}: {} with {} visible pixels in its region",
+                    draw.layer,
+                    if has_pixels { "drawn" } else { "skipped" },
+                    if has_pixels { "enough" } else { "too few" }
+                );
+                has_pixels
+            }
+            (None, None) => true,
+        }
+    }
+
+    fn layer_opacity(&self, layer: Layer) -> f32 {
+        if layer.is_overlay() {
+            self.overlay_opacity
+        } else {
+            1.0f32
+        }
+    }
+
+    fn layer_blend(&self, draw: &LayerDraw) -> LayerBlend {
+        LayerBlend {
+            opacity: self.layer_opacity(draw.layer),
+            lighting_scale: draw.lighting_scale,
+            debug_uvs: self.debug_uvs,
+            flip_v: self.flip_v,
+            wrap_mode: self.wrap_mode,
+            texel_offset: self.texel_offset,
+            lighting_mode: self.lighting_mode,
+        }
+    }
+
+    // The cape and extra layers don't have their own opacity or lighting.
+    fn default_blend(&self) -> LayerBlend {
+        LayerBlend {
+            debug_uvs: self.debug_uvs,
+            flip_v: self.flip_v,
+            wrap_mode: self.wrap_mode,
+            texel_offset: self.texel_offset,
+            lighting_mode: self.lighting_mode,
+            ..LayerBlend::default()
+        }
+    }
+
+    fn set_overlay_override(&mut self, layer: Layer, value: Option<bool>) {
+        match layer {
+            Layer::HeadOverlay => self.hat = value,
+            Layer::ChestOverlay => self.jacket = value,
+            Layer::LeftArmOverlay => self.left_sleeve = value,
+            Layer::RightArmOverlay => self.right_sleeve = value,
+            Layer::LeftLegOverlay => self.left_pants = value,
+            Layer::RightLegOverlay => self.right_pants = value,
+            _ => (),
+        }
+    }
+
+    fn overlay_override(&self, layer: Layer) -> Option<bool> {
+        match layer {
+            Layer::HeadOverlay => self.hat,
+            Layer::ChestOverlay => self.jacket,
+            Layer::LeftArmOverlay => self.left_sleeve,
+            Layer::RightArmOverlay => self.right_sleeve,
+            Layer::LeftLegOverlay => self.left_pants,
+            Layer::RightLegOverlay => self.right_pants,
+            _ => None,
+        }
+    }
+}
+
+/// A builder for [RenderOptions] created with [RenderOptions::builder].
+/// Unlike struct literals, code using the builder still compiles when new options are added.
+#[derive(Debug, Clone, Default)]
+pub struct RenderOptionsBuilder {
+    options: RenderOptions,
+}
+
+macro_rules! option_setters {
+    ($($field:ident: $ty:ty),*) => {
+        $(
+            #[doc = concat!("Sets [RenderOptions::", stringify!($field), "].")]
+            pub fn $field(mut self, $field: $ty) -> Self {
+                self.options.$field = $field;
+                self
+            }
+        )*
+    };
+}
+
+// Optional fields take the value directly since None is already the default.
+macro_rules! optional_option_setters {
+    ($($field:ident: $ty:ty),*) => {
+        $(
+            #[doc = concat!("Sets [RenderOptions::", stringify!($field), "] to `Some(", stringify!($field), ")`.")]
+            pub fn $field(mut self, $field: $ty) -> Self {
+                self.options.$field = Some($field);
+                self
+            }
+        )*
+    };
+}
+
+impl RenderOptionsBuilder {
+    option_setters!(
+        slim: bool,
+        scale: u32,
+        supersample: u32,
+        blend_gamma: f32,
+        disable_overlays: bool,
+        mirror: bool,
+        premultiply: bool,
+        overlay_min_alpha: u8,
+        overlay_min_count: u32,
+        pose: Pose,
+        smooth_uvs: bool,
+        limb_pose: LimbPose,
+        overlay_opacity: f32,
+        extras: Vec<ExtraLayer>,
+        debug_uvs: bool,
+        flip_v: bool,
+        wrap_mode: WrapMode,
+        texel_offset: f32,
+        lighting_mode: LightingMode
+    );
+
+    optional_option_setters!(
+        cape: RgbaImage,
+        background: Rgba<u8>,
+        hat: bool,
+        jacket: bool,
+        left_sleeve: bool,
+        right_sleeve: bool,
+        left_pants: bool,
+        right_pants: bool,
+        draw_order: Vec<LayerDraw>,
+        outline: (Rgba<u8>, u32),
+        only_layers: Vec<Layer>
+    );
+
+    /// Adds a layer to the end of [RenderOptions::extras].
+    pub fn extra(mut self, extra: ExtraLayer) -> Self {
+        self.options.extras.push(extra);
+        self
+    }
+
+    /// Returns the configured options.
+    pub fn build(self) -> RenderOptions {
+        self.options
+    }
+}
+
+const DEFAULT_GAMMA: f32 = 2.2f32;
+
+type UvMap = ImageBuffer<Rgba<u16>, Vec<u16>>;
+
+type OutputImage<P> = ImageBuffer<P, Vec<<P as Pixel>::Subpixel>>;
+
+/// A pixel format for render outputs.
+/// Blending works with gamma encoded values from 0.0 to 1.0 regardless of how the pixel stores them.
+trait OutputPixel: Pixel + 'static {
+    fn to_encoded(&self, gamma: f32) -> (f32, f32, f32, f32);
+    fn from_encoded(r: f32, g: f32, b: f32, a: f32, gamma: f32) -> Self;
+}
+
+impl OutputPixel for Rgba<u8> {
+    fn to_encoded(&self, _gamma: f32) -> (f32, f32, f32, f32) {
+        normalize_rgba_u8(self)
+    }
+
+    fn from_encoded(r: f32, g: f32, b: f32, a: f32, _gamma: f32) -> Self {
+        Rgba([
+            to_u8_clamped(r),
+            to_u8_clamped(g),
+            to_u8_clamped(b),
+            to_u8_clamped(a),
+        ])
+    }
+}
+
+/// A pixel format for skin textures.
+/// Skin textures store gamma encoded colors for any bit depth, so 16 bit skins only add precision.
+trait SkinPixel: Pixel + 'static {
+    fn to_normalized(&self) -> (f32, f32, f32, f32);
+
+    // Overlay detection uses 8 bit alpha thresholds.
+    fn to_rgba8(image: &OutputImage<Self>) -> Cow<'_, RgbaImage>;
+}
+
+impl SkinPixel for Rgba<u8> {
+    fn to_normalized(&self) -> (f32, f32, f32, f32) {
+        normalize_rgba_u8(self)
+    }
+
+    fn to_rgba8(image: &RgbaImage) -> Cow<'_, RgbaImage> {
+        Cow::Borrowed(image)
+    }
+}
+
+impl SkinPixel for Rgba<u16> {
+    fn to_normalized(&self) -> (f32, f32, f32, f32) {
+        normalize_rgba_u16(self)
+    }
+
+    fn to_rgba8(image: &UvMap) -> Cow<'_, RgbaImage> {
+        Cow::Owned(DynamicImage::ImageRgba16(image.clone()).into_rgba8())
+    }
+}
+
+// 16 bit outputs store linear colors, so converting back to 8 bit requires gamma encoding.
+impl OutputPixel for Rgba<u16> {
+    fn to_encoded(&self, gamma: f32) -> (f32, f32, f32, f32) {
+        let (r, g, b, a) = normalize_rgba_u16(self);
+        let encode = |c: f32| c.powf(1.0f32 / gamma);
+        (encode(r), encode(g), encode(b), a)
+    }
+
+    fn from_encoded(r: f32, g: f32, b: f32, a: f32, gamma: f32) -> Self {
+        let decode = |c: f32| to_u16_clamped(c.max(0f32).powf(gamma));
+        Rgba([decode(r), decode(g), decode(b), to_u16_clamped(a)])
+    }
+}
+
+// Float outputs store linear colors without clamping, so lighting can push values above 1.0.
+impl OutputPixel for Rgba<f32> {
+    fn to_encoded(&self, gamma: f32) -> (f32, f32, f32, f32) {
+        let encode = |c: f32| c.max(0f32).powf(1.0f32 / gamma);
+        (encode(self[0]), encode(self[1]), encode(self[2]), self[3])
+    }
+
+    fn from_encoded(r: f32, g: f32, b: f32, a: f32, gamma: f32) -> Self {
+        let decode = |c: f32| c.max(0f32).powf(gamma);
+        Rgba([decode(r), decode(g), decode(b), a.clamp(0f32, 1f32)])
+    }
+}
+
+// Identifies cache files from RenderContext::save_cache.
+// Increment the version when changing the layout or the embedded UV maps.
+const CACHE_MAGIC: &[u8; 8] = b"MCUVMAP\0";
+const CACHE_VERSION: u32 = 1;
+
+fn invalid_cache(message: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
+
+fn read_cache_bytes<'a>(bytes: &mut &'a [u8], len: usize) -> std::io::Result<&'a [u8]> {
+    if bytes.len() < len {
+        return Err(invalid_cache("unexpected end of UV map cache"));
+    }
+    let (value, remaining) = bytes.split_at(len);
+    *bytes = remaining;
+    Ok(value)
+}
+
+fn read_cache_u32(bytes: &mut &[u8]) -> std::io::Result<u32> {
+    let value = read_cache_bytes(bytes, 4)?;
+    Ok(u32::from_le_bytes([value[0], value[1], value[2], value[3]]))
+}
+
+// At least 16 bit precision is required for the texture sampling to look decent.
+fn load_rgba_u16(buffer: &[u8]) -> UvMap {
+    match image::load_from_memory(buffer).unwrap() {
+        DynamicImage::ImageRgba16(image_buffer) => image_buffer,
+        _ => panic!("Expected RGBA 16 bit for UVs"),
+    }
+}
+
+// The maps for each pose and then each model.
+// Loads a map from `path` that replaces the embedded map `embedded_bytes`.
+fn load_uv_map_file(path: &Path, embedded_bytes: &[u8]) -> Result<Option<UvMap>, UvMapError> {
+    let map = match load_packed_or_split_map(path)? {
+        Some(map) => map,
+        None => return Ok(None),
+    };
+    let expected = embedded_dimensions(embedded_bytes);
+    if map.dimensions() != expected {
+        return Err(UvMapError::Dimensions(
+            path.to_path_buf(),
+            expected,
+            map.dimensions(),
+        ));
+    }
+    Ok(Some(map))
+}
+
+fn load_packed_or_split_map(path: &Path) -> Result<Option<UvMap>, UvMapError> {
+    if path.exists() {
+        return open_uv_map_image(path).and_then(|image| match image {
+            DynamicImage::ImageRgba16(image_buffer) => Ok(Some(image_buffer)),
+            image => Err(UvMapError::Format(path.to_path_buf(), image.color())),
+        });
+    }
+
+    // Fall back to separate files like head_uv.png and head_lighting.png.
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let uv_path = path.with_file_name(format!("{}_uv.png", stem));
+    let lighting_path = path.with_file_name(format!("{}_lighting.png", stem));
+    match (uv_path.exists(), lighting_path.exists()) {
+        (false, false) => Ok(None),
+        (true, false) => Err(UvMapError::MissingSplitMap(lighting_path)),
+        (false, true) => Err(UvMapError::MissingSplitMap(uv_path)),
+        (true, true) => {
+            let uv_map = match open_uv_map_image(&uv_path)? {
+                DynamicImage::ImageRgba16(image_buffer) => image_buffer,
+                image => return Err(UvMapError::Format(uv_path, image.color())),
+            };
+            let lighting = match open_uv_map_image(&lighting_path)? {
+                image @ (DynamicImage::ImageLuma8(_) | DynamicImage::ImageLuma16(_)) => {
+                    image.into_luma16()
+                }
+                image => return Err(UvMapError::Format(lighting_path, image.color())),
+            };
+            if lighting.dimensions() != uv_map.dimensions() {
+                return Err(UvMapError::Dimensions(
+                    lighting_path,
+                    uv_map.dimensions(),
+                    lighting.dimensions(),
+                ));
+            }
+            Ok(Some(merge_uv_lighting(&uv_map, &lighting)))
+        }
+    }
+}
+
+// Reads the dimensions from the PNG header without decoding the embedded map.
+fn embedded_dimensions(bytes: &[u8]) -> (u32, u32) {
+    image::io::Reader::with_format(std::io::Cursor::new(bytes), image::ImageFormat::Png)
+        .into_dimensions()
+        .unwrap()
+}
+
+fn open_uv_map_image(path: &Path) -> Result<DynamicImage, UvMapError> {
+    image::open(path).map_err(|e| UvMapError::Image(path.to_path_buf(), e))
+}
+
+/// Packs the texture coordinates and alpha from `uv_map` and the values from `lighting`
+/// into a single map with the lighting in the blue channel like the embedded UV maps.
+/// The blue channel of `uv_map` is ignored, so lighting can be baked separately from the UVs.
+/// Both maps must have the same dimensions.
+pub fn merge_uv_lighting(
+    uv_map: &ImageBuffer<Rgba<u16>, Vec<u16>>,
+    lighting: &ImageBuffer<Luma<u16>, Vec<u16>>,
+) -> ImageBuffer<Rgba<u16>, Vec<u16>> {
+    assert_eq!(uv_map.dimensions(), lighting.dimensions());
+    let mut merged = uv_map.clone();
+    for (pixel, light) in merged.pixels_mut().zip(lighting.pixels()) {
+        pixel[2] = light[0];
+    }
+    merged
+}
+
+macro_rules! uv_map_bytes {
+    ($file_name:literal) => {
+        [
+            [
+                &include_bytes!(concat!("../images/uv_lighting_alpha/", $file_name))[..],
+                &include_bytes!(concat!("../images/uv_lighting_alpha/alex/", $file_name))[..],
+            ],
+            [
+                &include_bytes!(concat!("../images/uv_lighting_alpha/back/", $file_name))[..],
+                &include_bytes!(concat!(
+                    "../images/uv_lighting_alpha/alex/back/",
+                    $file_name
+                ))[..],
+            ],
+        ]
+    };
+    // Only the arms have separate back maps for the Alex model,
+    // so both models share the Steve back maps for the other layers.
+    (shared_back $file_name:literal) => {
+        [
+            [
+                &include_bytes!(concat!("../images/uv_lighting_alpha/", $file_name))[..],
+                &include_bytes!(concat!("../images/uv_lighting_alpha/alex/", $file_name))[..],
+            ],
+            [
+                &include_bytes!(concat!("../images/uv_lighting_alpha/back/", $file_name))[..],
+                &include_bytes!(concat!("../images/uv_lighting_alpha/back/", $file_name))[..],
+            ],
+        ]
+    };
+}
+
+fn select_uv_map_bytes(bytes: [[&'static [u8]; 2]; 2], is_alex: bool, pose: Pose) -> &'static [u8] {
+    bytes[pose as usize][is_alex as usize]
+}
+
+fn cape_uv_map_bytes(is_alex: bool, pose: Pose) -> &'static [u8] {
+    select_uv_map_bytes(uv_map_bytes!(shared_back "cape.png"), is_alex, pose)
+}
+
+/// A part of the model with its own UV map.
+/// The overlay layers use the second layer of the skin texture like the hat or jacket.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Layer {
+    Head,
+    HeadOverlay,
+    Chest,
+    ChestOverlay,
+    LeftArm,
+    LeftArmOverlay,
+    RightArm,
+    RightArmOverlay,
+    LeftLeg,
+    LeftLegOverlay,
+    RightLeg,
+    RightLegOverlay,
+}
+
+const LAYER_COUNT: usize = 12;
+
+const LAYERS: [Layer; LAYER_COUNT] = [
+    Layer::Head,
+    Layer::HeadOverlay,
+    Layer::Chest,
+    Layer::ChestOverlay,
+    Layer::LeftArm,
+    Layer::LeftArmOverlay,
+    Layer::RightArm,
+    Layer::RightArmOverlay,
+    Layer::LeftLeg,
+    Layer::LeftLegOverlay,
+    Layer::RightLeg,
+    Layer::RightLegOverlay,
+];
+
+/// The value in the region map from [create_render_with_ids] for pixels last drawn by the cape.
+pub const CAPE_REGION_ID: u8 = 7;
+
+/// The value in the region map from [create_render_with_ids] for pixels last drawn by an [ExtraLayer].
+pub const EXTRA_REGION_ID: u8 = 8;
+
+/// The region of the skin texture used by each overlay layer.
+/// Overlay layers are only drawn if the skin has pixels in their region
+/// unless overridden in [RenderOptions].
+pub const OVERLAY_REGIONS: [(Layer, Region); 6] = [
+    (Layer::HeadOverlay, HEAD_OVERLAY_REGION),
+    (Layer::ChestOverlay, CHEST_OVERLAY_REGION),
+    (Layer::LeftArmOverlay, LEFT_ARM_OVERLAY_REGION),
+    (Layer::RightArmOverlay, RIGHT_ARM_OVERLAY_REGION),
+    (Layer::LeftLegOverlay, LEFT_LEG_OVERLAY_REGION),
+    (Layer::RightLegOverlay, RIGHT_LEG_OVERLAY_REGION),
+];
+
+const HEAD_OVERLAY_REGION: Region = Region::new(0.5, 1.0, 0.0, 0.25);
+const CHEST_OVERLAY_REGION: Region = Region::new(0.25, 0.625, 0.5, 0.75);
+const LEFT_ARM_OVERLAY_REGION: Region = Region::new(0.75, 1.0, 0.75, 1.0);
+const RIGHT_ARM_OVERLAY_REGION: Region = Region::new(0.625, 0.875, 0.5, 0.75);
+const LEFT_LEG_OVERLAY_REGION: Region = Region::new(0.0, 0.25, 0.75, 1.0);
+const RIGHT_LEG_OVERLAY_REGION: Region = Region::new(0.0, 0.25, 0.5, 0.75);
+
+/// A layer in a draw order and the region of the skin texture that decides if the layer is drawn.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LayerDraw {
+    pub layer: Layer,
+    /// Only draw the layer if the skin has visible pixels in this region.
+    /// `None` always draws the layer.
+    /// The overlay overrides in [RenderOptions] take priority over the region.
+    pub region: Option<Region>,
+    /// The factor for the lighting from the UV maps when blending the layer.
+    /// Higher values brighten the layer. Defaults to [DEFAULT_LIGHTING_SCALE].
+    pub lighting_scale: f32,
+}
+
+/// The default [LayerDraw::lighting_scale] for all layers.
+/// The lighting pass is scaled down by a factor of 0.25 to fit into 8 bits per channel.
+/// Multiplying by 4 is a bit too bright, so use 2 instead.
+pub const DEFAULT_LIGHTING_SCALE: f32 = 2f32;
+
+impl LayerDraw {
+    /// Always draws `layer`.
+    pub const fn always(layer: Layer) -> Self {
+        Self {
+            layer,
+            region: None,
+            lighting_scale: DEFAULT_LIGHTING_SCALE,
+        }
+    }
+
+    /// Draws `layer` only if the skin has visible pixels in `region`.
+    pub const fn gated(layer: Layer, region: Region) -> Self {
+        Self {
+            layer,
+            region: Some(region),
+            lighting_scale: DEFAULT_LIGHTING_SCALE,
+        }
+    }
+
+    /// Uses `lighting_scale` instead of [DEFAULT_LIGHTING_SCALE] for the layer.
+    pub const fn with_lighting_scale(self, lighting_scale: f32) -> Self {
+        Self {
+            lighting_scale,
+            ..self
+        }
+    }
+}
+
+/// An additional layer like the ears for deadmau5 style skins with its own UV map.
+/// Extra layers are drawn on top of the model in the order they appear in [RenderOptions::extras].
+#[derive(Debug, Clone)]
+pub struct ExtraLayer {
+    /// The UV map for the layer using the same format and dimensions as the maps for the render's pose.
+    /// Layers with other dimensions are skipped.
+    pub uv_map: ImageBuffer<Rgba<u16>, Vec<u16>>,
+    /// Only draw the layer if the skin has visible pixels in this region.
+    /// `None` always draws the layer.
+    pub region: Option<Region>,
+}
+
+/// Statistics about the pixels of a UV map from [uv_coverage].
+/// The ranges are the smallest and largest normalized values for the covered pixels
+/// or `None` if the map has no covered pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Coverage {
+    /// The number of pixels with a nonzero alpha in the map.
+    pub pixel_count: u32,
+    /// The range of horizontal texture coordinates referenced by the map.
+    pub u_range: Option<(f32, f32)>,
+    /// The range of vertical texture coordinates referenced by the map.
+    pub v_range: Option<(f32, f32)>,
+    /// The range of lighting values before applying [LayerDraw::lighting_scale].
+    pub lighting_range: Option<(f32, f32)>,
+}
+
+/// Calculates which parts of the skin texture and what lighting values are used by `uv_map`.
+/// This is useful for checking that custom UV maps like [ExtraLayer::uv_map] reference the expected texture regions.
+pub fn uv_coverage(uv_map: &ImageBuffer<Rgba<u16>, Vec<u16>>) -> Coverage {
+    // Pixels with zero alpha are skipped when rendering, so their other channels don't matter.
+    let include = |range: Option<(f32, f32)>, value: f32| match range {
+        Some((min, max)) => Some((min.min(value), max.max(value))),
+        None => Some((value, value)),
+    };
+
+    let mut coverage = Coverage {
+        pixel_count: 0,
+        u_range: None,
+        v_range: None,
+        lighting_range: None,
+    };
+    for pixel in uv_map.pixels().filter(|p| p[3] > 0u16) {
+        let (u, v, lighting, _) = normalize_rgba_u16(pixel);
+        coverage.pixel_count += 1;
+        coverage.u_range = include(coverage.u_range, u);
+        coverage.v_range = include(coverage.v_range, v);
+        coverage.lighting_range = include(coverage.lighting_range, lighting);
+    }
+    coverage
+}
+
+/// The default order for drawing layers from back to front.
+/// Steve has simple geometry, so sorting the layers works instead of a depth map.
+/// The left arm is behind the body, and the right arm is in front of the body.
+/// The base layers are always drawn, and each overlay is drawn just after its base layer.
+/// The right arm and its overlay are drawn last, so they cover the chest and jacket where they overlap.
+pub const DRAW_ORDER: [LayerDraw; LAYER_COUNT] = [
+    LayerDraw::always(Layer::LeftArm),
+    LayerDraw::gated(Layer::LeftArmOverlay, LEFT_ARM_OVERLAY_REGION),
+    LayerDraw::always(Layer::LeftLeg),
+    LayerDraw::gated(Layer::LeftLegOverlay, LEFT_LEG_OVERLAY_REGION),
+    LayerDraw::always(Layer::RightLeg),
+    LayerDraw::gated(Layer::RightLegOverlay, RIGHT_LEG_OVERLAY_REGION),
+    LayerDraw::always(Layer::Chest),
+    LayerDraw::gated(Layer::ChestOverlay, CHEST_OVERLAY_REGION),
+    LayerDraw::always(Layer::Head),
+    LayerDraw::gated(Layer::HeadOverlay, HEAD_OVERLAY_REGION),
+    LayerDraw::always(Layer::RightArm),
+    LayerDraw::gated(Layer::RightArmOverlay, RIGHT_ARM_OVERLAY_REGION),
+];
+
+/// The order for drawing layers with [RenderOptions::mirror].
+/// This matches [DRAW_ORDER] with the arms swapped, so the left arm ends up in front after flipping.
+pub const MIRRORED_DRAW_ORDER: [LayerDraw; LAYER_COUNT] = [
+    LayerDraw::always(Layer::RightArm),
+    LayerDraw::gated(Layer::RightArmOverlay, RIGHT_ARM_OVERLAY_REGION),
+    LayerDraw::always(Layer::LeftLeg),
+    LayerDraw::gated(Layer::LeftLegOverlay, LEFT_LEG_OVERLAY_REGION),
+    LayerDraw::always(Layer::RightLeg),
+    LayerDraw::gated(Layer::RightLegOverlay, RIGHT_LEG_OVERLAY_REGION),
+    LayerDraw::always(Layer::Chest),
+    LayerDraw::gated(Layer::ChestOverlay, CHEST_OVERLAY_REGION),
+    LayerDraw::always(Layer::Head),
+    LayerDraw::gated(Layer::HeadOverlay, HEAD_OVERLAY_REGION),
+    LayerDraw::always(Layer::LeftArm),
+    LayerDraw::gated(Layer::LeftArmOverlay, LEFT_ARM_OVERLAY_REGION),
+];
+
+impl Layer {
+    /// The value in the region map from [create_render_with_ids] for pixels last drawn by this layer.
+    /// Overlay layers use the same value as their base layer, and 0 is used for the background.
+    pub fn region_id(self) -> u8 {
+        match self {
+            Layer::Head | Layer::HeadOverlay => 1,
+            Layer::Chest | Layer::ChestOverlay => 2,
+            Layer::LeftArm | Layer::LeftArmOverlay => 3,
+            Layer::RightArm | Layer::RightArmOverlay => 4,
+            Layer::LeftLeg | Layer::LeftLegOverlay => 5,
+            Layer::RightLeg | Layer::RightLegOverlay => 6,
+        }
+    }
+
+    // The file names for loading maps from a directory should match the embedded maps.
+    fn file_name(self) -> &'static str {
+        match self {
+            Layer::Head => "head.png",
+            Layer::HeadOverlay => "head2.png",
+            Layer::Chest => "chest.png",
+            Layer::ChestOverlay => "chest2.png",
+            Layer::LeftArm => "arm_l.png",
+            Layer::LeftArmOverlay => "arm_l2.png",
+            Layer::RightArm => "arm_r.png",
+            Layer::RightArmOverlay => "arm_r2.png",
+            Layer::LeftLeg => "leg_l.png",
+            Layer::LeftLegOverlay => "leg_l2.png",
+            Layer::RightLeg => "leg_r.png",
+            Layer::RightLegOverlay => "leg_r2.png",
+        }
+    }
+
+    fn is_overlay(self) -> bool {
+        matches!(
+            self,
+            Layer::HeadOverlay
+                | Layer::ChestOverlay
+                | Layer::LeftArmOverlay
+                | Layer::RightArmOverlay
+                | Layer::LeftLegOverlay
+                | Layer::RightLegOverlay
+        )
+    }
+
+    fn is_limb(self) -> bool {
+        !matches!(
+            self,
+            Layer::Head | Layer::HeadOverlay | Layer::Chest | Layer::ChestOverlay
+        )
+    }
+
+    fn uv_map_bytes(self, is_alex: bool, pose: Pose, limb_pose: LimbPose) -> &'static [u8] {
+        let bytes = match (self, limb_pose) {
+            (Layer::LeftArm, LimbPose::Spread) => uv_map_bytes!("spread/arm_l.png"),
+            (Layer::LeftArmOverlay, LimbPose::Spread) => uv_map_bytes!("spread/arm_l2.png"),
+            (Layer::RightArm, LimbPose::Spread) => uv_map_bytes!("spread/arm_r.png"),
+            (Layer::RightArmOverlay, LimbPose::Spread) => uv_map_bytes!("spread/arm_r2.png"),
+            (Layer::LeftLeg, LimbPose::Spread) => uv_map_bytes!(shared_back "spread/leg_l.png"),
+            (Layer::LeftLegOverlay, LimbPose::Spread) => {
+                uv_map_bytes!(shared_back "spread/leg_l2.png")
+            }
+            (Layer::RightLeg, LimbPose::Spread) => uv_map_bytes!(shared_back "spread/leg_r.png"),
+            (Layer::RightLegOverlay, LimbPose::Spread) => {
+                uv_map_bytes!(shared_back "spread/leg_r2.png")
+            }
+            (Layer::Head, _) => uv_map_bytes!(shared_back "head.png"),
+            (Layer::HeadOverlay, _) => uv_map_bytes!(shared_back "head2.png"),
+            (Layer::Chest, _) => uv_map_bytes!(shared_back "chest.png"),
+            (Layer::ChestOverlay, _) => uv_map_bytes!(shared_back "chest2.png"),
+            (Layer::LeftArm, _) => uv_map_bytes!("arm_l.png"),
+            (Layer::LeftArmOverlay, _) => uv_map_bytes!("arm_l2.png"),
+            (Layer::RightArm, _) => uv_map_bytes!("arm_r.png"),
+            (Layer::RightArmOverlay, _) => uv_map_bytes!("arm_r2.png"),
+            (Layer::LeftLeg, _) => uv_map_bytes!(shared_back "leg_l.png"),
+            (Layer::LeftLegOverlay, _) => uv_map_bytes!(shared_back "leg_l2.png"),
+            (Layer::RightLeg, _) => uv_map_bytes!(shared_back "leg_r.png"),
+            (Layer::RightLegOverlay, _) => uv_map_bytes!(shared_back "leg_r2.png"),
+        };
+        select_uv_map_bytes(bytes, is_alex, pose)
+    }
+}
+
+/// Decoded UV maps that can be reused for rendering multiple skins.
+/// Each map is only decoded the first time it's needed,
+/// so skins without overlays skip the cost of decoding the overlay maps.
+#[derive(Debug, Default)]
+pub struct RenderContext {
+    steve: ModelUvMaps,
+    alex: ModelUvMaps,
+    steve_back: ModelUvMaps,
+    alex_back: ModelUvMaps,
+}
+
+#[derive(Debug, Default)]
+struct ModelUvMaps {
+    layers: [OnceLock<UvMap>; LAYER_COUNT],
+    // Only the limb layers have maps for other limb poses.
+    spread_layers: [OnceLock<UvMap>; LAYER_COUNT],
+    cape: OnceLock<UvMap>,
+}
+
+impl RenderContext {
+    /// Creates a new context without decoding any UV maps.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new context that uses the UV maps in `dir` instead of the embedded UV maps.
+    /// The directory uses the same layout and file names as `images/uv_lighting_alpha`
+    /// like `head.png`, `alex/head.png`, or `back/cape.png`.
+    /// The embedded UV maps are used for any missing files.
+    /// Each map must be 16 bit RGBA with the same dimensions as the embedded maps.
+    ///
+    /// A map can also be split into a UV map and a grayscale lighting map like `head_uv.png` and `head_lighting.png`.
+    /// The maps are combined like [merge_uv_lighting], and the packed file is used if both are present.
+    ///
+    /// The embedded maps use an origin at the bottom left and texel centers at half a texel.
+    /// Maps made with other conventions or that intentionally tile the skin can be rendered by setting
+    /// [RenderOptions::flip_v], [RenderOptions::texel_offset], or [RenderOptions::wrap_mode].
+    pub fn from_dir<P: AsRef<Path>>(dir: P) -> Result<Self, UvMapError> {
+        let context = Self::new();
+        for pose in [Pose::Front, Pose::Back] {
+            for is_alex in [false, true] {
+                let mut model_dir = dir.as_ref().to_path_buf();
+                if is_alex {
+                    model_dir.push("alex");
+                }
+                if pose == Pose::Back {
+                    model_dir.push("back");
+                }
+
+                let maps = context.model_uv_maps(is_alex, pose);
+                for layer in LAYERS {
+                    let path = model_dir.join(layer.file_name());
+                    let bytes = layer.uv_map_bytes(is_alex, pose, LimbPose::Smash);
+                    if let Some(map) = load_uv_map_file(&path, bytes)? {
+                        let _ = maps.layers[layer as usize].set(map);
+                    }
+                    if layer.is_limb() {
+                        let path = model_dir.join("spread").join(layer.file_name());
+                        let bytes = layer.uv_map_bytes(is_alex, pose, LimbPose::Spread);
+                        if let Some(map) = load_uv_map_file(&path, bytes)? {
+                            let _ = maps.spread_layers[layer as usize].set(map);
+                        }
+                    }
+                }
+                let path = model_dir.join("cape.png");
+                if let Some(map) = load_uv_map_file(&path, cape_uv_map_bytes(is_alex, pose))? {
+                    let _ = maps.cape.set(map);
+                }
+            }
+        }
+        Ok(context)
+    }
+
+    /// Saves the decoded UV maps to `path` as raw 16 bit values for loading with [RenderContext::load_cache].
+    /// Only maps that have already been decoded are saved, so render with the needed options first.
+    /// Each map uses roughly 13 MB, so the file is much larger than the embedded PNG files.
+    pub fn save_cache<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let maps: Vec<_> = self
+            .uv_map_slots()
+            .into_iter()
+            .enumerate()
+            .filter_map(|(index, (slot, _))| slot.get().map(|map| (index as u32, map)))
+            .collect();
+
+        let mut writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+        writer.write_all(CACHE_MAGIC)?;
+        writer.write_all(&CACHE_VERSION.to_le_bytes())?;
+        writer.write_all(&(maps.len() as u32).to_le_bytes())?;
+        for (index, map) in maps {
+            for value in [index, map.width(), map.height()] {
+                writer.write_all(&value.to_le_bytes())?;
+            }
+            let bytes: Vec<u8> = map.as_raw().iter().flat_map(|v| v.to_le_bytes()).collect();
+            writer.write_all(&bytes)?;
+        }
+        writer.flush()
+    }
+
+    /// Creates a new context with the UV maps saved by [RenderContext::save_cache].
+    /// This skips decoding the PNG files, which is most of the startup time when rendering a single skin.
+    /// The embedded UV maps are used for any maps missing from the cache.
+    /// Caches from a different version of the library may be rejected, so save a new cache after updating.
+    pub fn load_cache<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let file = std::fs::read(path)?;
+        let mut bytes = &file[..];
+        if read_cache_bytes(&mut bytes, CACHE_MAGIC.len())? != CACHE_MAGIC {
+            return Err(invalid_cache("not a UV map cache"));
+        }
+        if read_cache_u32(&mut bytes)? != CACHE_VERSION {
+            return Err(invalid_cache("unsupported UV map cache version"));
+        }
+
+        let context = Self::new();
+        let slots = context.uv_map_slots();
+        let map_count = read_cache_u32(&mut bytes)?;
+        for _ in 0..map_count {
+            let index = read_cache_u32(&mut bytes)? as usize;
+            let width = read_cache_u32(&mut bytes)?;
+            let height = read_cache_u32(&mut bytes)?;
+            let (slot, embedded_bytes) = slots
+                .get(index)
+                .ok_or_else(|| invalid_cache("invalid UV map index in cache"))?;
+            if (width, height) != embedded_dimensions(embedded_bytes) {
+                return Err(invalid_cache("invalid UV map dimensions in cache"));
+            }
+
+            let len = width as usize * height as usize * 4;
+            let values = read_cache_bytes(&mut bytes, len * 2)?
+                .chunks_exact(2)
+                .map(|v| u16::from_le_bytes([v[0], v[1]]))
+                .collect();
+            let map = UvMap::from_raw(width, height, values)
+                .ok_or_else(|| invalid_cache("invalid UV map in cache"))?;
+            let _ = slot.set(map);
+        }
+        Ok(context)
+    }
+
+    /// Creates a Smash Ultimate Minecraft inspired render from the given Minecraft skin texture
+    /// using the settings in `options`.
+    pub fn render(&self, skin_texture: &RgbaImage, options: &RenderOptions) -> RgbaImage {
+        self.render_supersampled(skin_texture, options, &mut |_, _| {})
+            .0
+    }
+
+    /// Creates a render like [RenderContext::render] and information about the layers and time to render.
+    /// The time uses [Instant], which panics on `wasm32-unknown-unknown`.
+    pub fn render_with_stats(
+        &self,
+        skin_texture: &RgbaImage,
+        options: &RenderOptions,
+    ) -> (RgbaImage, RenderStats) {
+        let start_time = Instant::now();
+        let (output, stats) = self.render_supersampled(skin_texture, options, &mut |_, _| {});
+        let elapsed = start_time.elapsed();
+        log::info!("Rendered {} layers in {:?}", stats.layers_blended, elapsed);
+        (output, RenderStats { elapsed, ..stats })
+    }
+
+    /// Creates a render like [RenderContext::render] in `output` to reuse its allocation.
+    /// `output` is cleared first and is resized if it doesn't match the render's dimensions.
+    /// Supersampled renders still allocate the larger buffer for drawing.
+    pub fn render_into(
+        &self,
+        output: &mut RgbaImage,
+        skin_texture: &RgbaImage,
+        options: &RenderOptions,
+    ) {
+        if options.supersample > 1 {
+            *output = self.render(skin_texture, options);
+            return;
+        }
+
+        self.clear_output(output, options);
+        let (rendered, _) = self.render_scaled(
+            std::mem::take(output),
+            skin_texture,
+            options,
+            None,
+            &mut |_, _| {},
+        );
+        *output = finish_render(rendered, options, 1);
+    }
+
+    /// Creates a render like [RenderContext::render]
+    /// and calls `progress` with the fraction of completed layers from 0.0 to 1.0 after drawing each layer.
+    pub fn render_with_progress(
+        &self,
+        skin_texture: &RgbaImage,
+        options: &RenderOptions,
+        progress: &mut dyn FnMut(f32),
+    ) -> RgbaImage {
+        self.render_supersampled(skin_texture, options, &mut |f, _| progress(f))
+            .0
+    }
+
+    /// Creates the intermediate renders after drawing each layer using the settings in `options`.
+    /// The first frame is the empty background, and the last frame matches [RenderContext::render].
+    /// Skipped layers and layers without any visible pixels don't add a frame.
+    pub fn render_layer_frames(
+        &self,
+        skin_texture: &RgbaImage,
+        options: &RenderOptions,
+    ) -> Vec<RgbaImage> {
+        let factor = options.supersample.max(1);
+        let empty = self.new_output(options, options.scale * factor);
+        let mut frames = vec![finish_render(empty, options, factor)];
+        self.render_supersampled::<Rgba<u8>, Rgba<u8>>(skin_texture, options, &mut |_, output| {
+            if let Some(output) = output {
+                frames.push(finish_render(output.clone(), options, factor));
+            }
+        });
+        frames
+    }
+
+    /// Creates a render like [RenderContext::render] with 16 bits per channel of linear color.
+    /// Colors are decoded using [RenderOptions::blend_gamma] instead of being gamma encoded for display.
+    /// This avoids banding when further processing the render.
+    pub fn render_hdr(
+        &self,
+        skin_texture: &RgbaImage,
+        options: &RenderOptions,
+    ) -> ImageBuffer<Rgba<u16>, Vec<u16>> {
+        self.render_supersampled(skin_texture, options, &mut |_, _| {})
+            .0
+    }
+
+    /// Creates a render like [RenderContext::render_hdr] with 32 bit floating point channels.
+    /// Colors aren't clamped, so lit areas can exceed 1.0 for compositing in linear float pipelines.
+    pub fn render_linear(
+        &self,
+        skin_texture: &RgbaImage,
+        options: &RenderOptions,
+    ) -> ImageBuffer<Rgba<f32>, Vec<f32>> {
+        self.render_supersampled(skin_texture, options, &mut |_, _| {})
+            .0
+    }
+
+    /// Creates a render like [RenderContext::render] and the chara images for each of the [chara_slots].
+    /// The chara images use [Interpolation::Bilinear] and fill areas outside the render with the background color.
+    pub fn render_all(&self, skin_texture: &RgbaImage, options: &RenderOptions) -> RenderOutputs {
+        let render = self.render(skin_texture, options);
+        let fill = options.background.unwrap_or(Rgba([0u8, 0u8, 0u8, 0u8]));
+        let charas = chara_slots(Interpolation::Bilinear)
+            .iter()
+            .map(|slot| (slot.name, slot.create(&render, options.scale, fill)))
+            .collect();
+        RenderOutputs { render, charas }
+    }
+
+    /// Creates a render like [RenderContext::render] from a skin texture with 16 bits per channel.
+    /// Skins are sampled at their full precision before blending and converting to 8 bit output.
+    /// See [load_skin_16] for loading skins without converting to 8 bits.
+    pub fn render_16(
+        &self,
+        skin_texture: &ImageBuffer<Rgba<u16>, Vec<u16>>,
+        options: &RenderOptions,
+    ) -> RgbaImage {
+        self.render_supersampled(skin_texture, options, &mut |_, _| {})
+            .0
+    }
+
+    /// Creates a render like [RenderContext::render_hdr] from a skin texture with 16 bits per channel.
+    /// This preserves subtle gradients in HD skins that would otherwise be limited to 256 values per channel.
+    pub fn render_hdr_16(
+        &self,
+        skin_texture: &ImageBuffer<Rgba<u16>, Vec<u16>>,
+        options: &RenderOptions,
+    ) -> ImageBuffer<Rgba<u16>, Vec<u16>> {
+        self.render_supersampled(skin_texture, options, &mut |_, _| {})
+            .0
+    }
+
+    /// Renders each of the skins in parallel using the settings in `options`.
+    /// The outputs are in the same order as `skin_textures`.
+    #[cfg(feature = "rayon")]
+    pub fn render_many(
+        &self,
+        skin_textures: &[RgbaImage],
+        options: &RenderOptions,
+    ) -> Vec<RgbaImage> {
+        skin_textures
+            .par_iter()
+            .map(|skin_texture| self.render(skin_texture, options))
+            .collect()
+    }
+
+    /// Renders each of the skins in parallel and calls `callback` with the skin's index and output.
+    /// Skins are only taken from `skin_textures` as threads become available,
+    /// so at most one skin and output per thread is in memory at once.
+    /// The callback may be called out of order.
+    #[cfg(feature = "rayon")]
+    pub fn render_streaming<I, F>(&self, skin_textures: I, options: &RenderOptions, callback: F)
+    where
+        I: Iterator<Item = RgbaImage> + Send,
+        F: Fn(usize, RgbaImage) + Sync + Send,
+    {
+        skin_textures
+            .enumerate()
+            .par_bridge()
+            .for_each(|(i, skin_texture)| callback(i, self.render(&skin_texture, options)));
+    }
+
+    /// Creates a render like [RenderContext::render] and a map of the body part for each pixel.
+    /// See [Layer::region_id] for the values in the region map.
+    pub fn render_with_ids(
+        &self,
+        skin_texture: &RgbaImage,
+        options: &RenderOptions,
+    ) -> (RgbaImage, GrayImage) {
+        let factor = options.supersample.max(1);
+        let head_uvs = self.uv_map(Layer::Head, options);
+        let scale = options.scale * factor;
+        let mut ids = GrayImage::new(head_uvs.width() * scale, head_uvs.height() * scale);
+
+        let (output, _) = self.render_scaled(
+            self.new_output(options, scale),
+            skin_texture,
+            options,
+            Some(&mut ids),
+            &mut |_, _| {},
+        );
+        if options.mirror {
+            image::imageops::flip_horizontal_in_place(&mut ids);
+        }
+
+        // Averaging ids doesn't make sense, so use the center of each block instead.
+        if factor > 1 {
+            ids = GrayImage::from_fn(ids.width() / factor, ids.height() / factor, |x, y| {
+                *ids.get_pixel(x * factor + factor / 2, y * factor + factor / 2)
+            });
+        }
+        (finish_render(output, options, factor), ids)
+    }
+
+    fn render_supersampled<P: OutputPixel, S: SkinPixel>(
+        &self,
+        skin_texture: &OutputImage<S>,
+        options: &RenderOptions,
+        progress: &mut dyn FnMut(f32, Option<&OutputImage<P>>),
+    ) -> (OutputImage<P>, RenderStats) {
+        let factor = options.supersample.max(1);
+        let (output, stats) = self.render_scaled(
+            self.new_output(options, options.scale * factor),
+            skin_texture,
+            options,
+            None,
+            progress,
+        );
+        (finish_render(output, options, factor), stats)
+    }
+
+    // Draw the layers into `output`, which should be cleared to the background at the render scale.
+    fn render_scaled<P: OutputPixel, S: SkinPixel>(
+        &self,
+        mut output: OutputImage<P>,
+        skin_texture: &OutputImage<S>,
+        options: &RenderOptions,
+        mut ids: Option<&mut GrayImage>,
+        progress: &mut dyn FnMut(f32, Option<&OutputImage<P>>),
+    ) -> (OutputImage<P>, RenderStats) {
+        let mut stats = RenderStats::default();
+
+        let skin_rgba8 = S::to_rgba8(skin_texture);
+        let layers = options.layers_to_draw(&skin_rgba8);
+        let dimensions = self.uv_map(Layer::Head, options).dimensions();
+        let extras: Vec<_> = options
+            .extras
+            .iter()
+            .filter(|extra| {
+                // Maps with other dimensions wouldn't line up with the model.
+                if extra.uv_map.dimensions() != dimensions {
+                    log::warn!(
+                        "Extra layer skipped with UV map dimensions {:?} instead of {:?}",
+                        extra.uv_map.dimensions(),
+                        dimensions
+                    );
+                    return false;
+                }
+                extra.region.is_none_or(|region| {
+                    has_pixels_in_region(
+                        &skin_rgba8,
+                        region,
+                        options.overlay_min_alpha,
+                        options.overlay_min_count,
+                    )
+                })
+            })
+            .collect();
+        let step_count = layers.len() + extras.len() + options.cape.is_some() as usize;
+        let mut step = 0;
+        // Only pass the output for steps that changed it.
+        let mut finish_step = |output: &OutputImage<P>, drawn: bool| {
+            step += 1;
+            progress(step as f32 / step_count as f32, drawn.then_some(output));
+        };
+
+        // Alpha blending relies on having the correct color already present in the render buffer.
+        // The cape hangs behind the body, so it needs to be drawn first unless viewed from behind.
+        let draw_cape = |output: &mut OutputImage<P>, ids: Option<&mut GrayImage>| {
+            options.cape.as_ref().map(|cape_texture| {
+                self.render_cape(
+                    output,
+                    cape_texture,
+                    options,
+                    ids.map(|ids| (ids, CAPE_REGION_ID)),
+                )
+            })
+        };
+
+        if options.pose == Pose::Front {
+            if let Some(drawn) = draw_cape(&mut output, ids.as_deref_mut()) {
+                stats.layers_blended += drawn as usize;
+                finish_step(&output, drawn);
+            }
+        }
+
+        for draw in layers {
+            let layer = draw.layer;
+            let drawn = blend_layer_with_base(
+                &mut output,
+                self.uv_map(layer, options),
+                skin_texture,
+                options.layer_blend(&draw),
+                options.blend_gamma,
+                options.smooth_uvs,
+                ids.as_deref_mut().map(|ids| (ids, layer.region_id())),
+            );
+            if drawn {
+                stats.layers_blended += 1;
+                if let Some(i) = OVERLAY_REGIONS.iter().position(|(o, _)| *o == layer) {
+                    stats.overlays_present |= 1 << i;
+                }
+            }
+            finish_step(&output, drawn);
+        }
+
+        for extra in extras {
+            let drawn = blend_layer_with_base(
+                &mut output,
+                &extra.uv_map,
+                skin_texture,
+                options.default_blend(),
+                options.blend_gamma,
+                options.smooth_uvs,
+                ids.as_deref_mut().map(|ids| (ids, EXTRA_REGION_ID)),
+            );
+            stats.layers_blended += drawn as usize;
+            finish_step(&output, drawn);
+        }
+
+        if options.pose == Pose::Back {
+            if let Some(drawn) = draw_cape(&mut output, ids) {
+                stats.layers_blended += drawn as usize;
+                finish_step(&output, drawn);
+            }
+        }
+        (output, stats)
+    }
+
+    /// Creates a render like [RenderContext::render] that keeps the blended layers before
+    /// mirroring, supersampling, and other adjustments to the entire render.
+    /// Overlay layers can then be toggled with [RenderContext::reapply_overlay]
+    /// without drawing every layer again, which is useful for interactive editors.
+    pub fn render_retained(
+        &self,
+        skin_texture: &RgbaImage,
+        options: &RenderOptions,
+    ) -> RetainedRender {
+        let factor = options.supersample.max(1);
+        let (buffer, _) = self.render_scaled(
+            self.new_output(options, options.scale * factor),
+            skin_texture,
+            options,
+            None,
+            &mut |_, _| {},
+        );
+        RetainedRender {
+            buffer,
+            options: options.clone(),
+        }
+    }
+
+    /// Shows or hides the overlay `layer` in a render from [RenderContext::render_retained]
+    /// and returns the updated output.
+    /// Only the pixels covered by the layer's UV map are blended again,
+    /// so the result matches a full render with the layer's override in [RenderOptions] set to `visible`.
+    /// Base layers are always drawn, so the render is unchanged for base layers.
+    pub fn reapply_overlay(
+        &self,
+        render: &mut RetainedRender,
+        skin_texture: &RgbaImage,
+        layer: Layer,
+        visible: bool,
+    ) -> RgbaImage {
+        if !layer.is_overlay() {
+            return render.output();
+        }
+        render.options.set_overlay_override(layer, Some(visible));
+        let options = &render.options;
+
+        let uv_map = self.uv_map(layer, options);
+        // Only the pixels covered by the layer can change.
+        let bounds = uv_map
+            .enumerate_pixels()
+            .filter(|(_, _, uv)| uv[3] > 0u16)
+            .fold(None, |bounds, (x, y, _)| match bounds {
+                Some((x_min, y_min, x_max, y_max)) => {
+                    Some((min(x_min, x), min(y_min, y), max(x_max, x), max(y_max, y)))
+                }
+                None => Some((x, y, x, y)),
+            });
+        let bounds = match bounds {
+            Some((x_min, y_min, x_max, y_max)) => Rect {
+                x: x_min,
+                y: y_min,
+                width: x_max - x_min + 1,
+                height: y_max - y_min + 1,
+            },
+            None => return render.output(),
+        };
+
+        // Include a texel of margin, so smooth UVs sample the same neighbors as the full render.
+        let x = bounds.x.saturating_sub(1);
+        let y = bounds.y.saturating_sub(1);
+        let rect = Rect {
+            x,
+            y,
+            width: (bounds.x + bounds.width + 1).min(uv_map.width()) - x,
+            height: (bounds.y + bounds.height + 1).min(uv_map.height()) - y,
+        };
+        let (cropped, cropped_options) = self.cropped(options, rect);
+        let scale = options.scale * options.supersample.max(1);
+        let (patch, _) = cropped.render_scaled(
+            cropped.new_output(&cropped_options, scale),
+            skin_texture,
+            &cropped_options,
+            None,
+            &mut |_, _| {},
+        );
+
+        // Skip the margin, since the pixels there may depend on texels outside the cropped maps.
+        for patch_y in (bounds.y - rect.y) * scale..(bounds.y + bounds.height - rect.y) * scale {
+            for patch_x in (bounds.x - rect.x) * scale..(bounds.x + bounds.width - rect.x) * scale {
+                render.buffer.put_pixel(
+                    rect.x * scale + patch_x,
+                    rect.y * scale + patch_y,
+                    *patch.get_pixel(patch_x, patch_y),
+                );
+            }
+        }
+        render.output()
+    }
+
+    /// Creates a render like [RenderContext::render] as horizontal strips from top to bottom.
+    /// Each strip is drawn independently when the iterator advances,
+    /// so only one strip is in memory at a time for large [RenderOptions::scale] or [RenderOptions::supersample] values.
+    /// Strips have `strip_height` rows rounded up to a multiple of [RenderOptions::scale] except for the last strip.
+    /// Stacking the strips vertically gives the same pixels as the full render.
+    pub fn render_strips<'a>(
+        &'a self,
+        skin_texture: &'a RgbaImage,
+        options: &'a RenderOptions,
+        strip_height: u32,
+    ) -> impl Iterator<Item = RgbaImage> + 'a {
+        let map_height = self.uv_map(Layer::Head, options).height();
+        let rows = strip_height.div_ceil(options.scale).max(1);
+        (0..map_height)
+            .step_by(rows as usize)
+            .map(move |y| self.render_strip(skin_texture, options, y, rows.min(map_height - y)))
+    }
+
+    // Render the output rows for UV map rows y..y + rows.
+    fn render_strip(
+        &self,
+        skin_texture: &RgbaImage,
+        options: &RenderOptions,
+        y: u32,
+        rows: u32,
+    ) -> RgbaImage {
+        // Include enough rows for smooth UVs and outlines to see the same neighbors as the full render.
+        let outline_rows = options
+            .outline
+            .map_or(0, |(_, thickness)| thickness.div_ceil(options.scale));
+        let margin = 1 + outline_rows;
+        let uv_map = self.uv_map(Layer::Head, options);
+        let start = y.saturating_sub(margin);
+        let rect = Rect {
+            x: 0,
+            y: start,
+            width: uv_map.width(),
+            height: (y + rows + margin).min(uv_map.height()) - start,
+        };
+        let (cropped, cropped_options) = self.cropped(options, rect);
+        let output = cropped.render(skin_texture, &cropped_options);
+        image::imageops::crop_imm(
+            &output,
+            0,
+            (y - start) * options.scale,
+            output.width(),
+            rows * options.scale,
+        )
+        .to_image()
+    }
+
+    /// Creates a render of only `layer` using the settings in `options`.
+    /// The layer is always drawn even if the skin has no pixels in the layer's region.
+    pub fn render_layer(
+        &self,
+        skin_texture: &RgbaImage,
+        layer: Layer,
+        options: &RenderOptions,
+    ) -> RgbaImage {
+        let mut output = self.new_output(options, options.scale);
+        // Use the lighting from the draw order even if the layer wouldn't be drawn.
+        let draw = options
+            .draw_order()
+            .iter()
+            .find(|draw| draw.layer == layer)
+            .copied()
+            .unwrap_or(LayerDraw::always(layer));
+        blend_layer_with_base(
+            &mut output,
+            self.uv_map(layer, options),
+            skin_texture,
+            options.layer_blend(&draw),
+            options.blend_gamma,
+            options.smooth_uvs,
+            None,
+        );
+        finish_render(output, options, 1)
+    }
+
+    /// Renders each skin using the settings in `options` and combines the renders into a single image.
+    /// Each render is placed using the transform at the same index in `layout`,
+    /// and later renders are drawn in front of earlier renders.
+    /// Skins without a transform are skipped.
+    pub fn render_group(
+        &self,
+        skin_textures: &[RgbaImage],
+        layout: &GroupLayout,
+        options: &RenderOptions,
+    ) -> RgbaImage {
+        let mut output = RgbaImage::new(layout.width, layout.height);
+        for (skin_texture, transform) in skin_textures.iter().zip(&layout.transforms) {
+            let render = self.render(skin_texture, options);
+            let placed = warp_render(
+                &render,
+                layout.width,
+                layout.height,
+                transform.scale,
+                transform.translate_x,
+                transform.translate_y,
+            );
+            compositing::composite_over(&mut output, &placed, options.blend_gamma);
+        }
+        output
+    }
+
+    /// Creates a render of only the head and hat cropped to the drawn pixels using the settings in `options`.
+    /// This is useful for small icons like player lists.
+    /// The hat is only drawn if the skin has pixels in the hat region unless overridden with [RenderOptions::hat].
+    /// The result is empty if the head and hat are fully transparent.
+    pub fn render_head(&self, skin_texture: &RgbaImage, options: &RenderOptions) -> RgbaImage {
+        let options = RenderOptions {
+            cape: None,
+            draw_order: Some(vec![
+                LayerDraw::always(Layer::Head),
+                LayerDraw::gated(Layer::HeadOverlay, HEAD_OVERLAY_REGION),
+            ]),
+            ..options.clone()
+        };
+        let (render, ids) = self.render_with_ids(skin_texture, &options);
+
+        // Use the region map to find the head even with an opaque background.
+        let bounds = ids
+            .enumerate_pixels()
+            .filter(|(_, _, id)| id[0] != 0u8)
+            .fold(None, |bounds, (x, y, _)| match bounds {
+                Some((x_min, y_min, x_max, y_max)) => {
+                    Some((min(x_min, x), min(y_min, y), max(x_max, x), max(y_max, y)))
+                }
+                None => Some((x, y, x, y)),
+            });
+        match bounds {
+            Some((x_min, y_min, x_max, y_max)) => image::imageops::crop_imm(
+                &render,
+                x_min,
+                y_min,
+                x_max - x_min + 1,
+                y_max - y_min + 1,
+            )
+            .to_image(),
+            None => RgbaImage::new(0, 0),
+        }
+    }
+
+    fn clear_output(&self, output: &mut RgbaImage, options: &RenderOptions) {
+        let head_uvs = self.uv_map(Layer::Head, options);
+        let dimensions = (
+            head_uvs.width() * options.scale,
+            head_uvs.height() * options.scale,
+        );
+        if output.dimensions() != dimensions {
+            *output = self.new_output(options, options.scale);
+        } else {
+            let background = options.background.unwrap_or(Rgba([0u8; 4]));
+            for pixel in output.pixels_mut() {
+                *pixel = background;
+            }
+        }
+    }
+
+    fn new_output<P: OutputPixel>(&self, options: &RenderOptions, scale: u32) -> OutputImage<P> {
+        let head_uvs = self.uv_map(Layer::Head, options);
+        let width = head_uvs.width() * scale;
+        let height = head_uvs.height() * scale;
+        match options.background {
+            Some(color) => {
+                let (r, g, b, a) = normalize_rgba_u8(&color);
+                ImageBuffer::from_pixel(
+                    width,
+                    height,
+                    P::from_encoded(r, g, b, a, options.blend_gamma),
+                )
+            }
+            None => ImageBuffer::new(width, height),
+        }
+    }
+
+    fn render_cape<P: OutputPixel>(
+        &self,
+        output: &mut OutputImage<P>,
+        cape_texture: &RgbaImage,
+        options: &RenderOptions,
+        ids: Option<(&mut GrayImage, u8)>,
+    ) -> bool {
+        blend_layer_with_base(
+            output,
+            self.cape_uv_map(options),
+            cape_texture,
+            options.default_blend(),
+            options.blend_gamma,
+            options.smooth_uvs,
+            ids,
+        )
+    }
+
+    fn cape_uv_map(&self, options: &RenderOptions) -> &UvMap {
+        let (is_alex, pose) = (options.slim, options.pose);
+        // The front cape UVs sample the inner face of the 22x17 cape region of the 64x32 cape texture.
+        // The back cape UVs sample the outer face.
+        let maps = self.model_uv_maps(is_alex, pose);
+        maps.cape
+            .get_or_init(|| load_rgba_u16(cape_uv_map_bytes(is_alex, pose)))
+    }
+
+    fn uv_map(&self, layer: Layer, options: &RenderOptions) -> &UvMap {
+        let limb_pose = self.limb_pose(layer, options);
+        self.uv_map_slot(layer, options).get_or_init(|| {
+            load_rgba_u16(layer.uv_map_bytes(options.slim, options.pose, limb_pose))
+        })
+    }
+
+    fn uv_map_slot(&self, layer: Layer, options: &RenderOptions) -> &OnceLock<UvMap> {
+        let maps = self.model_uv_maps(options.slim, options.pose);
+        match self.limb_pose(layer, options) {
+            LimbPose::Spread => &maps.spread_layers[layer as usize],
+            LimbPose::Smash => &maps.layers[layer as usize],
+        }
+    }
+
+    fn limb_pose(&self, layer: Layer, options: &RenderOptions) -> LimbPose {
+        match options.limb_pose {
+            LimbPose::Spread if layer.is_limb() => LimbPose::Spread,
+            _ => LimbPose::Smash,
+        }
+    }
+
+    // A context and options with the maps for drawing a render with `options` cropped to `rect`.
+    fn cropped(&self, options: &RenderOptions, rect: Rect) -> (Self, RenderOptions) {
+        let crop = |map: &UvMap| {
+            image::imageops::crop_imm(map, rect.x, rect.y, rect.width, rect.height).to_image()
+        };
+        let context = Self::new();
+        for draw in options.draw_order() {
+            let _ = context
+                .uv_map_slot(draw.layer, options)
+                .set(crop(self.uv_map(draw.layer, options)));
+        }
+        // The background uses the dimensions of the head map.
+        let _ = context
+            .uv_map_slot(Layer::Head, options)
+            .set(crop(self.uv_map(Layer::Head, options)));
+        if options.cape.is_some() {
+            let maps = context.model_uv_maps(options.slim, options.pose);
+            let _ = maps.cape.set(crop(self.cape_uv_map(options)));
+        }
+        let cropped_options = RenderOptions {
+            extras: options
+                .extras
+                .iter()
+                .map(|extra| ExtraLayer {
+                    uv_map: crop(&extra.uv_map),
+                    region: extra.region,
+                })
+                .collect(),
+            ..options.clone()
+        };
+        (context, cropped_options)
+    }
+
+    // Every UV map and its embedded file in a fixed order to identify the maps in cache files.
+    fn uv_map_slots(&self) -> Vec<(&OnceLock<UvMap>, &'static [u8])> {
+        let mut slots = Vec::new();
+        for (pose, is_alex) in [
+            (Pose::Front, false),
+            (Pose::Front, true),
+            (Pose::Back, false),
+            (Pose::Back, true),
+        ] {
+            let maps = self.model_uv_maps(is_alex, pose);
+            for limb_pose in [LimbPose::Smash, LimbPose::Spread] {
+                let layers = match limb_pose {
+                    LimbPose::Smash => &maps.layers,
+                    LimbPose::Spread => &maps.spread_layers,
+                };
+                for layer in LAYERS {
+                    let bytes = layer.uv_map_bytes(is_alex, pose, limb_pose);
+                    slots.push((&layers[layer as usize], bytes));
+                }
+            }
+            slots.push((&maps.cape, cape_uv_map_bytes(is_alex, pose)));
+        }
+        slots
+    }
+
+    fn model_uv_maps(&self, is_alex: bool, pose: Pose) -> &ModelUvMaps {
+        match (pose, is_alex) {
+            (Pose::Front, false) => &self.steve,
+            (Pose::Front, true) => &self.alex,
+            (Pose::Back, false) => &self.steve_back,
+            (Pose::Back, true) => &self.alex_back,
+        }
+    }
+}
+
+/// A render from [RenderContext::render_retained] for toggling overlays with [RenderContext::reapply_overlay].
+#[derive(Debug, Clone)]
+pub struct RetainedRender {
+    // The blended layers at the supersampled resolution before finish_render.
+    buffer: RgbaImage,
+    options: RenderOptions,
+}
+
+impl RetainedRender {
+    /// The settings for the render including any overlays toggled with [RenderContext::reapply_overlay].
+    pub fn options(&self) -> &RenderOptions {
+        &self.options
+    }
+
+    /// The finished render matching [RenderContext::render] with [RetainedRender::options].
+    pub fn output(&self) -> RgbaImage {
+        finish_render(
+            self.buffer.clone(),
+            &self.options,
+            self.options.supersample.max(1),
+        )
+    }
+}
+
+/// Creates a Smash Ultimate Minecraft Steve inspired render from the given Minecraft skin texture.
+/// HD skin textures like 128x128 or 256x256 are sampled at their full resolution
+/// as long as they use the same layout as a 64x64 skin.
+pub fn create_render(skin_texture: &RgbaImage) -> RgbaImage {
+    let mut output = RgbaImage::new(0, 0);
+    create_render_into(&mut output, skin_texture);
+    output
+}
+
+/// Creates a render like [create_render] in `output` to reuse its allocation.
+/// `output` is cleared first and is resized if it doesn't match the render's dimensions.
+/// Use [RenderContext::render_into] to also avoid decoding the UV maps for each render.
+pub fn create_render_into(output: &mut RgbaImage, skin_texture: &RgbaImage) {
+    RenderContext::new().render_into(output, skin_texture, &RenderOptions::default())
+}
+
+/// Creates a Smash Ultimate Minecraft Alex inspired render from the given Minecraft skin texture.
+pub fn create_render_slim(skin_texture: &RgbaImage) -> RgbaImage {
+    create_render_with_options(
+        skin_texture,
+        &RenderOptions {
+            slim: true,
+            ..Default::default()
+        },
+    )
+}
+
+/// Creates a Smash Ultimate Minecraft inspired render from the given Minecraft skin texture
+/// using the settings in `options`.
+/// Use a [RenderContext] instead to avoid decoding the UV maps for each render.
+pub fn create_render_with_options(skin_texture: &RgbaImage, options: &RenderOptions) -> RgbaImage {
+    RenderContext::new().render(skin_texture, options)
+}
+
+/// Creates a render like [create_render]
+/// and calls `progress` with the fraction of completed layers from 0.0 to 1.0 after drawing each layer.
+/// This is useful for displaying a progress bar in applications.
+pub fn create_render_with_progress(
+    skin_texture: &RgbaImage,
+    progress: &mut dyn FnMut(f32),
+) -> RgbaImage {
+    RenderContext::new().render_with_progress(skin_texture, &RenderOptions::default(), progress)
+}
+
+/// Creates the intermediate renders of `skin_texture` after drawing each layer with the default settings.
+/// See [RenderContext::render_layer_frames].
+pub fn render_layer_frames(skin_texture: &RgbaImage) -> Vec<RgbaImage> {
+    RenderContext::new().render_layer_frames(skin_texture, &RenderOptions::default())
+}
+
+/// Creates a render of the back of the model from the given Minecraft skin texture.
+/// This uses the back faces of each body part like the back of the head instead of the front faces.
+pub fn create_render_back(skin_texture: &RgbaImage) -> RgbaImage {
+    create_render_with_options(
+        skin_texture,
+        &RenderOptions {
+            pose: Pose::Back,
+            ..Default::default()
+        },
+    )
+}
+
+/// Creates the front render from [create_render] and the back render from [create_render_back].
+pub fn create_render_poses(skin_texture: &RgbaImage) -> (RgbaImage, RgbaImage) {
+    let context = RenderContext::new();
+    let front = context.render(skin_texture, &RenderOptions::default());
+    let back = context.render(
+        skin_texture,
+        &RenderOptions {
+            pose: Pose::Back,
+            ..Default::default()
+        },
+    );
+    (front, back)
+}
+
+/// Creates a render and the chara images for each of the [chara_slots] using the settings in `options`.
+/// See [RenderContext::render_all] for details.
+pub fn render_all(skin_texture: &RgbaImage, options: &RenderOptions) -> RenderOutputs {
+    RenderContext::new().render_all(skin_texture, options)
+}
+
+/// Creates a render like [create_render] and information about the layers and time to render.
+/// See [RenderContext::render_with_stats] for details.
+pub fn create_render_with_stats(skin_texture: &RgbaImage) -> (RgbaImage, RenderStats) {
+    RenderContext::new().render_with_stats(skin_texture, &RenderOptions::default())
+}
+
+/// Creates a render like [create_render] and a map of the body part for each pixel.
+/// This is useful for masking specific body parts when editing the render.
+/// See [Layer::region_id] for the values in the region map.
+pub fn create_render_with_ids(skin_texture: &RgbaImage) -> (RgbaImage, GrayImage) {
+    RenderContext::new().render_with_ids(skin_texture, &RenderOptions::default())
+}
+
+/// Creates a render like [create_render] with 16 bits per channel of linear color.
+/// See [RenderContext::render_hdr] for details.
+pub fn create_render_hdr(skin_texture: &RgbaImage) -> ImageBuffer<Rgba<u16>, Vec<u16>> {
+    RenderContext::new().render_hdr(skin_texture, &RenderOptions::default())
+}
+
+/// The arm width of a Minecraft player model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum ModelType {
+    /// The 4 pixel wide arms of the Steve model.
+    Classic,
+    /// The 3 pixel wide arms of the Alex model.
+    Slim,
+}
+
+/// Guesses the model for a skin texture using the 64x64 layout.
+/// Slim skins leave the last 2 columns of the right arm transparent.
+/// Skins that don't fill those columns with the classic arms may be detected incorrectly.
+pub fn detect_model(skin_texture: &RgbaImage) -> ModelType {
+    if skin_texture.height() < skin_texture.width() {
+        // Legacy 64x32 skins predate the slim model.
+        return ModelType::Classic;
+    }
+
+    if has_pixel_in_region(
+        skin_texture,
+        54f32 / 64f32,
+        56f32 / 64f32,
+        20f32 / 64f32,
+        32f32 / 64f32,
+    ) {
+        ModelType::Classic
+    } else {
+        ModelType::Slim
+    }
+}
+
+/// A skin tagged with a model that contradicts the pixels in the arm regions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModelMismatch {
+    /// The model the skin was tagged with.
+    pub declared: ModelType,
+    /// The model suggested by the arm regions of the skin.
+    pub detected: ModelType,
+}
+
+impl std::fmt::Display for ModelMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "the skin is tagged as {:?} but the arms look {:?}",
+            self.declared, self.detected
+        )
+    }
+}
+
+impl std::error::Error for ModelMismatch {}
+
+/// Checks if the `declared` model for a skin like the model from the player's profile
+/// matches the model from [detect_model].
+/// Classic skins with slim arms have a gap in the arms, and slim skins with classic arms are stretched.
+/// Legacy 64x32 skins always use the classic model, so they aren't checked.
+///
+/// Only the columns x=54..56 of the right arm are checked like [detect_model].
+/// The columns x=50..54 hold the back face of the slim arm at x=50..53,
+/// so they have pixels for both models and can't tell the models apart.
+pub fn check_model(skin_texture: &RgbaImage, declared: ModelType) -> Result<(), ModelMismatch> {
+    if skin_texture.height() < skin_texture.width() {
+        return Ok(());
+    }
+
+    let detected = detect_model(skin_texture);
+    if detected == declared {
+        Ok(())
+    } else {
+        Err(ModelMismatch { declared, detected })
+    }
+}
+
+/// The default skin texture for `model` for rendering when a skin can't be loaded.
+/// Only the default Steve texture is included, so [ModelType::Slim] uses the Steve texture
+/// with its arms converted to the 3 pixel wide layout of the slim arms.
+/// Render with [RenderOptions::slim] to use the slim arms.
+pub fn fallback_skin(model: ModelType) -> RgbaImage {
+    let bytes: &[u8] = match model {
+        ModelType::Classic => include_bytes!("../sample.png"),
+        ModelType::Slim => include_bytes!("../sample_slim.png"),
+    };
+    image::load_from_memory(bytes).unwrap().into_rgba8()
+}
+
+/// Loads a skin texture from a file and converts it to 8 bit RGBA.
+/// Palette, grayscale, and RGB images are expanded to RGBA with the same colors.
+/// Images without an alpha channel are fully opaque, and 16 bit images are rounded to 8 bits.
+pub fn load_skin<P: AsRef<Path>>(path: P) -> Result<RgbaImage, RenderError> {
+    Ok(image::open(path)?.into_rgba8())
+}
+
+/// Loads a skin texture from a file and converts it to 16 bit RGBA for [RenderContext::render_16].
+/// This keeps the full precision of 16 bit skins, and 8 bit skins are expanded to 16 bits with the same colors.
+pub fn load_skin_16<P: AsRef<Path>>(
+    path: P,
+) -> Result<ImageBuffer<Rgba<u16>, Vec<u16>>, RenderError> {
+    match image::open(path)? {
+        image @ (DynamicImage::ImageLuma16(_)
+        | DynamicImage::ImageLumaA16(_)
+        | DynamicImage::ImageRgb16(_)
+        | DynamicImage::ImageRgba16(_)) => Ok(image.into_rgba16()),
+        // Converting 8 bit images with into_rgba16 shifts the values instead of scaling,
+        // so white would be 65280 instead of 65535.
+        image => Ok(expand_rgba8(&image.into_rgba8())),
+    }
+}
+
+fn expand_rgba8(image: &RgbaImage) -> ImageBuffer<Rgba<u16>, Vec<u16>> {
+    let (width, height) = image.dimensions();
+    let values = image.as_raw().iter().map(|c| *c as u16 * 257u16).collect();
+    ImageBuffer::from_raw(width, height, values).unwrap()
+}
+
+/// Checks for common mistakes with skin textures like exporting an empty layer from an image editor.
+pub fn validate_skin(skin_texture: &RgbaImage) -> Result<(), RenderError> {
+    // This returns on the first visible pixel, so the check is cheap for most skins.
+    if !has_pixel_in_region(skin_texture, 0.0f32, 1.0f32, 0.0f32, 1.0f32) {
+        return Err(RenderError::EmptySkin);
+    }
+    Ok(())
+}
+
+/// Clears overlay regions filled with opaque black, which some older skin editors used for unused areas.
+/// Otherwise the overlays are detected as present and drawn as black boxes over the model.
+/// This is a heuristic, so only use it for skins known to use this convention.
+/// A region is cleared if every visible pixel in the region is opaque black.
+/// Returns the overlay layers whose regions were cleared.
+pub fn clean_opaque_overlay_noise(skin_texture: &mut RgbaImage) -> Vec<Layer> {
+    let mut cleaned = Vec::new();
+    for (layer, region) in OVERLAY_REGIONS {
+        let (x_start, x_end) =
+            region_pixel_range(region.x_start, region.x_end, skin_texture.width());
+        let (y_start, y_end) =
+            region_pixel_range(region.y_start, region.y_end, skin_texture.height());
+
+        let visible: Vec<_> =
+            visible_pixels(skin_texture, x_start..x_end, y_start..y_end, 1).collect();
+        let is_noise = !visible.is_empty()
+            && visible
+                .iter()
+                .all(|(x, y)| *skin_texture.get_pixel(*x, *y) == Rgba([0u8, 0u8, 0u8, 255u8]));
+        if is_noise {
+            for (x, y) in visible {
+                skin_texture.put_pixel(x, y, Rgba([0u8; 4]));
+            }
+            cleaned.push(layer);
+        }
+    }
+    cleaned
+}
+
+/// Removes transparent padding from the bottom and right of a skin texture
+/// exported with a larger canvas like 128x128 or a non square canvas.
+/// The result is the smallest 64x64 or 64x32 layout at any HD resolution that contains the visible pixels.
+pub fn auto_crop_skin(skin_texture: &RgbaImage) -> Result<RgbaImage, RenderError> {
+    let (content_width, content_height) = skin_texture
+        .enumerate_pixels()
+        .filter(|(_, _, p)| p[3] > 0u8)
+        .fold((0, 0), |(w, h), (x, y, _)| (w.max(x + 1), h.max(y + 1)));
+    if content_width == 0 {
+        return Err(RenderError::EmptySkin);
+    }
+
+    // HD skins use power of two multiples of the standard layout.
+    let mut width = 64;
+    while width < content_width.max(content_height) {
+        width *= 2;
+    }
+    // Legacy skins only use the top half of the modern layout.
+    let height = if content_height <= width / 2 {
+        width / 2
+    } else {
+        width
+    };
+
+    if width > skin_texture.width() || height > skin_texture.height() {
+        return Err(RenderError::NoSkinRegion);
+    }
+    Ok(image::imageops::crop_imm(skin_texture, 0, 0, width, height).to_image())
+}
+
+/// The scale and translation in pixels for placing a render in a [GroupLayout].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GroupTransform {
+    pub scale: f32,
+    pub translate_x: f32,
+    pub translate_y: f32,
+}
+
+/// The output dimensions and placement of each render for [render_group].
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroupLayout {
+    pub width: u32,
+    pub height: u32,
+    /// The transform for the skin at the same index.
+    pub transforms: Vec<GroupTransform>,
+}
+
+/// Creates a render for each skin and combines the renders into a single image for group shots.
+/// See [RenderContext::render_group] for details.
+pub fn render_group(skin_textures: &[RgbaImage], layout: &GroupLayout) -> RgbaImage {
+    RenderContext::new().render_group(skin_textures, layout, &RenderOptions::default())
+}
+
+/// Creates a render of only the head and hat from the given Minecraft skin texture
+/// cropped to the drawn pixels for small icons.
+/// See [RenderContext::render_head] for details.
+pub fn create_head_render(skin_texture: &RgbaImage) -> RgbaImage {
+    RenderContext::new().render_head(skin_texture, &RenderOptions::default())
+}
+
+/// Creates a render of a single `layer` from the given Minecraft skin texture.
+/// This is useful for editing individual layers like the hat in external programs.
+pub fn render_layer(skin_texture: &RgbaImage, layer: Layer) -> RgbaImage {
+    RenderContext::new().render_layer(skin_texture, layer, &RenderOptions::default())
+}
+
+/// How the alpha of a chara reference masks the transformed render.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MaskMode {
+    /// Use the smaller of the two alpha values.
+    /// This matches the original chara images but can leave hard edges along the mask's antialiased border.
+    #[default]
+    Min,
+    /// Multiply the alpha values, so a 50% mask over a 50% render gives 25%.
+    /// This smooths the edges where the mask's border overlaps the render's antialiased edges.
+    Multiply,
+}
+
+/// Creates a render with the dimensions and alpha of the reference chara file
+/// by transforming the render using the given transformations.
+/// The reference alpha is combined with the render alpha using `mask_mode`.
+pub fn create_chara_image(
+    render: &RgbaImage,
+    chara_reference: &RgbaImage,
+    scale: f32,
+    translate_x: f32,
+    translate_y: f32,
+    fill: Rgba<u8>,
+    mask_mode: MaskMode,
+) -> RgbaImage {
+    let mut output = warp_chara_image(
+        render,
+        chara_reference.dimensions(),
+        scale,
+        0f32,
+        (translate_x, translate_y),
+        Interpolation::Bilinear,
+        fill,
+    );
+    blend_alpha(&mut output, chara_reference, mask_mode);
+    output
+}
+
+/// Creates a render with the dimensions and alpha of the reference chara file
+/// by transforming the render using the given transformations.
+/// The render is scaled and then rotated clockwise by `rotation` radians around its top left corner
+/// before applying the translation.
+/// [Interpolation::Nearest] preserves the hard edges of the skin pixels for small chara images.
+/// Areas outside the render use `fill` before applying the reference alpha with [MaskMode::Min].
+pub fn create_chara_image_affine(
+    render: &RgbaImage,
+    chara_reference: &RgbaImage,
+    scale: f32,
+    rotation: f32,
+    translation: (f32, f32),
+    interpolation: Interpolation,
+    fill: Rgba<u8>,
+) -> RgbaImage {
+    let mut output = warp_chara_image(
+        render,
+        chara_reference.dimensions(),
+        scale,
+        rotation,
+        translation,
+        interpolation,
+        fill,
+    );
+
+    // Use the reference image's alpha for appropriate masking on some portraits.
+    blend_alpha(&mut output, chara_reference, MaskMode::Min);
+
+    output
+}
+
+/// The region of a UI texture atlas for a portrait and the transformation to align the render with it.
+/// The transformation is relative to the top left corner of the region like [create_chara_image].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AtlasSlot {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub scale: f32,
+    pub translate_x: f32,
+    pub translate_y: f32,
+}
+
+/// Replaces the region for `slot` in `atlas` with the transformed render.
+/// Pixels outside the region are unchanged, and parts of the region past the edges of the atlas are clipped.
+pub fn place_into_atlas(atlas: &mut RgbaImage, render: &RgbaImage, slot: AtlasSlot) {
+    let portrait = warp_chara_image(
+        render,
+        (slot.width, slot.height),
+        slot.scale,
+        0f32,
+        (slot.translate_x, slot.translate_y),
+        Interpolation::Bilinear,
+        Rgba([0u8, 0u8, 0u8, 0u8]),
+    );
+    image::imageops::replace(atlas, &portrait, slot.x, slot.y);
+}
+
+/// The mask and transformation for creating a chara image from a render.
+#[derive(Debug, Clone)]
+pub struct CharaSlot {
+    /// The file name for the chara image without the extension like `"chara_3_custom"`.
+    pub name: &'static str,
+    /// The chara reference that defines the dimensions and alpha of the chara image.
+    pub mask: RgbaImage,
+    pub scale: f32,
+    pub translate_x: f32,
+    pub translate_y: f32,
+    pub interpolation: Interpolation,
+    /// The dimensions of the texture used in game.
+    pub native_size: (u32, u32),
+}
+
+impl CharaSlot {
+    /// Creates the chara image from `render` with [create_chara_image_affine].
+    /// The transformation assumes a render with the default resolution,
+    /// so `render_scale` should match [RenderOptions::scale] for the render.
+    pub fn create(&self, render: &RgbaImage, render_scale: u32, fill: Rgba<u8>) -> RgbaImage {
+        create_chara_image_affine(
+            render,
+            &self.mask,
+            self.scale / render_scale as f32,
+            0f32,
+            (self.translate_x, self.translate_y),
+            self.interpolation,
+            fill,
+        )
+    }
+}
+
+/// The chara images for Smash Ultimate's Steve UI created by [render_all].
+pub fn chara_slots(interpolation: Interpolation) -> Vec<CharaSlot> {
+    let load_mask = |bytes| image::load_from_memory(bytes).unwrap().into_rgba8();
+
+    // The transformations are hardcoded based on the output render resolution.
+    // New slots can use solve_transform with two matching points.
+    // The final render is scaled down to match the appropriate sizes.
+    vec![
+        CharaSlot {
+            name: "chara_3_custom",
+            mask: load_mask(include_bytes!("../images/masks/chara_3_mask.png")),
+            scale: 1.2845125f32,
+            translate_x: -456.55612f32,
+            translate_y: 11.757321f32,
+            interpolation,
+            native_size: (968, 1864),
+        },
+        CharaSlot {
+            name: "chara_4_custom",
+            mask: load_mask(include_bytes!("../images/masks/chara_4_mask.png")),
+            scale: 0.23288201f32,
+            translate_x: -90.16959f32,
+            translate_y: 9.084564f32,
+            interpolation,
+            // The mask is slightly larger than the in game texture.
+            native_size: (160, 160),
+        },
+        CharaSlot {
+            name: "chara_6_custom",
+            mask: load_mask(include_bytes!("../images/masks/chara_6_mask.png")),
+            scale: 0.938028f32,
+            translate_x: -480.87906f32,
+            translate_y: -96.13269f32,
+            interpolation,
+            native_size: (512, 256),
+        },
+    ]
+}
+
+/// The render and chara images created by [render_all].
+#[derive(Debug, Clone)]
+pub struct RenderOutputs {
+    pub render: RgbaImage,
+    /// The chara images by [CharaSlot::name].
+    pub charas: BTreeMap<&'static str, RgbaImage>,
+}
+
+fn warp_chara_image(
+    render: &RgbaImage,
+    target_dimensions: (u32, u32),
+    scale: f32,
+    rotation: f32,
+    translation: (f32, f32),
+    interpolation: Interpolation,
+    fill: Rgba<u8>,
+) -> RgbaImage {
+    // Sample a smaller copy of the render for small chara images to reduce aliasing.
+    let factor = prefilter_factor(scale);
+    let prefiltered;
+    let source = if factor > 1 {
+        prefiltered = downsample_linear(render, factor, DEFAULT_GAMMA);
+        &prefiltered
+    } else {
+        render
+    };
+
+    // Align the render with the target chara image.
+    warp_render_affine(
+        source,
+        target_dimensions,
+        scale * factor as f32,
+        rotation,
+        translation,
+        interpolation,
+        fill,
+    )
+}
+
+/// Finds the scale and translation for [create_chara_image] that moves the two points in `src_points` on the render
+/// to the pixel positions in `dst_points` on the chara image.
+/// This avoids guessing the transformation for new chara images by picking matching points like the eyes or feet.
+/// Points that would require rotating the render use the closest fit for the scale.
+pub fn solve_transform(
+    src_points: [(f32, f32); 2],
+    dst_points: [(f32, f32); 2],
+) -> (f32, f32, f32) {
+    let [(src_x0, src_y0), (src_x1, src_y1)] = src_points;
+    let [(dst_x0, dst_y0), (dst_x1, dst_y1)] = dst_points;
+
+    // Project the target direction onto the source direction for a least squares fit of the scale.
+    let (src_dx, src_dy) = (src_x1 - src_x0, src_y1 - src_y0);
+    let (dst_dx, dst_dy) = (dst_x1 - dst_x0, dst_y1 - dst_y0);
+    let scale = (src_dx * dst_dx + src_dy * dst_dy) / (src_dx * src_dx + src_dy * src_dy);
+
+    // Align the midpoints, which splits any remaining error between the points.
+    let translate_x = (dst_x0 + dst_x1) / 2f32 - scale * (src_x0 + src_x1) / 2f32;
+    let translate_y = (dst_y0 + dst_y1) / 2f32 - scale * (src_y0 + src_y1) / 2f32;
+    (scale, translate_x, translate_y)
+}
+
+/// Downsamples `render` with [downsample_linear] by the largest power of two
+/// that keeps the remaining `scale` at or above 0.5.
+/// Bilinear interpolation only uses the 4 nearest pixels,
+/// so resizing a render to less than half its size without prefiltering skips pixels and aliases.
+/// [create_chara_image] applies this automatically.
+pub fn prefilter_for_scale(render: &RgbaImage, scale: f32) -> RgbaImage {
+    let factor = prefilter_factor(scale);
+    if factor > 1 {
+        downsample_linear(render, factor, DEFAULT_GAMMA)
+    } else {
+        render.clone()
+    }
+}
+
+fn prefilter_factor(scale: f32) -> u32 {
+    let mut factor = 1;
+    while scale * (factor * 2) as f32 <= 1.0f32 {
+        factor *= 2;
+    }
+    factor
+}
+
+/// Transforms `render` into a new image with the given dimensions
+/// by scaling and then translating the render.
+/// Areas outside the render are transparent.
+pub fn warp_render(
+    render: &RgbaImage,
+    target_width: u32,
+    target_height: u32,
+    scale: f32,
+    translate_x: f32,
+    translate_y: f32,
+) -> RgbaImage {
+    warp_render_affine(
+        render,
+        (target_width, target_height),
+        scale,
+        0f32,
+        (translate_x, translate_y),
+        Interpolation::Bilinear,
+        Rgba([0u8, 0u8, 0u8, 0u8]),
+    )
+}
+
+/// Transforms `render` like [warp_render] but also rotates the render clockwise by `rotation` radians
+/// around its top left corner before applying the translation.
+/// Areas outside the render use the `fill` color.
+/// Interpolation uses linear colors, so the edges between light and dark pixels aren't darkened by the gamma encoding.
+pub fn warp_render_affine(
+    render: &RgbaImage,
+    target_dimensions: (u32, u32),
+    scale: f32,
+    rotation: f32,
+    translation: (f32, f32),
+    interpolation: Interpolation,
+    fill: Rgba<u8>,
+) -> RgbaImage {
+    let (translate_x, translate_y) = translation;
+    let mut output = ImageBuffer::new(target_dimensions.0, target_dimensions.1);
+
+    // Bilinear interpolation treats the last row and column as out of bounds.
+    // Repeat the edge pixels, so the entire render is used.
+    let (width, height) = render.dimensions();
+    let padded = RgbaImage::from_fn(width + 1, height + 1, |x, y| {
+        *render.get_pixel(min(x, width - 1), min(y, height - 1))
+    });
+
+    // warp_into_with defines the preimage, so invert the transformation.
+    let (sin, cos) = rotation.sin_cos();
+    let mapping = |x, y| {
+        let x = x - translate_x;
+        let y = y - translate_y;
+        ((x * cos + y * sin) / scale, (y * cos - x * sin) / scale)
+    };
+
+    // Nearest neighbor doesn't blend pixels, so the encoding doesn't matter.
+    if interpolation == Interpolation::Nearest {
+        warp_into_with(&padded, mapping, interpolation, fill, &mut output);
+        return output;
+    }
+
+    // Interpolating gamma encoded values makes the transition between light and dark pixels too dark.
+    // Keep straight alpha to preserve the colors of transparent pixels.
+    let to_linear = |pixel: &Rgba<u8>| {
+        let (r, g, b, a) = normalize_rgba_u8(pixel);
+        let linear = |c: f32| c.powf(DEFAULT_GAMMA);
+        Rgba([linear(r), linear(g), linear(b), a])
+    };
+    let linear = ImageBuffer::from_fn(width + 1, height + 1, |x, y| {
+        to_linear(padded.get_pixel(x, y))
+    });
+    let mut linear_output = ImageBuffer::new(target_dimensions.0, target_dimensions.1);
+    warp_into_with(
+        &linear,
+        mapping,
+        interpolation,
+        to_linear(&fill),
+        &mut linear_output,
+    );
+
+    // Bicubic interpolation can overshoot, so clamp before encoding.
+    let encode = |c: f32| to_u8_clamped(c.max(0f32).powf(1f32 / DEFAULT_GAMMA));
+    for (pixel, linear_pixel) in output.pixels_mut().zip(linear_output.pixels()) {
+        *pixel = Rgba([
+            encode(linear_pixel[0]),
+            encode(linear_pixel[1]),
+            encode(linear_pixel[2]),
+            to_u8_clamped(linear_pixel[3]),
+        ]);
+    }
+    output
+}
+
+/// Converts a color from Minecraft to match Smash ultimate using the following formula:
+/// `ultimate = (minecraft ^ 0.72) * 0.72`
+pub fn color_correct(color: &Rgba<u8>) -> Rgba<u8> {
+    color_correct_with(color, 0.72f32, 0.72f32)
+}
+
+/// Adjusts the contrast of the RGB channels using the following formula:
+/// `output = (input ^ gamma) * scale`
+/// The alpha channel is unchanged.
+pub fn color_correct_with(color: &Rgba<u8>, gamma: f32, scale: f32) -> Rgba<u8> {
+    let reduce_contrast = |c: f32| c.powf(gamma) * scale;
+    let (r, g, b, _) = normalize_rgba_u8(color);
+    Rgba([
+        to_u8_clamped(reduce_contrast(r)),
+        to_u8_clamped(reduce_contrast(g)),
+        to_u8_clamped(reduce_contrast(b)),
+        color[3],
+    ])
+}
+
+/// Converts a color corrected with [color_correct] back to Minecraft using the following formula:
+/// `minecraft = (ultimate / 0.72) ^ (1 / 0.72)`
+/// Channels above the brightest corrected value of 184 are clamped to 255.
+pub fn color_correct_inverse(color: &Rgba<u8>) -> Rgba<u8> {
+    color_correct_inverse_with(color, 0.72f32, 0.72f32)
+}
+
+/// Reverses [color_correct_with] for the same `gamma` and `scale` using the following formula:
+/// `input = (output / scale) ^ (1 / gamma)`
+/// The alpha channel is unchanged.
+pub fn color_correct_inverse_with(color: &Rgba<u8>, gamma: f32, scale: f32) -> Rgba<u8> {
+    let restore_contrast = |c: f32| (c / scale).powf(1f32 / gamma);
+    let (r, g, b, _) = normalize_rgba_u8(color);
+    Rgba([
+        to_u8_clamped(restore_contrast(r)),
+        to_u8_clamped(restore_contrast(g)),
+        to_u8_clamped(restore_contrast(b)),
+        color[3],
+    ])
+}
+
+/// Converts a color from Minecraft to match Smash Ultimate like [color_correct]
+/// but applies the adjustment to linear sRGB values instead of the encoded values.
+/// This is slower but more accurate for colors far from black or white.
+pub fn color_correct_linear(color: &Rgba<u8>) -> Rgba<u8> {
+    color_correct_linear_with(color, 0.72f32, 0.72f32)
+}
+
+/// Adjusts the contrast of the RGB channels like [color_correct_with]
+/// after decoding the sRGB encoded channels to linear values.
+/// The result is encoded to sRGB again, and the alpha channel is unchanged.
+pub fn color_correct_linear_with(color: &Rgba<u8>, gamma: f32, scale: f32) -> Rgba<u8> {
+    let reduce_contrast = |c: f32| linear_to_srgb(srgb_to_linear(c).powf(gamma) * scale);
+    let (r, g, b, _) = normalize_rgba_u8(color);
+    Rgba([
+        to_u8_clamped(reduce_contrast(r)),
+        to_u8_clamped(reduce_contrast(g)),
+        to_u8_clamped(reduce_contrast(b)),
+        color[3],
+    ])
+}
+
+/// Settings for [color_correct_with_options].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorCorrectOptions {
+    /// The exponent for the RGB channels.
+    pub gamma: f32,
+    /// The factor for the RGB channels after applying `gamma`.
+    pub scale: f32,
+    /// Adjust linear values like [color_correct_linear_with] instead of the encoded values.
+    pub linear: bool,
+    /// Leave fully transparent pixels unchanged.
+    /// Transparent pixels can still have colors that bleed into the edges of the render with bilinear sampling,
+    /// so this preserves those colors and skips unnecessary work.
+    pub skip_transparent: bool,
+}
+
+impl Default for ColorCorrectOptions {
+    fn default() -> Self {
+        Self {
+            gamma: 0.72f32,
+            scale: 0.72f32,
+            linear: false,
+            skip_transparent: false,
+        }
+    }
+}
+
+/// Adjusts the contrast of the RGB channels using the settings in `options`.
+/// The alpha channel is unchanged.
+pub fn color_correct_with_options(color: &Rgba<u8>, options: &ColorCorrectOptions) -> Rgba<u8> {
+    if options.skip_transparent && color[3] == 0u8 {
+        return *color;
+    }
+
+    if options.linear {
+        color_correct_linear_with(color, options.gamma, options.scale)
+    } else {
+        color_correct_with(color, options.gamma, options.scale)
+    }
+}
+
+/// Applies [color_correct] to every pixel of `image`.
+pub fn color_correct_image(image: &mut RgbaImage) {
+    color_correct_image_with_options(image, &ColorCorrectOptions::default())
+}
+
+/// Returns a copy of `image` with [color_correct] applied to every pixel.
+pub fn color_corrected(image: &RgbaImage) -> RgbaImage {
+    let mut corrected = image.clone();
+    color_correct_image(&mut corrected);
+    corrected
+}
+
+/// Applies [color_correct_with_options] to every pixel of `image`.
+pub fn color_correct_image_with_options(image: &mut RgbaImage, options: &ColorCorrectOptions) {
+    for pixel in image.pixels_mut() {
+        *pixel = color_correct_with_options(pixel, options);
+    }
+}
+
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045f32 {
+        c / 12.92f32
+    } else {
+        ((c + 0.055f32) / 1.055f32).powf(2.4f32)
+    }
+}
+
+fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308f32 {
+        c * 12.92f32
+    } else {
+        1.055f32 * c.powf(1.0f32 / 2.4f32) - 0.055f32
+    }
+}
+
+// The per layer settings for blend_layer_with_base.
+#[derive(Debug, Clone, Copy)]
+struct LayerBlend {
+    opacity: f32,
+    lighting_scale: f32,
+    debug_uvs: bool,
+    flip_v: bool,
+    wrap_mode: WrapMode,
+    texel_offset: f32,
+    lighting_mode: LightingMode,
+}
+
+impl Default for LayerBlend {
+    fn default() -> Self {
+        Self {
+            opacity: 1.0f32,
+            lighting_scale: DEFAULT_LIGHTING_SCALE,
+            debug_uvs: false,
+            flip_v: true,
+            wrap_mode: WrapMode::Clamp,
+            texel_offset: DEFAULT_TEXEL_OFFSET,
+            lighting_mode: LightingMode::Multiply,
+        }
+    }
+}
+
+fn blend_layer_with_base<P: OutputPixel, S: SkinPixel>(
+    base: &mut OutputImage<P>,
+    layer_uvs_lighting: &UvMap,
+    texture: &OutputImage<S>,
+    blend: LayerBlend,
+    gamma: f32,
+    smooth_uvs: bool,
+    mut ids: Option<(&mut GrayImage, u8)>,
+) -> bool {
+    // The output may be larger than the UV map for higher resolution renders.
+    let scale = base.width() / layer_uvs_lighting.width();
+    let mut drawn = false;
+
+    for x in 0..base.width() {
+        for y in 0..base.height() {
+            // Skip pixels outside the masked region to improve performance.
+            let uv_rgba = layer_uvs_lighting.get_pixel(x / scale, y / scale);
+            if uv_rgba[3] == 0u16 {
+                continue;
+            }
+
+            // Skip fully transparent sampled texels to improve performance.
+            let (u, v, lighting, uv_alpha) = normalize_rgba_u16(uv_rgba);
+            let (u, v, lighting) = if smooth_uvs && scale > 1 {
+                // Only blend UVs within a texel of a 64x64 skin to avoid blending different faces.
+                sample_uv_map_bilinear(
+                    layer_uvs_lighting,
+                    (x as f32 + 0.5f32) / scale as f32,
+                    (y as f32 + 0.5f32) / scale as f32,
+                    1f32 / 64f32,
+                )
+            } else {
+                (u, v, lighting)
+            };
+            let (layer_r, layer_g, layer_b, layer_alpha) = if blend.debug_uvs {
+                // Draw the entire masked region even where the skin is transparent.
+                (u, v, 0f32, 1f32)
+            } else {
+                sample_texture_with_flip(
+                    texture,
+                    u,
+                    v,
+                    blend.wrap_mode,
+                    blend.flip_v,
+                    blend.texel_offset,
+                )
+                .to_normalized()
+            };
+            if layer_alpha == 0f32 {
+                continue;
+            }
+
+            // See DEFAULT_LIGHTING_SCALE for why the lighting needs to be scaled.
+            let apply_lighting = |color: f32, light: f32| {
+                if blend.debug_uvs {
+                    color
+                } else {
+                    blend
+                        .lighting_mode
+                        .apply(color, light * blend.lighting_scale)
+                }
+            };
+
+            let (base_r, base_g, base_b, base_a) = base.get_pixel(x, y).to_encoded(gamma);
+
+            // Use the uv map alpha as well to prevent blending outside the masked region.
+            // Weight the layer by its contribution to the combined coverage.
+            // This avoids dark edges from blending with the color of transparent pixels
+            // while still blending edges with an opaque background.
+            let coverage = (layer_alpha * uv_alpha * blend.opacity).min(1f32);
+            if coverage <= 0f32 {
+                continue;
+            }
+            let layer_weight = coverage / (coverage + base_a * (1f32 - coverage));
+
+            let get_result = |base: f32, layer: f32| {
+                let lighting_result = apply_lighting(layer, lighting);
+
+                // Skip the costly floating point gamma correction and blending if possible.
+                // A weight of 1.0 only happens for fully covered pixels or a transparent base,
+                // so the base can't show through for base or overlay layers.
+                if layer_weight < 1f32 {
+                    alpha_blend(base, lighting_result, layer_weight, gamma)
+                } else {
+                    lighting_result
+                }
+            };
+
+            let r = get_result(base_r, layer_r);
+            let g = get_result(base_g, layer_g);
+            let b = get_result(base_b, layer_b);
+            // Composite the alpha "over" the base so overlapping partial coverage stays partially transparent.
+            let alpha_final = coverage + base_a * (1f32 - coverage);
+
+            *base.get_pixel_mut(x, y) = P::from_encoded(r, g, b, alpha_final, gamma);
+
+            if let Some((ids, id)) = &mut ids {
+                ids.put_pixel(x, y, image::Luma([*id]));
+            }
+            drawn = true;
+        }
+    }
+    drawn
+}
+
+// Apply adjustments to the entire render after drawing all the layers.
+fn finish_render<P: OutputPixel>(
+    mut output: OutputImage<P>,
+    options: &RenderOptions,
+    supersample: u32,
+) -> OutputImage<P> {
+    if options.mirror {
+        image::imageops::flip_horizontal_in_place(&mut output);
+    }
+    if supersample > 1 {
+        output = downsample(&output, supersample, options.blend_gamma);
+    }
+    if let Some((color, thickness)) = options.outline {
+        draw_outline(&mut output, color, thickness, options.blend_gamma);
+    }
+    if options.premultiply {
+        premultiply(&mut output, options.blend_gamma);
+    }
+    output
+}
+
+// Draw the outline behind the existing pixels by dilating the alpha with a square of the given radius.
+fn draw_outline<P: OutputPixel>(
+    image: &mut OutputImage<P>,
+    color: Rgba<u8>,
+    thickness: u32,
+    gamma: f32,
+) {
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    let radius = thickness as usize;
+    let alpha: Vec<f32> = image.pixels().map(|p| p.to_encoded(gamma).3).collect();
+
+    // The maximum over a square is separable, so dilate the rows and then the columns.
+    let mut rows = vec![0f32; alpha.len()];
+    for y in 0..height {
+        for x in 0..width {
+            let start = x.saturating_sub(radius);
+            let end = (x + radius).min(width - 1);
+            rows[y * width + x] = alpha[y * width + start..=y * width + end]
+                .iter()
+                .fold(0f32, |a, b| a.max(*b));
+        }
+    }
+    let mut dilated = vec![0f32; alpha.len()];
+    for y in 0..height {
+        for x in 0..width {
+            let start = y.saturating_sub(radius);
+            let end = (y + radius).min(height - 1);
+            dilated[y * width + x] = (start..=end)
+                .map(|y| rows[y * width + x])
+                .fold(0f32, f32::max);
+        }
+    }
+
+    let (outline_r, outline_g, outline_b, outline_a) = normalize_rgba_u8(&color);
+    for (pixel, dilated_alpha) in image.pixels_mut().zip(dilated) {
+        let (r, g, b, a) = pixel.to_encoded(gamma);
+        let outline_alpha = dilated_alpha * outline_a;
+        if a >= 1f32 || outline_alpha <= 0f32 {
+            continue;
+        }
+
+        // Composite the pixel "over" the outline.
+        let alpha_final = a + outline_alpha * (1f32 - a);
+        let weight = a / alpha_final;
+        *pixel = P::from_encoded(
+            alpha_blend(outline_r, r, weight, gamma),
+            alpha_blend(outline_g, g, weight, gamma),
+            alpha_blend(outline_b, b, weight, gamma),
+            alpha_final,
+            gamma,
+        );
+    }
+}
+
+/// Multiplies the color channels of `image` by alpha after linearizing with `gamma`.
+/// The colors are gamma encoded again afterwards, so only the alpha handling changes.
+pub fn premultiply_alpha(image: &mut RgbaImage, gamma: f32) {
+    premultiply(image, gamma)
+}
+
+fn premultiply<P: OutputPixel>(image: &mut OutputImage<P>, gamma: f32) {
+    for pixel in image.pixels_mut() {
+        let (r, g, b, a) = pixel.to_encoded(gamma);
+        let multiply = |c: f32| (c.powf(gamma) * a).powf(1.0f32 / gamma);
+        *pixel = P::from_encoded(multiply(r), multiply(g), multiply(b), a, gamma);
+    }
+}
+
+/// Divides the color channels of `image` by alpha after linearizing with `gamma`.
+/// This undoes [premultiply_alpha] for skins exported with premultiplied alpha,
+/// which would otherwise render too dark for partially transparent pixels.
+/// Fully transparent and fully opaque pixels are unchanged.
+pub fn unpremultiply_alpha(image: &mut RgbaImage, gamma: f32) {
+    for pixel in image.pixels_mut() {
+        if pixel[3] == 0u8 || pixel[3] == 255u8 {
+            continue;
+        }
+        let (r, g, b, a) = normalize_rgba_u8(pixel);
+        let divide = |c: f32| to_u8_clamped((c.powf(gamma) / a).powf(1.0f32 / gamma));
+        *pixel = Rgba([divide(r), divide(g), divide(b), pixel[3]]);
+    }
+}
+
+/// Reduces the dimensions of `image` by `factor` by averaging each `factor` x `factor` block of pixels.
+/// Colors are averaged after linearizing with `gamma` and weighted by alpha
+/// to avoid darkening edges with the color of transparent pixels.
+pub fn downsample_linear(image: &RgbaImage, factor: u32, gamma: f32) -> RgbaImage {
+    downsample(image, factor, gamma)
+}
+
+fn downsample<P: OutputPixel>(image: &OutputImage<P>, factor: u32, gamma: f32) -> OutputImage<P> {
+    let sample_count = (factor * factor) as f32;
+    ImageBuffer::from_fn(image.width() / factor, image.height() / factor, |x, y| {
+        let mut sum = [0f32; 4];
+        for block_x in 0..factor {
+            for block_y in 0..factor {
+                let pixel = image.get_pixel(x * factor + block_x, y * factor + block_y);
+                let (r, g, b, a) = pixel.to_encoded(gamma);
+                sum[0] += r.powf(gamma) * a;
+                sum[1] += g.powf(gamma) * a;
+                sum[2] += b.powf(gamma) * a;
+                sum[3] += a;
+            }
+        }
+
+        if sum[3] == 0f32 {
+            return P::from_encoded(0f32, 0f32, 0f32, 0f32, gamma);
+        }
+
+        let average = |c: f32| (c / sum[3]).powf(1.0f32 / gamma);
+        P::from_encoded(
+            average(sum[0]),
+            average(sum[1]),
+            average(sum[2]),
+            sum[3] / sample_count,
+            gamma,
+        )
+    })
+}
+
+/// A rectangular region of an image using coordinates from 0.0 to 1.0.
+/// The start is inclusive, and the end is exclusive.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Region {
+    pub x_start: f32,
+    pub x_end: f32,
+    pub y_start: f32,
+    pub y_end: f32,
+}
+
+impl Region {
+    /// Creates a region from the start and end coordinates for each axis.
+    pub const fn new(x_start: f32, x_end: f32, y_start: f32, y_end: f32) -> Self {
+        Self {
+            x_start,
+            x_end,
+            y_start,
+            y_end,
+        }
+    }
+}
+
+// Convert the region bounds to a range of pixels by flooring the start and ceiling the end.
+// Bounds within a small tolerance of a pixel edge are snapped to the edge first,
+// so float error like 1/3 * 3 = 1.0000001 doesn't include an extra row or column.
+fn region_pixel_range(start: f32, end: f32, size: u32) -> (u32, u32) {
+    let to_pixels = |f: f32| {
+        let pixels = f * size as f32;
+        if (pixels - pixels.round()).abs() < 1e-3f32 {
+            pixels.round()
+        } else {
+            pixels
+        }
+    };
+    let start = to_pixels(start).floor().max(0f32) as u32;
+    let end = (to_pixels(end).ceil().max(0f32) as u32).min(size);
+    (start, end)
+}
+
+fn has_pixel_in_region(
+    image: &RgbaImage,
+    x_start: f32,
+    x_end: f32,
+    y_start: f32,
+    y_end: f32,
+) -> bool {
+    has_pixels_in_region(image, Region::new(x_start, x_end, y_start, y_end), 1, 1)
+}
+
+/// Returns `true` if `region` of `image` has at least `min_count` pixels with an alpha of at least `min_alpha`.
+/// Pixels partially covered by the region count as inside the region.
+pub fn has_pixels_in_region(
+    image: &RgbaImage,
+    region: Region,
+    min_alpha: u8,
+    min_count: u32,
+) -> bool {
+    let (x_start, x_end) = region_pixel_range(region.x_start, region.x_end, image.width());
+    let (y_start, y_end) = region_pixel_range(region.y_start, region.y_end, image.height());
+
+    // Stop scanning once enough pixels are found.
+    visible_pixels(image, x_start..x_end, y_start..y_end, min_alpha)
+        .enumerate()
+        .any(|(i, _)| i + 1 >= min_count as usize)
+}
+
+/// Crops `image` to the smallest rectangle containing all pixels with nonzero alpha.
+/// The rectangle is in the coordinates of `image` for adjusting transformations like [create_chara_image]
+/// by subtracting the rectangle's position from the translation.
+/// Images without any visible pixels are returned unchanged.
+pub fn trim_transparent(image: &RgbaImage) -> (RgbaImage, Rect) {
+    let bounds = visible_pixels(image, 0..image.width(), 0..image.height(), 1).fold(
+        None,
+        |bounds: Option<(u32, u32, u32, u32)>, (x, y)| match bounds {
+            Some((x_min, y_min, x_max, y_max)) => {
+                Some((x_min.min(x), y_min.min(y), x_max.max(x), y_max.max(y)))
+            }
+            None => Some((x, y, x, y)),
+        },
+    );
+
+    let rect = match bounds {
+        Some((x_min, y_min, x_max, y_max)) => Rect {
+            x: x_min,
+            y: y_min,
+            width: x_max - x_min + 1,
+            height: y_max - y_min + 1,
+        },
+        None => Rect {
+            x: 0,
+            y: 0,
+            width: image.width(),
+            height: image.height(),
+        },
+    };
+    let trimmed = image::imageops::crop_imm(image, rect.x, rect.y, rect.width, rect.height);
+    (trimmed.to_image(), rect)
+}
+
+fn visible_pixels(
+    image: &RgbaImage,
+    x_range: std::ops::Range<u32>,
+    y_range: std::ops::Range<u32>,
+    min_alpha: u8,
+) -> impl Iterator<Item = (u32, u32)> + '_ {
+    x_range
+        .flat_map(move |x| y_range.clone().map(move |y| (x, y)))
+        .filter(move |(x, y)| image.get_pixel(*x, *y)[3] >= min_alpha)
+}
+
+fn blend_alpha(current: &mut RgbaImage, reference: &RgbaImage, mask_mode: MaskMode) {
+    // Use the alpha of current with reference as a mask.
+    for x in 0..current.width() {
+        for y in 0..current.height() {
+            let current = current.get_pixel_mut(x, y);
+            let target = reference.get_pixel(x, y);
+            let alpha = match mask_mode {
+                MaskMode::Min => min(current[3], target[3]),
+                MaskMode::Multiply => {
+                    // Round to the nearest value for the product of the normalized alphas.
+                    ((current[3] as u32 * target[3] as u32 + 127) / 255) as u8
+                }
+            };
+            *current = Rgba([current[0], current[1], current[2], alpha]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cape_behind_torso() {
+        let skin_texture = RgbaImage::from_pixel(64, 64, Rgba([0u8, 0u8, 255u8, 255u8]));
+        let cape = RgbaImage::from_pixel(64, 32, Rgba([255u8, 0u8, 0u8, 255u8]));
+        let render = create_render_with_options(
+            &skin_texture,
+            &RenderOptions {
+                cape: Some(cape),
+                ..Default::default()
+            },
+        );
+
+        // The cape is only visible past the edges of the body.
+        let cape_pixel = render.get_pixel(940, 1250);
+        assert!(cape_pixel[0] > 0u8 && cape_pixel[2] == 0u8);
+
+        // The torso is drawn over the cape.
+        let torso_pixel = render.get_pixel(735, 650);
+        assert!(torso_pixel[0] == 0u8 && torso_pixel[2] > 0u8);
+    }
+
+    #[test]
+    fn test_disable_overlays() {
+        let skin_texture = RgbaImage::from_pixel(64, 64, Rgba([0u8, 0u8, 255u8, 255u8]));
+
+        // This pixel is only covered by the head overlay.
+        let render = create_render(&skin_texture);
+        assert!(render.get_pixel(500, 200)[3] > 0u8);
+
+        let render = create_render_with_options(
+            &skin_texture,
+            &RenderOptions {
+                disable_overlays: true,
+                ..Default::default()
+            },
+        );
+        assert_eq!(render.get_pixel(500, 200), &Rgba([0u8, 0u8, 0u8, 0u8]));
+
+        let render = create_render_with_options(
+            &skin_texture,
+            &RenderOptions {
+                disable_overlays: true,
+                hat: Some(true),
+                ..Default::default()
+            },
+        );
+        assert!(render.get_pixel(500, 200)[3] > 0u8);
+    }
+
+    #[test]
+    fn test_background_color() {
+        let white = Rgba([255u8, 255u8, 255u8, 255u8]);
+        let render = create_render_with_options(
+            &image::open("sample.png").unwrap().into_rgba8(),
+            &RenderOptions {
+                background: Some(white),
+                ..Default::default()
+            },
+        );
+
+        let (width, height) = render.dimensions();
+        assert_eq!(render.get_pixel(0, 0), &white);
+        assert_eq!(render.get_pixel(width - 1, 0), &white);
+        assert_eq!(render.get_pixel(0, height - 1), &white);
+        assert_eq!(render.get_pixel(width - 1, height - 1), &white);
+    }
+
+    #[test]
+    fn test_render_scale() {
+        let skin_texture = image::open("sample.png").unwrap().into_rgba8();
+        let render = create_render(&skin_texture);
+        let render_2x = create_render_with_options(
+            &skin_texture,
+            &RenderOptions {
+                scale: 2,
+                ..Default::default()
+            },
+        );
+        assert_eq!(render_2x.width(), render.width() * 2);
+        assert_eq!(render_2x.height(), render.height() * 2);
+    }
+
+    #[test]
+    fn test_render_hdr_precision() {
+        let skin_texture = RgbaImage::from_pixel(64, 64, Rgba([220u8, 220u8, 220u8, 255u8]));
+        let render = create_render(&skin_texture);
+        let render_hdr = create_render_hdr(&skin_texture);
+        assert_eq!(render.dimensions(), render_hdr.dimensions());
+
+        // Some pixels that round to the maximum 8 bit value are still below the maximum 16 bit value.
+        assert!(render
+            .enumerate_pixels()
+            .any(|(x, y, pixel)| pixel[0] == 255u8 && render_hdr.get_pixel(x, y)[0] < 65535u16));
+
+        // The 16 bit render stores linear values, so gamma encoding gives the 8 bit render.
+        for (x, y, pixel) in render.enumerate_pixels().step_by(97) {
+            let (r, _, _, a) = render_hdr.get_pixel(x, y).to_encoded(DEFAULT_GAMMA);
+            assert!((to_u8_clamped(r) as i32 - pixel[0] as i32).abs() <= 1);
+            assert_eq!(to_u8_clamped(a), pixel[3]);
+        }
+    }
+
+    #[test]
+    fn test_render_context_threads() {
+        let context = std::sync::Arc::new(RenderContext::new());
+        let colors = [[255u8, 0u8, 0u8], [0u8, 255u8, 0u8], [0u8, 0u8, 255u8]];
+
+        let threads: Vec<_> = colors
+            .iter()
+            .map(|&[r, g, b]| {
+                let context = context.clone();
+                std::thread::spawn(move || {
+                    let skin_texture = RgbaImage::from_pixel(64, 64, Rgba([r, g, b, 255u8]));
+                    context.render(&skin_texture, &RenderOptions::default())
+                })
+            })
+            .collect();
+
+        // Each thread renders its own skin with the shared UV maps.
+        for (thread, [r, g, b]) in threads.into_iter().zip(colors.iter()) {
+            let render = thread.join().unwrap();
+            let pixel = render.get_pixel(735, 650);
+            assert_eq!(pixel[0] > 0u8, *r > 0u8);
+            assert_eq!(pixel[1] > 0u8, *g > 0u8);
+            assert_eq!(pixel[2] > 0u8, *b > 0u8);
+        }
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_render_many_order() {
+        let skin_textures = vec![
+            RgbaImage::from_pixel(64, 64, Rgba([255u8, 0u8, 0u8, 255u8])),
+            RgbaImage::from_pixel(64, 64, Rgba([0u8, 0u8, 255u8, 255u8])),
+        ];
+        let context = RenderContext::new();
+        let renders = context.render_many(&skin_textures, &RenderOptions::default());
+        assert!(renders[0].get_pixel(735, 650)[0] > 0u8);
+        assert!(renders[1].get_pixel(735, 650)[2] > 0u8);
+
+        let count = std::sync::atomic::AtomicUsize::new(0);
+        context.render_streaming(
+            skin_textures.into_iter(),
+            &RenderOptions::default(),
+            |i, render| {
+                assert_eq!(render.get_pixel(735, 650)[0] > 0u8, i == 0);
+                count.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            },
+        );
+        assert_eq!(count.into_inner(), 2);
+    }
+
+    #[test]
+    fn test_create_render_with_progress() {
+        let skin_texture = image::open("sample.png").unwrap().into_rgba8();
+        let mut fractions = Vec::new();
+        let render = create_render_with_progress(&skin_texture, &mut |f| fractions.push(f));
+        assert_eq!(render, create_render(&skin_texture));
+
+        // Each of the base layers is drawn at least once.
+        assert!(fractions.len() >= 6);
+        assert!(fractions.windows(2).all(|w| w[0] < w[1]));
+        assert!(fractions[0] > 0f32);
+        assert_eq!(*fractions.last().unwrap(), 1f32);
+    }
+
+    #[test]
+    fn test_premultiply_alpha() {
+        let mut image = RgbaImage::from_pixel(1, 1, Rgba([255u8, 255u8, 255u8, 128u8]));
+        premultiply_alpha(&mut image, DEFAULT_GAMMA);
+        // Half of white in linear space is brighter than 128 after gamma encoding.
+        assert_eq!(image.get_pixel(0, 0), &Rgba([186u8, 186u8, 186u8, 128u8]));
+
+        let mut image = RgbaImage::from_pixel(1, 1, Rgba([255u8, 255u8, 255u8, 128u8]));
+        premultiply_alpha(&mut image, 1.0f32);
+        assert_eq!(image.get_pixel(0, 0), &Rgba([128u8, 128u8, 128u8, 128u8]));
+
+        // Opaque pixels are unchanged.
+        let skin_texture = image::open("sample.png").unwrap().into_rgba8();
+        let render = create_render(&skin_texture);
+        let premultiplied = create_render_with_options(
+            &skin_texture,
+            &RenderOptions {
+                premultiply: true,
+                ..Default::default()
+            },
+        );
+        for (x, y, pixel) in premultiplied.enumerate_pixels() {
+            if pixel[3] == 255u8 {
+                assert_eq!(pixel, render.get_pixel(x, y));
+            }
+        }
+    }
+
+    #[test]
+    fn test_unpremultiply_alpha() {
+        // A gradient of orange with increasing alpha.
+        let original = RgbaImage::from_fn(256, 1, |x, _| Rgba([255u8, 128u8, 64u8, x as u8]));
+        let mut image = original.clone();
+        premultiply_alpha(&mut image, DEFAULT_GAMMA);
+        unpremultiply_alpha(&mut image, DEFAULT_GAMMA);
+
+        // Rounding loses precision for very transparent pixels.
+        for (pixel, expected) in image.pixels().zip(original.pixels()).skip(16) {
+            for c in 0..4 {
+                assert!(
+                    (pixel[c] as i32 - expected[c] as i32).abs() <= 2,
+                    "{:?}",
+                    pixel
+                );
+            }
+        }
+        assert_eq!(image.get_pixel(0, 0), &Rgba([0u8, 0u8, 0u8, 0u8]));
+    }
+
+    #[test]
+    fn test_render_hd_skin() {
+        let skin_texture = image::open("sample.png").unwrap().into_rgba8();
+        let hd_skin_texture =
+            image::imageops::resize(&skin_texture, 128, 128, image::imageops::Nearest);
+        assert_eq!(
+            render_layer(&skin_texture, Layer::Head),
+            render_layer(&hd_skin_texture, Layer::Head)
+        );
+
+        // Alternate colors for each HD texel, which averages to gray for a 64x64 skin.
+        let black = Rgba([0u8, 0u8, 0u8, 255u8]);
+        let white = Rgba([255u8, 255u8, 255u8, 255u8]);
+        let checker_texture =
+            RgbaImage::from_fn(
+                128,
+                128,
+                |x, y| if (x + y) % 2 == 0 { black } else { white },
+            );
+        let render = render_layer(&checker_texture, Layer::Head);
+        assert!(render.pixels().any(|p| p[3] == 255u8 && p[0] == 0u8));
+        assert!(render.pixels().any(|p| p[3] == 255u8 && p[0] > 200u8));
+    }
+
+    #[test]
+    fn test_create_render_with_ids() {
+        let skin_texture = image::open("sample.png").unwrap().into_rgba8();
+        let (render, ids) = create_render_with_ids(&skin_texture);
+        assert_eq!(render, create_render(&skin_texture));
+        assert_eq!(render.dimensions(), ids.dimensions());
+
+        // Background pixels aren't part of any layer.
+        assert_eq!(ids.get_pixel(0, 0)[0], 0u8);
+        assert!(render.get_pixel(737, 220)[3] > 0u8);
+        assert_eq!(ids.get_pixel(737, 220)[0], Layer::Head.region_id());
+        assert_eq!(ids.get_pixel(735, 650)[0], Layer::Chest.region_id());
+    }
+
+    #[test]
+    fn test_has_pixels_in_region_thresholds() {
+        let mut image = RgbaImage::new(8, 8);
+        image.put_pixel(5, 1, Rgba([255u8, 255u8, 255u8, 10u8]));
+        let region = Region {
+            x_start: 0.5f32,
+            x_end: 1.0f32,
+            y_start: 0.0f32,
+            y_end: 0.5f32,
+        };
+
+        assert!(has_pixels_in_region(&image, region, 1, 1));
+        assert!(has_pixels_in_region(&image, region, 10, 1));
+        assert!(!has_pixels_in_region(&image, region, 11, 1));
+        assert!(!has_pixels_in_region(&image, region, 1, 2));
+
+        // The pixel is outside this region.
+        let region = Region {
+            x_start: 0.0f32,
+            x_end: 0.5f32,
+            ..region
+        };
+        assert!(!has_pixels_in_region(&image, region, 1, 1));
+    }
+
+    #[test]
+    fn test_trim_transparent() {
+        let skin_texture = image::open("sample.png").unwrap().into_rgba8();
+        let render = create_render(&skin_texture);
+        let (trimmed, rect) = trim_transparent(&render);
+        assert!(trimmed.width() < render.width() && trimmed.height() < render.height());
+        assert_eq!(
+            (trimmed.width(), trimmed.height()),
+            (rect.width, rect.height)
+        );
+        assert_eq!(
+            trimmed,
+            image::imageops::crop_imm(&render, rect.x, rect.y, rect.width, rect.height).to_image()
+        );
+
+        // Each edge should have at least one visible pixel.
+        let (width, height) = trimmed.dimensions();
+        let visible = |x: u32, y: u32| trimmed.get_pixel(x, y)[3] > 0u8;
+        assert!((0..width).any(|x| visible(x, 0)));
+        assert!((0..width).any(|x| visible(x, height - 1)));
+        assert!((0..height).any(|y| visible(0, y)));
+        assert!((0..height).any(|y| visible(width - 1, y)));
+
+        // Nothing should be trimmed from an image without any visible pixels.
+        let empty = RgbaImage::new(8, 4);
+        let (trimmed, rect) = trim_transparent(&empty);
+        assert_eq!(trimmed, empty);
+        assert_eq!((rect.x, rect.y, rect.width, rect.height), (0, 0, 8, 4));
+    }
+
+    #[test]
+    fn test_has_pixels_in_region_boundaries() {
+        // Bounds exactly on pixel edges don't include the neighboring columns.
+        let region = Region::new(0.625, 0.875, 0.0, 1.0);
+        let mut image = RgbaImage::new(64, 64);
+        image.put_pixel(39, 0, Rgba([255u8; 4]));
+        image.put_pixel(56, 0, Rgba([255u8; 4]));
+        assert!(!has_pixels_in_region(&image, region, 1, 1));
+        image.put_pixel(55, 0, Rgba([255u8; 4]));
+        assert!(has_pixels_in_region(&image, region, 1, 1));
+
+        // 1/3 * 3 isn't exactly 1.0 as a float, so only the middle pixel is in the region.
+        let region = Region::new(1.0 / 3.0, 2.0 / 3.0, 0.0, 1.0);
+        let mut image = RgbaImage::new(3, 1);
+        image.put_pixel(0, 0, Rgba([255u8; 4]));
+        image.put_pixel(2, 0, Rgba([255u8; 4]));
+        assert!(!has_pixels_in_region(&image, region, 1, 1));
+
+        // Partially covered pixels are in the region for sizes that aren't multiples of 8.
+        // 0.625 * 100 = 62.5 and 0.875 * 100 = 87.5, so columns 62 through 87 are included.
+        let region = Region::new(0.625, 0.875, 0.0, 1.0);
+        let mut image = RgbaImage::new(100, 1);
+        image.put_pixel(87, 0, Rgba([255u8; 4]));
+        assert!(has_pixels_in_region(&image, region, 1, 1));
+        let mut image = RgbaImage::new(100, 1);
+        image.put_pixel(62, 0, Rgba([255u8; 4]));
+        assert!(has_pixels_in_region(&image, region, 1, 1));
+        let mut image = RgbaImage::new(100, 1);
+        image.put_pixel(88, 0, Rgba([255u8; 4]));
+        assert!(!has_pixels_in_region(&image, region, 1, 1));
+    }
+
+    #[test]
+    fn test_overlay_regions() {
+        for (layer, region) in OVERLAY_REGIONS {
+            for bound in [region.x_start, region.x_end, region.y_start, region.y_end] {
+                assert!((0.0f32..=1.0f32).contains(&bound), "{:?}", layer);
+            }
+            assert!(region.x_start < region.x_end, "{:?}", layer);
+            assert!(region.y_start < region.y_end, "{:?}", layer);
+        }
+    }
+
+    #[test]
+    fn test_render_mirror() {
+        let skin_texture = image::open("sample.png").unwrap().into_rgba8();
+        let options = RenderOptions {
+            mirror: true,
+            ..Default::default()
+        };
+        let context = RenderContext::new();
+        let render = context.render(&skin_texture, &options);
+
+        // The arms are swapped before flipping the render.
+        let (swapped_render, _): (RgbaImage, _) = context.render_scaled(
+            context.new_output(&options, 1),
+            &skin_texture,
+            &options,
+            None,
+            &mut |_, _| {},
+        );
+        assert_eq!(render, image::imageops::flip_horizontal(&swapped_render));
+        assert_ne!(
+            render,
+            image::imageops::flip_horizontal(&create_render(&skin_texture))
+        );
+    }
+
+    fn region_bounds(ids: &GrayImage, layer: Layer) -> (u32, u32, u32, u32) {
+        let mut bounds = (u32::MAX, u32::MAX, 0, 0);
+        for (x, y, id) in ids.enumerate_pixels() {
+            if id[0] == layer.region_id() {
+                bounds = (
+                    bounds.0.min(x),
+                    bounds.1.min(y),
+                    bounds.2.max(x),
+                    bounds.3.max(y),
+                );
+            }
+        }
+        bounds
+    }
+
+    #[test]
+    fn test_render_back_slim_arms() {
+        // The slim back arms are combined with the classic back maps for the other layers.
+        let skin_texture = RgbaImage::from_pixel(64, 64, Rgba([0u8, 0u8, 255u8, 255u8]));
+        let context = RenderContext::new();
+        let render_ids = |slim| {
+            let options = RenderOptions {
+                pose: Pose::Back,
+                slim,
+                disable_overlays: true,
+                ..Default::default()
+            };
+            context.render_with_ids(&skin_texture, &options).1
+        };
+        let classic = render_ids(false);
+        let slim = render_ids(true);
+
+        let chest = region_bounds(&slim, Layer::Chest);
+        assert_eq!(chest, region_bounds(&classic, Layer::Chest));
+        for ids in [&classic, &slim] {
+            // The back view swaps the sides of the arms.
+            let left_arm = region_bounds(ids, Layer::LeftArm);
+            let right_arm = region_bounds(ids, Layer::RightArm);
+            assert_eq!(left_arm.2 + 1, chest.0);
+            assert_eq!(right_arm.0, chest.2 + 1);
+            assert_eq!((left_arm.1, left_arm.3), (chest.1, chest.3));
+            assert_eq!((right_arm.1, right_arm.3), (chest.1, chest.3));
+        }
+
+        // Slim arms are 3 pixels wide instead of 4 on the skin texture.
+        let arm_width = |ids, layer| {
+            let (x0, _, x1, _) = region_bounds(ids, layer);
+            x1 - x0 + 1
+        };
+        for layer in [Layer::LeftArm, Layer::RightArm] {
+            assert_eq!(arm_width(&slim, layer) * 4, arm_width(&classic, layer) * 3);
+        }
+    }
+
+    #[test]
+    fn test_render_back_faces() {
+        // Only the back face of the torso is red.
+        let mut skin_texture = RgbaImage::from_pixel(64, 64, Rgba([0u8, 0u8, 255u8, 255u8]));
+        for x in 32..40 {
+            for y in 20..32 {
+                skin_texture.put_pixel(x, y, Rgba([255u8, 0u8, 0u8, 255u8]));
+            }
+        }
+
+        let context = RenderContext::new();
+        let check_torso = |pose, is_red: bool| {
+            let options = RenderOptions {
+                pose,
+                disable_overlays: true,
+                ..Default::default()
+            };
+            let (render, ids) = context.render_with_ids(&skin_texture, &options);
+            let mut torso_pixels = 0;
+            for (x, y, pixel) in render.enumerate_pixels() {
+                if ids.get_pixel(x, y)[0] == Layer::Chest.region_id() && pixel[3] == 255u8 {
+                    torso_pixels += 1;
+                    assert_eq!(pixel[0] > pixel[2], is_red, "{:?} at {},{}", pose, x, y);
+                }
+            }
+            assert!(torso_pixels > 0);
+        };
+        check_torso(Pose::Front, false);
+        check_torso(Pose::Back, true);
+
+        let (front, back) = create_render_poses(&skin_texture);
+        assert_eq!(front.dimensions(), back.dimensions());
+        assert_ne!(front, back);
+    }
+
+    #[test]
+    fn test_render_context_cache() {
+        let path = std::env::temp_dir().join("minecraft_render_test_cache.bin");
+        let skin_texture = image::open("sample.png").unwrap().into_rgba8();
+        let options = RenderOptions::default();
+
+        let context = RenderContext::new();
+        let render = context.render(&skin_texture, &options);
+        context.save_cache(&path).unwrap();
+
+        let cached_context = RenderContext::load_cache(&path).unwrap();
+        // Only the maps decoded for the first render are saved.
+        for ((slot, _), (cached_slot, _)) in context
+            .uv_map_slots()
+            .into_iter()
+            .zip(cached_context.uv_map_slots())
+        {
+            assert_eq!(slot.get(), cached_slot.get());
+        }
+        assert!(cached_context.steve.layers[Layer::Head as usize]
+            .get()
+            .is_some());
+        assert_eq!(render, cached_context.render(&skin_texture, &options));
+
+        std::fs::write(&path, b"not a cache").unwrap();
+        assert!(RenderContext::load_cache(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_render_context_invalid_cache() {
+        let path = std::env::temp_dir().join("minecraft_render_test_invalid_cache.bin");
+        let load_cache = |index: u32, (width, height): (u32, u32), values: &[u8]| {
+            let mut bytes = CACHE_MAGIC.to_vec();
+            for value in [CACHE_VERSION, 1, index, width, height] {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+            bytes.extend_from_slice(values);
+            std::fs::write(&path, bytes).unwrap();
+            RenderContext::load_cache(&path).unwrap_err().to_string()
+        };
+
+        // Each map must match the dimensions of the embedded map for its slot.
+        assert_eq!(
+            load_cache(0, (2, 2), &[0u8; 32]),
+            "invalid UV map dimensions in cache"
+        );
+        assert_eq!(
+            load_cache(0, (1497, 1097), &[]),
+            "invalid UV map dimensions in cache"
+        );
+        assert_eq!(
+            load_cache(0, (1097, 1497), &[0u8; 64]),
+            "unexpected end of UV map cache"
+        );
+        assert_eq!(
+            load_cache(u32::MAX, (1097, 1497), &[]),
+            "invalid UV map index in cache"
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    // A map with the embedded map dimensions that only covers the pixel at (10, 10).
+    fn single_pixel_uv_map(pixel: Rgba<u16>) -> UvMap {
+        let mut uv_map = UvMap::new(1097, 1497);
+        uv_map.put_pixel(10, 10, pixel);
+        uv_map
+    }
+
+    // Loads a context from a temporary directory with a head map from single_pixel_uv_map.
+    // Each test uses a different `name` for its directory since tests run in parallel.
+    fn context_with_head_pixel(name: &str, pixel: Rgba<u16>) -> RenderContext {
+        let dir = std::env::temp_dir().join(format!("minecraft_render_test_{}", name));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        DynamicImage::ImageRgba16(single_pixel_uv_map(pixel))
+            .save(dir.join("head.png"))
+            .unwrap();
+        let context = RenderContext::from_dir(&dir);
+        std::fs::remove_dir_all(&dir).unwrap();
+        context.unwrap()
+    }
+
+    #[test]
+    fn test_render_wrap_mode() {
+        // A u of 1.0 is the right edge when clamped and wraps to the left edge when repeated.
+        let context =
+            context_with_head_pixel("wrap_mode", Rgba([65535u16, 65023u16, 32768u16, 65535u16]));
+
+        let mut skin_texture = RgbaImage::from_pixel(64, 64, Rgba([0u8, 0u8, 255u8, 255u8]));
+        skin_texture.put_pixel(0, 0, Rgba([255u8, 0u8, 0u8, 255u8]));
+        skin_texture.put_pixel(63, 0, Rgba([0u8, 255u8, 0u8, 255u8]));
+
+        let options = RenderOptions::default();
+        assert_eq!(options.wrap_mode, WrapMode::Clamp);
+        let render = context.render_layer(&skin_texture, Layer::Head, &options);
+        assert_eq!(render.get_pixel(10, 10), &Rgba([0u8, 255u8, 0u8, 255u8]));
+
+        let options = RenderOptions::builder().wrap_mode(WrapMode::Repeat).build();
+        let render = context.render_layer(&skin_texture, Layer::Head, &options);
+        assert_eq!(render.get_pixel(10, 10), &Rgba([255u8, 0u8, 0u8, 255u8]));
+    }
+
+    #[test]
+    fn test_render_context_from_dir() {
+        // Override the head with a single pixel that samples the top left texel.
+        let context =
+            context_with_head_pixel("from_dir", Rgba([512u16, 65023u16, 32768u16, 65535u16]));
+
+        let mut skin_texture = RgbaImage::from_pixel(64, 64, Rgba([0u8, 0u8, 255u8, 255u8]));
+        skin_texture.put_pixel(0, 0, Rgba([255u8, 0u8, 0u8, 255u8]));
+
+        let options = RenderOptions::default();
+        let render = context.render_layer(&skin_texture, Layer::Head, &options);
+        assert_eq!(render.get_pixel(10, 10), &Rgba([255u8, 0u8, 0u8, 255u8]));
+        assert_eq!(render.pixels().filter(|p| p[3] > 0u8).count(), 1);
+
+        // Missing files use the embedded maps.
+        let render = context.render_layer(&skin_texture, Layer::Chest, &options);
+        assert_eq!(
+            render,
+            RenderContext::new().render_layer(&skin_texture, Layer::Chest, &options)
+        );
+
+        // UV maps need 16 bits per channel.
+        let dir = std::env::temp_dir().join("minecraft_render_test_from_dir_format");
+        std::fs::create_dir_all(&dir).unwrap();
+        RgbaImage::new(4, 4).save(dir.join("chest.png")).unwrap();
+        let result = RenderContext::from_dir(&dir);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(matches!(result, Err(UvMapError::Format(_, _))));
+    }
+
+    #[test]
+    fn test_render_context_from_dir_dimensions() {
+        let dir = std::env::temp_dir().join("minecraft_render_test_from_dir_dimensions");
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // Maps must have the same dimensions as the embedded maps.
+        DynamicImage::ImageRgba16(UvMap::new(64, 64))
+            .save(dir.join("head.png"))
+            .unwrap();
+        let result = RenderContext::from_dir(&dir);
+        std::fs::remove_dir_all(&dir).unwrap();
+        match result {
+            Err(UvMapError::Dimensions(path, expected, actual)) => {
+                assert_eq!(path, dir.join("head.png"));
+                assert_eq!(expected, (1097, 1497));
+                assert_eq!(actual, (64, 64));
+            }
+            _ => panic!("expected a dimensions error"),
+        }
+    }
+
+    #[test]
+    fn test_render_texel_offset() {
+        // A u of 0.6 texels rounds to the first column with the default offset
+        // and to the second column without an offset.
+        let u = (0.6f32 / 64f32 * 65535f32).round() as u16;
+        let context =
+            context_with_head_pixel("texel_offset", Rgba([u, 32768u16, 32768u16, 65535u16]));
+
+        let skin_texture = RgbaImage::from_fn(64, 64, |x, _| match x {
+            0 => Rgba([255u8, 0u8, 0u8, 255u8]),
+            1 => Rgba([0u8, 255u8, 0u8, 255u8]),
+            _ => Rgba([0u8, 0u8, 255u8, 255u8]),
+        });
+
+        let options = RenderOptions::default();
+        assert_eq!(options.texel_offset, DEFAULT_TEXEL_OFFSET);
+        let render = context.render_layer(&skin_texture, Layer::Head, &options);
+        assert_eq!(render.get_pixel(10, 10), &Rgba([255u8, 0u8, 0u8, 255u8]));
+
+        let options = RenderOptions::builder().texel_offset(0f32).build();
+        let render = context.render(&skin_texture, &options);
+        assert_eq!(render.get_pixel(10, 10), &Rgba([0u8, 255u8, 0u8, 255u8]));
+    }
+
+    #[test]
+    fn test_render_context_from_dir_split_maps() {
+        let dir = std::env::temp_dir().join("minecraft_render_test_split_maps");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // Split the embedded head map and clear the lighting channel in the UV map.
+        let packed = RenderContext::new()
+            .uv_map(Layer::Head, &RenderOptions::default())
+            .clone();
+        let mut uvs = packed.clone();
+        uvs.pixels_mut().for_each(|p| p[2] = 0u16);
+        let lighting = ImageBuffer::from_fn(packed.width(), packed.height(), |x, y| {
+            Luma([packed.get_pixel(x, y)[2]])
+        });
+        DynamicImage::ImageRgba16(uvs)
+            .save(dir.join("head_uv.png"))
+            .unwrap();
+        DynamicImage::ImageLuma16(lighting)
+            .save(dir.join("head_lighting.png"))
+            .unwrap();
+
+        let context = RenderContext::from_dir(&dir).unwrap();
+        assert_eq!(
+            context.uv_map(Layer::Head, &RenderOptions::default()),
+            &packed
+        );
+
+        // Both maps are required.
+        std::fs::remove_file(dir.join("head_lighting.png")).unwrap();
+        let result = RenderContext::from_dir(&dir);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(matches!(result, Err(UvMapError::MissingSplitMap(_))));
+    }
+
+    #[test]
+    fn test_limb_pose() {
+        let skin_texture = image::open("sample.png").unwrap().into_rgba8();
+        let context = RenderContext::new();
+        let arm_pixels = |limb_pose| {
+            let options = RenderOptions {
+                limb_pose,
+                ..Default::default()
+            };
+            let (_, ids) = context.render_with_ids(&skin_texture, &options);
+            ids.enumerate_pixels()
+                .filter(|(_, _, id)| id[0] == Layer::RightArm.region_id())
+                .map(|(x, y, _)| (x, y))
+                .collect::<Vec<_>>()
+        };
+
+        // The right arm is on the left side of the render and rotates away from the body.
+        let smash = arm_pixels(LimbPose::Smash);
+        let spread = arm_pixels(LimbPose::Spread);
+        assert_ne!(smash, spread);
+        let min_x = |pixels: &[(u32, u32)]| pixels.iter().map(|(x, _)| *x).min().unwrap();
+        assert!(min_x(&spread) < min_x(&smash));
+    }
+
+    #[test]
+    fn test_draw_order() {
+        let skin_texture = image::open("sample.png").unwrap().into_rgba8();
+        let context = RenderContext::new();
+        let render_ids = |draw_order: &[LayerDraw]| {
+            let options = RenderOptions {
+                draw_order: Some(draw_order.to_vec()),
+                ..Default::default()
+            };
+            context.render_with_ids(&skin_texture, &options).1
+        };
+
+        // The right arm overlaps the chest, so the last layer drawn covers the overlap.
+        let arm = LayerDraw::always(Layer::RightArm);
+        let chest = LayerDraw::always(Layer::Chest);
+        let arm_in_front = render_ids(&[chest, arm]);
+        let chest_in_front = render_ids(&[arm, chest]);
+
+        let arm_id = Layer::RightArm.region_id();
+        let chest_id = Layer::Chest.region_id();
+        let overlap_count = arm_in_front
+            .pixels()
+            .zip(chest_in_front.pixels())
+            .filter(|(a, c)| a[0] == arm_id && c[0] == chest_id)
+            .count();
+        assert!(overlap_count > 0);
+        assert!(!arm_in_front
+            .pixels()
+            .zip(chest_in_front.pixels())
+            .any(|(a, c)| a[0] == chest_id && c[0] == arm_id));
+
+        // The default order should match the explicit table.
+        assert_eq!(
+            context.render_with_ids(&skin_texture, &RenderOptions::default()),
+            context.render_with_ids(
+                &skin_texture,
+                &RenderOptions {
+                    draw_order: Some(DRAW_ORDER.to_vec()),
+                    ..Default::default()
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn test_overlay_opacity() {
+        // Add a pixel to the front of the hat.
+        let mut skin_texture = image::open("sample.png").unwrap().into_rgba8();
+        skin_texture.put_pixel(44, 12, Rgba([255u8, 0u8, 0u8, 255u8]));
+
+        let render = |overlay_opacity, hat| {
+            create_render_with_options(
+                &skin_texture,
+                &RenderOptions {
+                    overlay_opacity,
+                    hat: Some(hat),
+                    ..Default::default()
+                },
+            )
+        };
+        let without_hat = render(1.0f32, false);
+        assert_eq!(render(0.0f32, true), without_hat);
+        assert_ne!(render(1.0f32, true), without_hat);
+
+        // The hat pixel is partially blended with the head underneath.
+        let faint_hat = render(0.5f32, true);
+        let hat = render(1.0f32, true);
+        let differences = |a: &RgbaImage, b: &RgbaImage| {
+            a.pixels()
+                .zip(b.pixels())
+                .map(|(p1, p2)| (p1[0] as i32 - p2[0] as i32).abs())
+                .sum::<i32>()
+        };
+        let faint_difference = differences(&faint_hat, &without_hat);
+        assert!(faint_difference > 0 && faint_difference < differences(&hat, &without_hat));
+    }
+
+    #[test]
+    fn test_uv_coverage() {
+        let context = RenderContext::new();
+        let uv_map = context.uv_map(Layer::Head, &RenderOptions::default());
+        let coverage = uv_coverage(uv_map);
+        assert!(
+            coverage.pixel_count > 0 && coverage.pixel_count < uv_map.width() * uv_map.height()
+        );
+
+        // The maps flip the vertical coordinate, so the head at the top of the skin uses the largest values.
+        let (u_min, u_max) = coverage.u_range.unwrap();
+        assert!(u_min >= 0f32 && u_max <= 0.5f32, "{:?}", coverage);
+        let (v_min, v_max) = coverage.v_range.unwrap();
+        assert!(v_min >= 0.74f32 && v_max <= 1f32, "{:?}", coverage);
+        let (lighting_min, lighting_max) = coverage.lighting_range.unwrap();
+        assert!(lighting_min > 0f32 && lighting_max < 1f32, "{:?}", coverage);
+
+        let empty = uv_coverage(&UvMap::new(4, 4));
+        assert_eq!(
+            empty,
+            Coverage {
+                pixel_count: 0,
+                u_range: None,
+                v_range: None,
+                lighting_range: None
+            }
+        );
+    }
+
+    #[test]
+    fn test_extra_layers() {
+        // Only the top left texel in the unused corner of the skin has pixels.
+        let mut skin_texture = image::open("sample.png").unwrap().into_rgba8();
+        for x in 0..8 {
+            for y in 0..8 {
+                skin_texture.put_pixel(x, y, Rgba([0u8, 0u8, 0u8, 0u8]));
+            }
+        }
+        skin_texture.put_pixel(0, 0, Rgba([255u8, 0u8, 0u8, 255u8]));
+
+        // A single pixel in the top left corner of the render samples the top left texel.
+        let extra = ExtraLayer {
+            uv_map: single_pixel_uv_map(Rgba([512u16, 65023u16, 32768u16, 65535u16])),
+            region: Some(Region::new(0.0, 0.125, 0.0, 0.125)),
+        };
+
+        let context = RenderContext::new();
+        let without_extras = context.render(&skin_texture, &RenderOptions::default());
+        let render = |overlay_min_count| {
+            context.render_with_stats(
+                &skin_texture,
+                &RenderOptions {
+                    extras: vec![extra.clone()],
+                    overlay_min_count,
+                    ..Default::default()
+                },
+            )
+        };
+
+        let (with_extras, stats) = render(1);
+        assert_eq!(
+            with_extras.get_pixel(10, 10),
+            &Rgba([255u8, 0u8, 0u8, 255u8])
+        );
+        let mut expected = without_extras.clone();
+        expected.put_pixel(10, 10, Rgba([255u8, 0u8, 0u8, 255u8]));
+        assert_eq!(with_extras, expected);
+        let (_, default_stats) = context.render_with_stats(&skin_texture, &Default::default());
+        assert_eq!(stats.layers_blended, default_stats.layers_blended + 1);
+
+        // The region only has a single pixel, so the extra layer isn't drawn.
+        let options = RenderOptions {
+            overlay_min_count: 2,
+            ..Default::default()
+        };
+        assert_eq!(render(2).0, context.render(&skin_texture, &options));
+
+        // Layers that don't match the dimensions of the model's maps are skipped.
+        let options = RenderOptions {
+            extras: vec![ExtraLayer {
+                uv_map: UvMap::new(64, 64),
+                region: None,
+            }],
+            ..Default::default()
+        };
+        let (render, stats) = context.render_with_stats(&skin_texture, &options);
+        assert_eq!(render, without_extras);
+        assert_eq!(stats.layers_blended, default_stats.layers_blended);
+    }
+
+    #[test]
+    fn test_blend_layer_alpha_over() {
+        // Two layers with 50% coverage should leave 25% of the background visible.
+        let uv_map = UvMap::from_pixel(1, 1, Rgba([32768u16, 32768u16, 32768u16, 65535u16]));
+        let texture = RgbaImage::from_pixel(1, 1, Rgba([255u8, 255u8, 255u8, 128u8]));
+        let mut base = RgbaImage::new(1, 1);
+        for _ in 0..2 {
+            assert!(blend_layer_with_base(
+                &mut base,
+                &uv_map,
+                &texture,
+                LayerBlend::default(),
+                DEFAULT_GAMMA,
+                false,
+                None,
+            ));
+        }
+        assert_eq!(base.get_pixel(0, 0)[3], 192u8);
+    }
+
+    #[test]
+    fn test_blend_layer_over_transparent_base() {
+        let texture = RgbaImage::from_pixel(1, 1, Rgba([255u8, 0u8, 0u8, 255u8]));
+        let blend = |uv_alpha: u16| {
+            let uv_map = UvMap::from_pixel(1, 1, Rgba([32768u16, 32768u16, 32768u16, uv_alpha]));
+            let mut base = RgbaImage::from_pixel(1, 1, Rgba([0u8, 0u8, 255u8, 128u8]));
+            blend_layer_with_base(
+                &mut base,
+                &uv_map,
+                &texture,
+                LayerBlend::default(),
+                DEFAULT_GAMMA,
+                false,
+                None,
+            );
+            *base.get_pixel(0, 0)
+        };
+
+        // An opaque overlay fully covers the semi-transparent base.
+        assert_eq!(blend(65535u16), Rgba([255u8, 0u8, 0u8, 255u8]));
+
+        // Partial coverage along the edges of the overlay still shows the base.
+        let pixel = blend(32768u16);
+        assert_eq!(pixel[3], 192u8);
+        assert!(pixel[0] > 0u8 && pixel[2] > 0u8, "{:?}", pixel);
+    }
+
+    #[test]
+    fn test_clean_opaque_overlay_noise() {
+        let mut skin_texture = image::open("sample.png").unwrap().into_rgba8();
+        // Fill the hat faces with opaque black and leave the unused corners transparent.
+        for x in 32..64 {
+            for y in 0..16 {
+                let corner = y < 8 && !(40..56).contains(&x);
+                if !corner {
+                    skin_texture.put_pixel(x, y, Rgba([0u8, 0u8, 0u8, 255u8]));
+                }
+            }
+        }
+        assert!(has_pixels_in_region(
+            &skin_texture,
+            HEAD_OVERLAY_REGION,
+            1,
+            1
+        ));
+
+        let original = skin_texture.clone();
+        assert_eq!(
+            clean_opaque_overlay_noise(&mut skin_texture),
+            vec![Layer::HeadOverlay]
+        );
+        assert!(!has_pixels_in_region(
+            &skin_texture,
+            HEAD_OVERLAY_REGION,
+            1,
+            1
+        ));
+        let options = RenderOptions::default();
+        assert!(!options.should_draw(&DRAW_ORDER[9], &skin_texture));
+
+        // Other regions are unchanged.
+        assert_eq!(
+            image::imageops::crop_imm(&skin_texture, 0, 16, 64, 48).to_image(),
+            image::imageops::crop_imm(&original, 0, 16, 64, 48).to_image()
+        );
+        // Overlays with any color aren't cleared.
+        skin_texture.put_pixel(40, 8, Rgba([255u8, 0u8, 0u8, 255u8]));
+        skin_texture.put_pixel(41, 8, Rgba([0u8, 0u8, 0u8, 255u8]));
+        assert!(clean_opaque_overlay_noise(&mut skin_texture).is_empty());
+    }
+
+    #[test]
+    fn test_right_arm_covers_jacket() {
+        let position = |layer| DRAW_ORDER.iter().position(|d| d.layer == layer).unwrap();
+        assert!(position(Layer::RightArm) > position(Layer::ChestOverlay));
+        assert!(position(Layer::RightArmOverlay) > position(Layer::RightArm));
+
+        // A red jacket and a blue right arm with a partially transparent blue sleeve.
+        let skin_texture = RgbaImage::from_fn(64, 64, |x, y| match (x, y) {
+            (16..=39, 16..=31) => Rgba([0u8, 255u8, 0u8, 255u8]),
+            (16..=39, 32..=47) => Rgba([255u8, 0u8, 0u8, 255u8]),
+            (40..=55, 16..=31) => Rgba([0u8, 0u8, 255u8, 255u8]),
+            (40..=55, 32..=47) => Rgba([0u8, 0u8, 255u8, 128u8]),
+            _ => Rgba([0u8, 0u8, 0u8, 0u8]),
+        });
+        let context = RenderContext::new();
+        let options = RenderOptions::default();
+        let render = context.render(&skin_texture, &options);
+        let arm = context.render_layer(&skin_texture, Layer::RightArm, &options);
+        let jacket = context.render_layer(&skin_texture, Layer::ChestOverlay, &options);
+
+        let overlapping: Vec<_> = arm
+            .enumerate_pixels()
+            .filter(|(x, y, p)| p[3] == 255u8 && jacket.get_pixel(*x, *y)[3] > 0u8)
+            .collect();
+        assert!(!overlapping.is_empty());
+        // The arm's UV map alpha is slightly below 1.0, which can leave a trace of the jacket.
+        assert!(overlapping
+            .iter()
+            .all(|(x, y, _)| render.get_pixel(*x, *y)[0] <= 1u8));
+    }
+
+    #[test]
+    fn test_render_only_layers() {
+        let skin_texture = image::open("sample.png").unwrap().into_rgba8();
+        let context = RenderContext::new();
+        let options = RenderOptions {
+            only_layers: Some(vec![Layer::Head, Layer::Chest]),
+            ..Default::default()
+        };
+        let (render, ids) = context.render_with_ids(&skin_texture, &options);
+        assert!(ids.pixels().all(|id| [0u8, 1u8, 2u8].contains(&id[0])));
+        // Overlays are still drawn with their base layers.
+        let (_, stats) = context.render_with_stats(&skin_texture, &options);
+        let (_, full_stats) = context.render_with_stats(&skin_texture, &RenderOptions::default());
+        assert_eq!(stats.overlays_present, full_stats.overlays_present & 0b11);
+
+        // Pixels last drawn by the legs or the left arm aren't covered by the head or chest.
+        let (_, full_ids) = context.render_with_ids(&skin_texture, &RenderOptions::default());
+        let hidden: Vec<_> = full_ids
+            .enumerate_pixels()
+            .filter(|(_, _, id)| [3u8, 5u8, 6u8].contains(&id[0]))
+            .collect();
+        assert!(!hidden.is_empty());
+        assert!(hidden
+            .iter()
+            .all(|(x, y, _)| render.get_pixel(*x, *y)[3] == 0u8));
+    }
+
+    #[test]
+    fn test_reapply_overlay() {
+        let skin_texture = image::open("sample.png").unwrap().into_rgba8();
+        let context = RenderContext::new();
+        for options in [
+            RenderOptions::default(),
+            RenderOptions {
+                supersample: 2,
+                smooth_uvs: true,
+                mirror: true,
+                ..Default::default()
+            },
+        ] {
+            let without_hat = RenderOptions {
+                hat: Some(false),
+                ..options.clone()
+            };
+            let mut render = context.render_retained(&skin_texture, &without_hat);
+            assert_eq!(render.output(), context.render(&skin_texture, &without_hat));
+
+            let with_hat = RenderOptions {
+                hat: Some(true),
+                ..options.clone()
+            };
+            let output =
+                context.reapply_overlay(&mut render, &skin_texture, Layer::HeadOverlay, true);
+            assert_eq!(output, context.render(&skin_texture, &with_hat));
+            assert_eq!(render.options().hat, Some(true));
+
+            let output =
+                context.reapply_overlay(&mut render, &skin_texture, Layer::HeadOverlay, false);
+            assert_eq!(output, context.render(&skin_texture, &without_hat));
+        }
+    }
+
+    #[test]
+    fn test_render_strips() {
+        let skin_texture = image::open("sample.png").unwrap().into_rgba8();
+        let context = RenderContext::new();
+        for (options, strip_height) in [
+            (RenderOptions::default(), 100),
+            (
+                RenderOptions {
+                    scale: 2,
+                    supersample: 2,
+                    smooth_uvs: true,
+                    mirror: true,
+                    outline: Some((Rgba([255u8, 0u8, 0u8, 255u8]), 3)),
+                    ..Default::default()
+                },
+                301,
+            ),
+        ] {
+            let render = context.render(&skin_texture, &options);
+            let strips: Vec<_> = context
+                .render_strips(&skin_texture, &options, strip_height)
+                .collect();
+            assert!(strips.len() > 1);
+            assert!(strips.iter().all(|s| s.height() % options.scale == 0));
+
+            let mut stacked = RgbaImage::new(render.width(), render.height());
+            let mut y = 0;
+            for strip in &strips {
+                image::imageops::replace(&mut stacked, strip, 0, y);
+                y += strip.height();
+            }
+            assert_eq!(y, render.height());
+            assert_eq!(stacked, render);
+        }
+    }
+
+    #[test]
+    fn test_lighting_modes() {
+        // A mid gray texel lit with a shadow and a highlight after the default lighting scale.
+        let texture = RgbaImage::from_pixel(1, 1, Rgba([128u8, 128u8, 128u8, 255u8]));
+        let blend = |lighting: u16, lighting_mode: LightingMode| {
+            let uv_map = UvMap::from_pixel(1, 1, Rgba([32768u16, 32768u16, lighting, 65535u16]));
+            let mut base = RgbaImage::new(1, 1);
+            blend_layer_with_base(
+                &mut base,
+                &uv_map,
+                &texture,
+                LayerBlend {
+                    lighting_mode,
+                    ..LayerBlend::default()
+                },
+                DEFAULT_GAMMA,
+                false,
+                None,
+            );
+            base.get_pixel(0, 0)[0]
+        };
+
+        // Multiply scales the gray by the lighting.
+        assert_eq!(blend(16384u16, LightingMode::Multiply), 64u8);
+        assert_eq!(blend(49151u16, LightingMode::Multiply), 192u8);
+        // Additive offsets the gray by the same amount for shadows and highlights.
+        assert_eq!(blend(16384u16, LightingMode::Additive), 1u8);
+        assert_eq!(blend(49151u16, LightingMode::Additive), 255u8);
+        // Screen ignores shadows and moves halfway to white for this highlight.
+        assert_eq!(blend(16384u16, LightingMode::Screen), 128u8);
+        assert_eq!(blend(49151u16, LightingMode::Screen), 191u8);
+
+        // Neutral lighting leaves the color unchanged for every mode.
+        for mode in [
+            LightingMode::Multiply,
+            LightingMode::Additive,
+            LightingMode::Screen,
+        ] {
+            assert_eq!(blend(32768u16, mode), 128u8);
+        }
+    }
+
+    #[test]
+    fn test_blend_layer_debug_uvs() {
+        // The skin is fully transparent, so only the UVs are drawn.
+        let uv_map = UvMap::from_pixel(1, 1, Rgba([16384u16, 49152u16, 32768u16, 65535u16]));
+        let texture = RgbaImage::new(1, 1);
+        let mut base = RgbaImage::new(1, 1);
+        assert!(blend_layer_with_base(
+            &mut base,
+            &uv_map,
+            &texture,
+            LayerBlend {
+                debug_uvs: true,
+                ..LayerBlend::default()
+            },
+            DEFAULT_GAMMA,
+            false,
+            None,
+        ));
+        assert_eq!(*base.get_pixel(0, 0), Rgba([64u8, 191u8, 0u8, 255u8]));
+
+        // The UVs for a full render are in the skin's texture coordinates.
+        let skin_texture = image::open("sample.png").unwrap().into_rgba8();
+        let options = RenderOptions {
+            debug_uvs: true,
+            ..RenderOptions::default()
+        };
+        let render = create_render_with_options(&skin_texture, &options);
+        assert!(render.pixels().all(|p| p[2] == 0u8));
+        assert!(render.pixels().any(|p| p[3] == 255u8));
+    }
+
+    #[test]
+    fn test_outline() {
+        let skin_texture = image::open("sample.png").unwrap().into_rgba8();
+        let red = Rgba([255u8, 0u8, 0u8, 255u8]);
+        let context = RenderContext::new();
+        let render = context.render(&skin_texture, &RenderOptions::default());
+        let outlined = context.render(
+            &skin_texture,
+            &RenderOptions {
+                outline: Some((red, 2)),
+                ..Default::default()
+            },
+        );
+
+        // Find the distance in rings to the nearest visible pixel in the original render.
+        let (width, height) = render.dimensions();
+        let distance = |x: u32, y: u32| {
+            (0..=3u32)
+                .find(|d| {
+                    let (x0, x1) = (x.saturating_sub(*d), (x + d).min(width - 1));
+                    let (y0, y1) = (y.saturating_sub(*d), (y + d).min(height - 1));
+                    (x0..=x1).any(|x| (y0..=y1).any(|y| render.get_pixel(x, y)[3] > 0u8))
+                })
+                .unwrap_or(4)
+        };
+
+        let mut outline_pixels = 0;
+        for (x, y, pixel) in outlined.enumerate_pixels() {
+            let original = render.get_pixel(x, y);
+            if original[3] == 255u8 {
+                assert_eq!(pixel, original);
+            } else if original[3] == 0u8 {
+                match distance(x, y) {
+                    1 | 2 => {
+                        assert_eq!((pixel[0], pixel[1], pixel[2]), (255u8, 0u8, 0u8));
+                        assert!(pixel[3] > 0u8);
+                        outline_pixels += 1;
+                    }
+                    _ => assert_eq!(pixel[3], 0u8),
+                }
+            }
+        }
+        assert!(outline_pixels > 0);
+    }
+
+    #[test]
+    fn test_render_options_builder() {
+        // RenderOptions doesn't implement PartialEq, so compare the debug output.
+        assert_eq!(
+            format!("{:?}", RenderOptions::builder().build()),
+            format!("{:?}", RenderOptions::default())
+        );
+
+        let options = RenderOptions::builder()
+            .mirror(true)
+            .scale(2)
+            .background(Rgba([255u8, 255u8, 255u8, 255u8]))
+            .hat(false)
+            .pose(Pose::Back)
+            .build();
+        let expected = RenderOptions {
+            mirror: true,
+            scale: 2,
+            background: Some(Rgba([255u8, 255u8, 255u8, 255u8])),
+            hat: Some(false),
+            pose: Pose::Back,
+            ..Default::default()
+        };
+        assert_eq!(format!("{:?}", options), format!("{:?}", expected));
+    }
+
+    // Record messages with the thread that logged them to ignore other tests running in parallel.
+    struct CaptureLogger(std::sync::Mutex<Vec<(std::thread::ThreadId, String)>>);
+
+    impl log::Log for CaptureLogger {
+        fn enabled(&self, _: &log::Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &log::Record) {
+            self.0
+                .lock()
+                .unwrap()
+                .push((std::thread::current().id(), record.args().to_string()));
+        }
+
+        fn flush(&self) {}
+    }
+
+    static LOGGER: CaptureLogger = CaptureLogger(std::sync::Mutex::new(Vec::new()));
+
+    // The sample skin with transparent pixels in every overlay region.
+    fn sample_skin_without_overlays() -> RgbaImage {
+        let mut skin_texture = image::open("sample.png").unwrap().into_rgba8();
+        for (_, region) in OVERLAY_REGIONS {
+            for x in (region.x_start * 64f32) as u32..(region.x_end * 64f32) as u32 {
+                for y in (region.y_start * 64f32) as u32..(region.y_end * 64f32) as u32 {
+                    skin_texture.put_pixel(x, y, Rgba([0u8, 0u8, 0u8, 0u8]));
+                }
+            }
+        }
+        skin_texture
+    }
+
+    #[test]
+    fn test_overlay_decision_logs() {
+        let _ = log::set_logger(&LOGGER);
+        log::set_max_level(log::LevelFilter::Debug);
+
+        // Only the hat overlay has pixels.
+        let mut skin_texture = sample_skin_without_overlays();
+        skin_texture.put_pixel(44, 12, Rgba([255u8, 0u8, 0u8, 255u8]));
+        let options = RenderOptions {
+            jacket: Some(true),
+            ..Default::default()
+        };
+        RenderContext::new().render_with_stats(&skin_texture, &options);
+
+        let thread = std::thread::current().id();
+        let messages: Vec<_> = LOGGER
+            .0
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(id, _)| *id == thread)
+            .map(|(_, message)| message.clone())
+            .collect();
+        let logged = |message: &str| messages.iter().any(|m| m == message);
+        assert!(logged(
+            "HeadOverlay: drawn with enough visible pixels in its region"
+        ));
+        assert!(logged("ChestOverlay: drawn by override"));
+        assert!(logged(
+            "LeftLegOverlay: skipped with too few visible pixels in its region"
+        ));
+        // The forced jacket has no visible pixels, so it isn't counted as blended.
+        assert!(messages.iter().any(|m| m.starts_with("Rendered 7 layers")));
+    }
+
+    #[test]
+    fn test_render_layer_frames() {
+        // The sample skin has no overlays, so only the base layers add frames.
+        let skin_texture = image::open("sample.png").unwrap().into_rgba8();
+        let context = RenderContext::new();
+        let options = RenderOptions {
+            jacket: Some(true),
+            ..Default::default()
+        };
+        let frames = context.render_layer_frames(&skin_texture, &options);
+        let (render, stats) = context.render_with_stats(&skin_texture, &options);
+
+        // The forced jacket doesn't draw any pixels, so it doesn't add a duplicate frame.
+        assert_eq!(frames.len(), stats.layers_blended + 1);
+        assert!(frames[0].pixels().all(|p| p[3] == 0u8));
+        assert_eq!(frames.last().unwrap(), &render);
+        for frames in frames.windows(2) {
+            assert_ne!(frames[0], frames[1]);
+        }
+    }
+
+    #[test]
+    fn test_render_into_reused_buffer() {
+        let skin_texture = image::open("sample.png").unwrap().into_rgba8();
+        let mut other_skin_texture = skin_texture.clone();
+        other_skin_texture.put_pixel(44, 12, Rgba([255u8, 0u8, 0u8, 255u8]));
+
+        let context = RenderContext::new();
+        let options = RenderOptions::default();
+        let mut output = RgbaImage::new(0, 0);
+        context.render_into(&mut output, &other_skin_texture, &options);
+        assert_eq!(output, context.render(&other_skin_texture, &options));
+
+        // Nothing from the previous render remains in the buffer.
+        let buffer = output.as_ptr();
+        context.render_into(&mut output, &skin_texture, &options);
+        assert_eq!(output, context.render(&skin_texture, &options));
+        assert_eq!(output.as_ptr(), buffer);
+
+        let options = RenderOptions {
+            background: Some(Rgba([0u8, 0u8, 255u8, 255u8])),
+            mirror: true,
+            ..Default::default()
+        };
+        context.render_into(&mut output, &skin_texture, &options);
+        assert_eq!(output, context.render(&skin_texture, &options));
+    }
+
+    #[test]
+    fn test_check_model() {
+        let classic = image::open("sample.png").unwrap().into_rgba8();
+        let slim = image::open("tests/fixtures/skins/blocks_slim.png")
+            .unwrap()
+            .into_rgba8();
+
+        assert_eq!(check_model(&classic, ModelType::Classic), Ok(()));
+        assert_eq!(check_model(&slim, ModelType::Slim), Ok(()));
+        assert_eq!(
+            check_model(&classic, ModelType::Slim),
+            Err(ModelMismatch {
+                declared: ModelType::Slim,
+                detected: ModelType::Classic
+            })
+        );
+        assert_eq!(
+            check_model(&slim, ModelType::Classic),
+            Err(ModelMismatch {
+                declared: ModelType::Classic,
+                detected: ModelType::Slim
+            })
+        );
+
+        // Legacy skins predate the slim model.
+        let legacy = image::imageops::crop_imm(&classic, 0, 0, 64, 32).to_image();
+        assert_eq!(check_model(&legacy, ModelType::Slim), Ok(()));
+
+        // The back face of the slim arm fills x=50..53, so only x=54..56 decides the model.
+        let arm_columns = |columns: std::ops::Range<u32>| {
+            let mut skin_texture = RgbaImage::new(64, 64);
+            for x in columns {
+                for y in 20..32 {
+                    skin_texture.put_pixel(x, y, Rgba([255u8; 4]));
+                }
+            }
+            skin_texture
+        };
+        assert_eq!(check_model(&arm_columns(50..54), ModelType::Slim), Ok(()));
+        assert_eq!(
+            check_model(&arm_columns(50..54), ModelType::Classic),
+            Err(ModelMismatch {
+                declared: ModelType::Classic,
+                detected: ModelType::Slim
+            })
+        );
+        assert_eq!(
+            check_model(&arm_columns(54..56), ModelType::Classic),
+            Ok(())
+        );
+        assert_eq!(
+            check_model(&arm_columns(54..56), ModelType::Slim),
+            Err(ModelMismatch {
+                declared: ModelType::Slim,
+                detected: ModelType::Classic
+            })
+        );
+    }
+
+    #[test]
+    fn test_fallback_skin() {
+        let classic = fallback_skin(ModelType::Classic);
+        let slim = fallback_skin(ModelType::Slim);
+        assert_ne!(classic, slim);
+        assert_eq!(detect_model(&classic), ModelType::Classic);
+        assert_eq!(detect_model(&slim), ModelType::Slim);
+
+        // Only the arms and sleeves differ.
+        let arm_regions = [(40, 16), (40, 32), (32, 48), (48, 48)];
+        for (x, y, pixel) in classic.enumerate_pixels() {
+            let in_arm = arm_regions
+                .iter()
+                .any(|&(ax, ay)| (ax..ax + 16).contains(&x) && (ay..ay + 16).contains(&y));
+            if !in_arm {
+                assert_eq!(slim.get_pixel(x, y), pixel, "{},{}", x, y);
+            }
+        }
+    }
 
-fn create_render_inner(skin_texture: &RgbaImage, is_alex: bool) -> RgbaImage {
-    // At least 16 bit precision is required for the texture sampling to look decent.
-    let load_rgba_u16 = |buffer| match image::load_from_memory(buffer).unwrap() {
-        DynamicImage::ImageRgba16(image_buffer) => image_buffer,
-        _ => panic!("Expected RGBA 16 bit for UVs"),
-    };
-    
-    let head_uvs = 
-        if is_alex { 
-            load_rgba_u16(include_bytes!("../images/uv_lighting_alpha/alex/head.png"))
-        } else {
-            
-            load_rgba_u16(include_bytes!("../images/uv_lighting_alpha/head.png"))
-        };
-    let chest_uvs = 
-        if is_alex { 
-            load_rgba_u16(include_bytes!("../images/uv_lighting_alpha/alex/chest.png"))
-        } else {
-            load_rgba_u16(include_bytes!("../images/uv_lighting_alpha/chest.png"))
-        };
-    let arm_l_uvs = 
-        if is_alex { 
-            load_rgba_u16(include_bytes!("../images/uv_lighting_alpha/alex/arm_l.png"))
-        } else {
-            load_rgba_u16(include_bytes!("../images/uv_lighting_alpha/arm_l.png"))
-        };
-    let arm_r_uvs = 
-        if is_alex { 
-            load_rgba_u16(include_bytes!("../images/uv_lighting_alpha/alex/arm_r.png"))
-        } else {
-            load_rgba_u16(include_bytes!("../images/uv_lighting_alpha/arm_r.png"))
+    #[test]
+    fn test_place_into_atlas() {
+        let skin_texture = image::open("sample.png").unwrap().into_rgba8();
+        let render = create_render(&skin_texture);
+
+        let background = Rgba([128u8, 128u8, 128u8, 255u8]);
+        let mut atlas = RgbaImage::from_pixel(2048, 2048, background);
+        let slot = AtlasSlot {
+            x: 512,
+            y: 1024,
+            width: 256,
+            height: 320,
+            scale: 0.2f32,
+            translate_x: 16f32,
+            translate_y: 8f32,
         };
-    let leg_l_uvs = 
-        if is_alex { 
-            load_rgba_u16(include_bytes!("../images/uv_lighting_alpha/alex/leg_l.png"))
-        } else {
-            load_rgba_u16(include_bytes!("../images/uv_lighting_alpha/leg_l.png"))
+        place_into_atlas(&mut atlas, &render, slot);
+
+        let in_slot = |x: u32, y: u32| {
+            (slot.x..slot.x + slot.width).contains(&x)
+                && (slot.y..slot.y + slot.height).contains(&y)
         };
-    let leg_r_uvs = 
-        if is_alex { 
-            load_rgba_u16(include_bytes!("../images/uv_lighting_alpha/alex/leg_r.png"))
-        } else {
-            load_rgba_u16(include_bytes!("../images/uv_lighting_alpha/leg_r.png"))
+        for (x, y, pixel) in atlas.enumerate_pixels() {
+            if !in_slot(x, y) {
+                assert_eq!(pixel, &background);
+            }
+        }
+
+        // The region matches the transformed render, including its transparent background.
+        let portrait = image::imageops::crop_imm(&atlas, slot.x, slot.y, slot.width, slot.height);
+        let expected = warp_chara_image(
+            &render,
+            (slot.width, slot.height),
+            slot.scale,
+            0f32,
+            (slot.translate_x, slot.translate_y),
+            Interpolation::Bilinear,
+            Rgba([0u8, 0u8, 0u8, 0u8]),
+        );
+        assert_eq!(portrait.to_image(), expected);
+        assert!(expected.pixels().any(|p| p[3] == 255u8));
+    }
+
+    #[test]
+    fn test_layer_lighting_scale() {
+        let skin_texture = image::open("sample.png").unwrap().into_rgba8();
+        let context = RenderContext::new();
+        let (render, ids) = context.render_with_ids(&skin_texture, &RenderOptions::default());
+
+        // Only brighten the head.
+        let draw_order: Vec<_> = DRAW_ORDER
+            .iter()
+            .map(|draw| match draw.layer {
+                Layer::Head => draw.with_lighting_scale(3f32),
+                _ => *draw,
+            })
+            .collect();
+        let options = RenderOptions {
+            draw_order: Some(draw_order),
+            ..Default::default()
         };
+        let brighter = context.render(&skin_texture, &options);
 
-    let mut output = ImageBuffer::new(head_uvs.dimensions().0, head_uvs.dimensions().1);
+        let brightness = |p: &Rgba<u8>| p[0] as u32 + p[1] as u32 + p[2] as u32;
+        let mut brightened = 0;
+        for ((pixel, brighter_pixel), id) in
+            render.pixels().zip(brighter.pixels()).zip(ids.pixels())
+        {
+            if id[0] == Layer::Head.region_id() && pixel[3] == 255u8 {
+                assert!(brightness(brighter_pixel) >= brightness(pixel));
+                brightened += (brightness(brighter_pixel) > brightness(pixel)) as usize;
+            }
+        }
+        assert!(brightened > 0);
 
-    // Alpha blending relies on having the correct color already present in the render buffer.
-    // Steve has simple geometry, so blend layers from back to front rather than using a depth map.
-    blend_layer_with_base(&mut output, &arm_l_uvs, skin_texture);
+        // Pixels without any contribution from the head are unchanged.
+        let head = context.render_layer(&skin_texture, Layer::Head, &RenderOptions::default());
+        for ((pixel, brighter_pixel), head_pixel) in
+            render.pixels().zip(brighter.pixels()).zip(head.pixels())
+        {
+            if head_pixel[3] == 0u8 {
+                assert_eq!(pixel, brighter_pixel);
+            }
+        }
 
-    // Skip costly image loading and blending for regions with fully transparent pixels.
-    // Assume the base layers are always used.
-    if has_pixel_in_region(&skin_texture, 0.75f32, 1.0f32, 0.75f32, 1.0f32) {
-        let arm_l_uvs2 = 
-            if is_alex { 
-                load_rgba_u16(include_bytes!("../images/uv_lighting_alpha/alex/arm_l2.png"))
-            } else {
-                load_rgba_u16(include_bytes!("../images/uv_lighting_alpha/arm_l2.png"))
-            };
-        blend_layer_with_base(&mut output, &arm_l_uvs2, skin_texture);
-    }
-    blend_layer_with_base(&mut output, &leg_l_uvs, skin_texture);
-    if has_pixel_in_region(&skin_texture, 0.0f32, 0.25f32, 0.75f32, 1.0f32) {
-        let leg_l_uvs2 = 
-            if is_alex { 
-                load_rgba_u16(include_bytes!("../images/uv_lighting_alpha/alex/leg_l2.png"))
-            } else {
-                load_rgba_u16(include_bytes!("../images/uv_lighting_alpha/leg_l2.png"))
-            };
-        blend_layer_with_base(&mut output, &leg_l_uvs2, skin_texture);
+        // The default table matches the previous constant lighting scale.
+        assert!(DRAW_ORDER
+            .iter()
+            .chain(MIRRORED_DRAW_ORDER.iter())
+            .all(|draw| draw.lighting_scale == 2f32));
     }
 
-    blend_layer_with_base(&mut output, &leg_r_uvs, skin_texture);
-    if has_pixel_in_region(&skin_texture, 0.0f32, 0.25f32, 0.5f32, 0.75f32) {
-        let leg_r_uvs2 = 
-            if is_alex { 
-                load_rgba_u16(include_bytes!("../images/uv_lighting_alpha/alex/leg_r2.png"))
-            } else {
-                load_rgba_u16(include_bytes!("../images/uv_lighting_alpha/leg_r2.png"))
-            };
-        blend_layer_with_base(&mut output, &leg_r_uvs2, skin_texture);
-    }
+    #[test]
+    fn test_render_manifest() {
+        // Only the hat overlay has pixels.
+        let mut skin_texture = sample_skin_without_overlays();
+        skin_texture.put_pixel(44, 12, Rgba([255u8, 0u8, 0u8, 255u8]));
 
+        let options = RenderOptions::default();
+        let (render, stats) = RenderContext::new().render_with_stats(&skin_texture, &options);
+        let manifest = RenderManifest::new(&skin_texture, &render, &options, &stats);
+        assert_eq!(
+            manifest.overlays,
+            OverlayFlags {
+                hat: true,
+                ..Default::default()
+            }
+        );
+        assert!(!manifest.overlays.jacket);
+        assert_eq!(manifest.model, ModelType::Classic);
+        assert_eq!((manifest.skin_width, manifest.skin_height), (64, 64));
+        assert_eq!(
+            (manifest.output_width, manifest.output_height),
+            render.dimensions()
+        );
+    }
 
+    #[test]
+    fn test_render_with_stats() {
+        // Only the hat and left sleeve overlays have pixels.
+        let mut skin_texture = sample_skin_without_overlays();
+        // The front faces of the hat and left sleeve.
+        skin_texture.put_pixel(44, 12, Rgba([255u8, 0u8, 0u8, 255u8]));
+        skin_texture.put_pixel(54, 58, Rgba([255u8, 0u8, 0u8, 255u8]));
 
-    blend_layer_with_base(&mut output, &chest_uvs, skin_texture);
-    if has_pixel_in_region(&skin_texture, 0.25f32, 0.625f32, 0.5f32, 0.75f32) {
-        let chest_uvs2 = 
-            if is_alex { 
-                load_rgba_u16(include_bytes!("../images/uv_lighting_alpha/alex/chest2.png"))
-            } else {
-                load_rgba_u16(include_bytes!("../images/uv_lighting_alpha/chest2.png"))
-            };
-        blend_layer_with_base(&mut output, &chest_uvs2, skin_texture);
-    }
+        let context = RenderContext::new();
+        let options = RenderOptions::default();
+        let (render, stats) = context.render_with_stats(&skin_texture, &options);
+        assert_eq!(render, context.render(&skin_texture, &options));
 
-    blend_layer_with_base(&mut output, &head_uvs, skin_texture);
-    if has_pixel_in_region(&skin_texture, 0.5f32, 1.0f32, 0.0f32, 0.25f32) {
-        let head_uvs2 = 
-            if is_alex { 
-                load_rgba_u16(include_bytes!("../images/uv_lighting_alpha/alex/head2.png"))
-            } else {
-                load_rgba_u16(include_bytes!("../images/uv_lighting_alpha/head2.png"))
-            };
-        blend_layer_with_base(&mut output, &head_uvs2, skin_texture);
+        let drawn_layers = options
+            .layers_to_draw(&skin_texture)
+            .into_iter()
+            .filter(|draw| {
+                let layer_render = context.render_layer(&skin_texture, draw.layer, &options);
+                layer_render.pixels().any(|p| p[3] > 0u8)
+            })
+            .count();
+        assert_eq!(stats.layers_blended, drawn_layers);
+        assert_eq!(stats.layers_blended, 8);
+        assert_eq!(stats.overlays_present, 0b101);
     }
-    blend_layer_with_base(&mut output, &arm_r_uvs, skin_texture);
 
-    if has_pixel_in_region(&skin_texture, 0.625f32, 0.875f32, 0.5f32, 0.75f32) {
-        let arm_r_uvs2 = 
-            if is_alex { 
-                load_rgba_u16(include_bytes!("../images/uv_lighting_alpha/alex/arm_r2.png"))
-            } else {
-                load_rgba_u16(include_bytes!("../images/uv_lighting_alpha/arm_r2.png"))
-            };
-        blend_layer_with_base(&mut output, &arm_r_uvs2, skin_texture);
-    }
-    output
-}
+    #[test]
+    fn test_render_group() {
+        // Use solid colors to tell the renders apart.
+        let red_skin = RgbaImage::from_pixel(64, 64, Rgba([255u8, 0u8, 0u8, 255u8]));
+        let blue_skin = RgbaImage::from_pixel(64, 64, Rgba([0u8, 0u8, 255u8, 255u8]));
 
-/// Creates a Smash Ultimate Minecraft Steve inspired render from the given Minecraft skin texture.
-pub fn create_render(skin_texture: &RgbaImage) -> RgbaImage {
-    create_render_inner(skin_texture, false)
-}
+        // Place half size renders side by side.
+        let layout = GroupLayout {
+            width: 1097,
+            height: 749,
+            transforms: vec![
+                GroupTransform {
+                    scale: 0.5f32,
+                    translate_x: 0f32,
+                    translate_y: 0f32,
+                },
+                GroupTransform {
+                    scale: 0.5f32,
+                    translate_x: 548f32,
+                    translate_y: 0f32,
+                },
+            ],
+        };
+        let group = render_group(&[red_skin, blue_skin], &layout);
+        assert_eq!(group.dimensions(), (1097, 749));
 
-/// Creates a Smash Ultimate Minecraft Alex inspired render from the given Minecraft skin texture.
-pub fn create_render_slim(skin_texture: &RgbaImage) -> RgbaImage {
-    create_render_inner(skin_texture, true)
-}
+        let is_visible = |p: &Rgba<u8>| p[3] > 0u8;
+        let left = image::imageops::crop_imm(&group, 0, 0, 548, 749).to_image();
+        let right = image::imageops::crop_imm(&group, 548, 0, 549, 749).to_image();
+        assert!(left
+            .pixels()
+            .any(|p| is_visible(p) && p[0] > 0u8 && p[2] == 0u8));
+        assert!(!left.pixels().any(|p| is_visible(p) && p[2] > 0u8));
+        assert!(right
+            .pixels()
+            .any(|p| is_visible(p) && p[2] > 0u8 && p[0] == 0u8));
+        assert!(!right.pixels().any(|p| is_visible(p) && p[0] > 0u8));
+    }
 
-/// Creates a render with the dimensions and alpha of the reference chara file
-/// by transforming the render using the given transformations.
-pub fn create_chara_image(
-    render: &RgbaImage,
-    chara_reference: &RgbaImage,
-    scale: f32,
-    translate_x: f32,
-    translate_y: f32,
-) -> RgbaImage {
-    let mut output = ImageBuffer::new(
-        chara_reference.dimensions().0,
-        chara_reference.dimensions().1,
-    );
+    #[test]
+    fn test_create_head_render() {
+        let skin_texture = image::open("sample.png").unwrap().into_rgba8();
+        let render = create_render(&skin_texture);
+        let head = create_head_render(&skin_texture);
+        assert!(head.width() < render.width() && head.height() < render.height());
 
-    // Align the render with the target chara image.
-    // warp_into_with defines the preimage, so invert the transformation.
-    warp_into_with(
-        &render,
-        |x, y| ((x - translate_x) / scale, (y - translate_y) / scale),
-        Interpolation::Bilinear,
-        Rgba([0u8, 0u8, 0u8, 0u8]),
-        &mut output,
-    );
+        // The crop should be tight around the head with visible pixels on every edge.
+        let (width, height) = head.dimensions();
+        assert!((0..width).any(|x| head.get_pixel(x, 0)[3] > 0u8));
+        assert!((0..width).any(|x| head.get_pixel(x, height - 1)[3] > 0u8));
+        assert!((0..height).any(|y| head.get_pixel(0, y)[3] > 0u8));
+        assert!((0..height).any(|y| head.get_pixel(width - 1, y)[3] > 0u8));
 
-    // Use the reference image's alpha for appropriate masking on some portraits.
-    blend_alpha(&mut output, &chara_reference);
+        // The head UV map fits within this region of the full render.
+        assert!(width <= 960 - 514 + 1 && height <= 410 - 34 + 1);
 
-    output
-}
+        let empty = create_head_render(&RgbaImage::new(64, 64));
+        assert_eq!(empty.dimensions(), (0, 0));
+    }
 
-/// Converts a color from Minecraft to match Smash ultimate using the following formula:
-/// `ultimate = (minecraft ^ (1.0 / 0.72)) * 0.72`
-pub fn color_correct(color: &Rgba<u8>) -> Rgba<u8> {
-    let reduce_contrast = |c: f32| c.powf(0.72f32) * 0.72f32;
-    let (r, g, b, _) = normalize_rgba_u8(color);
-    Rgba([
-        to_u8_clamped(reduce_contrast(r)),
-        to_u8_clamped(reduce_contrast(g)),
-        to_u8_clamped(reduce_contrast(b)),
-        color[3],
-    ])
-}
+    #[test]
+    fn test_render_layer_head() {
+        let skin_texture = image::open("sample.png").unwrap().into_rgba8();
+        let render = render_layer(&skin_texture, Layer::Head);
 
-fn blend_layer_with_base(
-    base: &mut RgbaImage,
-    layer_uvs_lighting: &ImageBuffer<Rgba<u16>, Vec<u16>>,
-    texture: &RgbaImage,
-) {
-    for x in 0..base.width() {
-        for y in 0..base.height() {
-            // Skip pixels outside the masked region to improve performance.
-            let uv_rgba = layer_uvs_lighting.get_pixel(x, y);
-            if uv_rgba[3] == 0u16 {
-                continue;
+        // The bounding box of the head UV map.
+        let mut has_pixels = false;
+        for (x, y, pixel) in render.enumerate_pixels() {
+            if pixel[3] > 0u8 {
+                has_pixels = true;
+                assert!((514..=960).contains(&x) && (34..=410).contains(&y));
             }
+        }
+        assert!(has_pixels);
+    }
 
-            // Skip fully transparent sampled texels to improve performance.
-            let (u, v, lighting, uv_alpha) = normalize_rgba_u16(uv_rgba);
-            let layer_color = sample_texture(texture, u, v);
-            if layer_color[3] == 0u8 {
-                continue;
+    #[test]
+    fn test_downsample_linear_edges() {
+        // A 2x2 square centered in a 4x4 image covers one pixel of each 2x2 block.
+        let image = RgbaImage::from_fn(4, 4, |x, y| {
+            if (1..3).contains(&x) && (1..3).contains(&y) {
+                Rgba([255u8, 0u8, 0u8, 255u8])
+            } else {
+                Rgba([0u8, 0u8, 0u8, 0u8])
             }
+        });
+        let output = downsample_linear(&image, 2, 2.2f32);
+        assert_eq!(output.dimensions(), (2, 2));
+        for pixel in output.pixels() {
+            assert_eq!(pixel, &Rgba([255u8, 0u8, 0u8, 64u8]));
+        }
+    }
 
-            let (layer_r, layer_g, layer_b, layer_alpha) = normalize_rgba_u8(layer_color);
-
-            // The lighting pass is scaled down by a factor of 0.25 to fit into 8 bits per channel.
-            // Multiplying by 4 is a bit too bright, so use 2 instead.
-            let apply_lighting = |color: f32, light: f32| color * light * 2f32;
+    #[test]
+    fn test_create_chara_image_rotation() {
+        let mut render = RgbaImage::new(4, 4);
+        render.put_pixel(1, 1, Rgba([255u8, 255u8, 255u8, 255u8]));
+        let reference = RgbaImage::from_pixel(4, 4, Rgba([0u8, 0u8, 0u8, 255u8]));
 
-            let get_result = |base: f32, layer: f32| {
-                let lighting_result = apply_lighting(layer, lighting);
+        // Rotating 90 degrees clockwise maps (x, y) to (-y, x) before translating.
+        let output = create_chara_image_affine(
+            &render,
+            &reference,
+            1.0f32,
+            std::f32::consts::FRAC_PI_2,
+            (3.0f32, 0.0f32),
+            Interpolation::Bilinear,
+            Rgba([0u8, 0u8, 0u8, 0u8]),
+        );
+        assert!(output.get_pixel(2, 1)[0] > 250u8);
+        assert!(output.get_pixel(2, 1)[3] > 250u8);
+        assert_eq!(output.get_pixel(1, 1)[3], 0u8);
+    }
 
-                // Skip the costly floating point gamma correction and blending if possible.
-                if layer_color[3] < 255u8 {
-                    alpha_blend(base, lighting_result, layer_alpha * uv_alpha)
-                } else {
-                    lighting_result
-                }
-            };
+    #[test]
+    fn test_create_chara_image_fill() {
+        let render = RgbaImage::from_pixel(4, 4, Rgba([0u8, 0u8, 255u8, 255u8]));
+        // The reference hides the bottom right corner.
+        let mut reference = RgbaImage::from_pixel(12, 12, Rgba([0u8, 0u8, 0u8, 255u8]));
+        reference.put_pixel(11, 11, Rgba([0u8, 0u8, 0u8, 0u8]));
 
-            let (base_r, base_g, base_b, base_a) = normalize_rgba_u8(base.get_pixel(x, y));
+        let red = Rgba([255u8, 0u8, 0u8, 255u8]);
+        let output = create_chara_image(
+            &render,
+            &reference,
+            1.0f32,
+            4.0f32,
+            4.0f32,
+            red,
+            MaskMode::Min,
+        );
+        assert_eq!(output.get_pixel(0, 0), &red);
+        assert_eq!(output.get_pixel(11, 0), &red);
+        assert_eq!(output.get_pixel(0, 11), &red);
+        assert_eq!(output.get_pixel(11, 11), &Rgba([255u8, 0u8, 0u8, 0u8]));
+        assert_eq!(output.get_pixel(5, 5), &Rgba([0u8, 0u8, 255u8, 255u8]));
+    }
 
-            // Use the uv map alpha as well to prevent blending outside the masked region.
-            let r = get_result(base_r, layer_r);
-            let g = get_result(base_g, layer_g);
-            let b = get_result(base_b, layer_b);
-            let alpha_final = base_a + layer_alpha * uv_alpha;
+    #[test]
+    fn test_create_chara_image_mask_mode() {
+        // A half transparent render under a mask that fades from transparent to opaque.
+        let render = RgbaImage::from_pixel(256, 1, Rgba([0u8, 0u8, 255u8, 128u8]));
+        let reference = RgbaImage::from_fn(256, 1, |x, _| Rgba([0u8, 0u8, 0u8, x as u8]));
+        let create = |mask_mode| {
+            create_chara_image(
+                &render,
+                &reference,
+                1.0f32,
+                0f32,
+                0f32,
+                Rgba([0u8, 0u8, 0u8, 0u8]),
+                mask_mode,
+            )
+        };
+        let min_output = create(MaskMode::Min);
+        let multiply_output = create(MaskMode::Multiply);
 
-            *base.get_pixel_mut(x, y) = Rgba([
-                to_u8_clamped(r),
-                to_u8_clamped(g),
-                to_u8_clamped(b),
-                to_u8_clamped(alpha_final),
-            ]);
+        for x in 0..256 {
+            let min_alpha = min_output.get_pixel(x, 0)[3];
+            let multiply_alpha = multiply_output.get_pixel(x, 0)[3];
+            assert_eq!(min_alpha, min(x as u8, 128u8));
+            assert!(multiply_alpha <= min_alpha);
         }
+
+        // The modes only agree where either alpha is 0 or 255.
+        assert_eq!(multiply_output.get_pixel(0, 0)[3], 0u8);
+        assert_eq!(multiply_output.get_pixel(128, 0)[3], 64u8);
+        assert_eq!(multiply_output.get_pixel(255, 0)[3], 128u8);
+        assert_eq!(min_output.get_pixel(128, 0)[3], 128u8);
     }
-}
 
-fn has_pixel_in_region(
-    image: &RgbaImage,
-    x_start: f32,
-    x_end: f32,
-    y_start: f32,
-    y_end: f32,
-) -> bool {
-    let x_start = (x_start * image.width() as f32) as u32;
-    let x_end = (x_end * image.width() as f32) as u32;
+    #[test]
+    fn test_solve_transform() {
+        let src_points = [(514f32, 34f32), (960f32, 1410f32)];
+        let transform = |(x, y): (f32, f32)| (x * 0.25f32 - 90f32, y * 0.25f32 + 9.5f32);
+        let dst_points = [transform(src_points[0]), transform(src_points[1])];
+
+        let (scale, translate_x, translate_y) = solve_transform(src_points, dst_points);
+        assert!((scale - 0.25f32).abs() < 1e-6f32);
+        assert!((translate_x + 90f32).abs() < 1e-3f32);
+        assert!((translate_y - 9.5f32).abs() < 1e-3f32);
+        for (src, dst) in src_points.iter().zip(dst_points.iter()) {
+            assert!((src.0 * scale + translate_x - dst.0).abs() < 1e-3f32);
+            assert!((src.1 * scale + translate_y - dst.1).abs() < 1e-3f32);
+        }
 
-    let y_start = (y_start * image.height() as f32) as u32;
-    let y_end = (y_end * image.height() as f32) as u32;
+        // A single red pixel lands on the expected pixel after warping.
+        let mut render = RgbaImage::new(64, 64);
+        render.put_pixel(32, 16, Rgba([255u8, 0u8, 0u8, 255u8]));
+        let (scale, translate_x, translate_y) = solve_transform(
+            [(0f32, 0f32), (64f32, 64f32)],
+            [(4f32, 8f32), (132f32, 136f32)],
+        );
+        let output = warp_render(&render, 136, 136, scale, translate_x, translate_y);
+        assert_eq!(output.get_pixel(4 + 64, 8 + 32)[0], 255u8);
+    }
 
-    for x in x_start..x_end {
-        for y in y_start..y_end {
-            if image.get_pixel(x, y)[3] > 0u8 {
-                return true;
+    #[test]
+    fn test_prefilter_for_scale() {
+        let render = RgbaImage::from_fn(400, 400, |x, y| {
+            if (x + y) % 2 == 0 {
+                Rgba([0u8, 0u8, 0u8, 255u8])
+            } else {
+                Rgba([255u8, 255u8, 255u8, 255u8])
             }
-        }
+        });
+        assert_eq!(
+            prefilter_for_scale(&render, 0.23f32).dimensions(),
+            (100, 100)
+        );
+        assert_eq!(prefilter_for_scale(&render, 0.9f32), render);
+
+        // The checkerboard is too fine to show at this scale, so it should blend to a single gray.
+        let reference = RgbaImage::from_pixel(92, 92, Rgba([0u8, 0u8, 0u8, 255u8]));
+        let output = create_chara_image(
+            &render,
+            &reference,
+            0.23f32,
+            0f32,
+            0f32,
+            Rgba([0u8, 0u8, 0u8, 0u8]),
+            MaskMode::Min,
+        );
+        let values: Vec<_> = output.pixels().map(|p| p[0]).collect();
+        let min = *values.iter().min().unwrap();
+        let max = *values.iter().max().unwrap();
+        assert!(max - min <= 2u8, "{} {}", min, max);
     }
 
-    false
-}
+    #[test]
+    fn test_warp_render_identity() {
+        let render = image::open("sample.png").unwrap().into_rgba8();
+        let output = warp_render(&render, render.width(), render.height(), 1.0f32, 0f32, 0f32);
+        assert_eq!(output, render);
+    }
 
-fn blend_alpha(current: &mut RgbaImage, reference: &RgbaImage) {
-    // Use the alpha of current with reference as a mask.
-    for x in 0..current.width() {
-        for y in 0..current.height() {
-            let current = current.get_pixel_mut(x, y);
-            let target = reference.get_pixel(x, y);
-            *current = Rgba([
-                current[0],
-                current[1],
-                current[2],
-                min(current[3], target[3]),
-            ]);
-        }
+    #[test]
+    fn test_warp_render_linear_edge() {
+        let render = RgbaImage::from_fn(2, 1, |x, _| {
+            if x == 0 {
+                Rgba([0u8, 0u8, 0u8, 255u8])
+            } else {
+                Rgba([255u8, 255u8, 255u8, 255u8])
+            }
+        });
+        // Sample halfway between the black and white pixels.
+        let output = warp_render_affine(
+            &render,
+            (1, 1),
+            1.0f32,
+            0f32,
+            (-0.5f32, 0f32),
+            Interpolation::Bilinear,
+            Rgba([0u8, 0u8, 0u8, 0u8]),
+        );
+        // Averaging the encoded values would give 128 instead of the gray for half the linear intensity.
+        let expected = to_u8_clamped(0.5f32.powf(1f32 / DEFAULT_GAMMA));
+        let pixel = output.get_pixel(0, 0);
+        assert!(pixel[0].abs_diff(expected) <= 1u8, "{:?}", pixel);
+        assert!(pixel[0] > 180u8);
+        assert_eq!(pixel[3], 255u8);
     }
-}
 
-fn alpha_blend(val1: f32, val2: f32, alpha: f32) -> f32 {
-    // Gamma correct to ensure the blending result is more accurate.
-    let val1_gamma_corrected = val1.powf(2.2f32);
-    let val2_gamma_corrected = val2.powf(2.2f32);
-    let result = val1_gamma_corrected * (1f32 - alpha) + val2_gamma_corrected * alpha;
-    result.powf(1.0f32 / 2.2f32)
-}
+    #[test]
+    fn test_warp_render_interpolation() {
+        let render = RgbaImage::from_fn(4, 1, |x, _| {
+            if x < 2 {
+                Rgba([0u8, 0u8, 0u8, 255u8])
+            } else {
+                Rgba([255u8, 255u8, 255u8, 255u8])
+            }
+        });
+        let warp = |interpolation| {
+            warp_render_affine(
+                &render,
+                (6, 1),
+                1.5f32,
+                0f32,
+                (0f32, 0f32),
+                interpolation,
+                Rgba([0u8, 0u8, 0u8, 0u8]),
+            )
+        };
 
-fn sample_texture(image: &RgbaImage, u: f32, v: f32) -> &Rgba<u8> {
-    // Flip v to transform from an origin at the bottom left (OpenGL) to top left (image).
-    let (x, y) = interpolate_nearest(u, 1f32 - v, image.dimensions().0, image.dimensions().1);
-    image.get_pixel(x, y)
-}
+        // Nearest neighbor only uses the original colors.
+        let output = warp(Interpolation::Nearest);
+        assert!(output.pixels().all(|p| p[0] == 0u8 || p[0] == 255u8));
 
-fn interpolate_nearest(x: f32, y: f32, width: u32, height: u32) -> (u32, u32) {
-    // Clamp to the edges for out of bounds indices.
-    // Clamp to 0.0f32 before casting to avoid underflow.
-    let nearest = |f: f32, max_val: u32| {
-        let val = (f * max_val as f32 - 0.5f32).round();
-        if val < 0f32 {
-            0u32
-        } else {
-            min(val as u32, max_val - 1)
-        }
-    };
+        // Bilinear blends the pixels along the edge.
+        let output = warp(Interpolation::Bilinear);
+        assert!(output.pixels().any(|p| p[0] > 0u8 && p[0] < 255u8));
+    }
 
-    (nearest(x, width), nearest(y, height))
-}
+    #[test]
+    fn test_auto_crop_skin() {
+        let skin_texture = image::open("sample.png").unwrap().into_rgba8();
+        let mut padded = RgbaImage::new(128, 128);
+        image::imageops::replace(&mut padded, &skin_texture, 0, 0);
+        assert_eq!(auto_crop_skin(&padded).unwrap(), skin_texture);
 
-fn normalize_rgba_u8(pixel: &Rgba<u8>) -> (f32, f32, f32, f32) {
-    // 0u16 -> 0.0f32, 65535u16 -> 1.0f32
-    let normalize = |u| u as f32 / 255f32;
-    (
-        normalize(pixel[0]),
-        normalize(pixel[1]),
-        normalize(pixel[2]),
-        normalize(pixel[3]),
-    )
-}
+        // Legacy skins only use the top half.
+        let legacy = image::imageops::crop_imm(&skin_texture, 0, 0, 64, 32).to_image();
+        let mut padded = RgbaImage::new(128, 128);
+        image::imageops::replace(&mut padded, &legacy, 0, 0);
+        assert_eq!(auto_crop_skin(&padded).unwrap(), legacy);
 
-fn normalize_rgba_u16(pixel: &Rgba<u16>) -> (f32, f32, f32, f32) {
-    // 0u16 -> 0.0f32, 65535u16 -> 1.0f32
-    let normalize = |u| u as f32 / 65535f32;
-    (
-        normalize(pixel[0]),
-        normalize(pixel[1]),
-        normalize(pixel[2]),
-        normalize(pixel[3]),
-    )
-}
+        // Standard skins are unchanged.
+        assert_eq!(auto_crop_skin(&skin_texture).unwrap(), skin_texture);
 
-fn to_u8_clamped(x: f32) -> u8 {
-    // Pick the nearest integer so values close to 1.0 are still converted to 255u8.
-    let result = (x * 255f32).round();
-    if result < 0.0f32 {
-        return 0u8;
-    } else if result > 255f32 {
-        return 255u8;
-    } else {
-        return result as u8;
-    }
-}
+        assert!(matches!(
+            auto_crop_skin(&RgbaImage::new(128, 128)),
+            Err(RenderError::EmptySkin)
+        ));
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        // The content is too large for a 64x64 skin but too small for a 128x128 skin.
+        let mut image = RgbaImage::new(100, 100);
+        image.put_pixel(80, 80, Rgba([255u8, 255u8, 255u8, 255u8]));
+        assert!(matches!(
+            auto_crop_skin(&image),
+            Err(RenderError::NoSkinRegion)
+        ));
+    }
 
     #[test]
-    fn test_interpolate_nearest_8x8_edges() {
-        assert_eq!(interpolate_nearest(0f32, 0f32, 8u32, 8u32), (0u32, 0u32));
-        assert_eq!(interpolate_nearest(0f32, 1f32, 8u32, 8u32), (0u32, 7u32));
-        assert_eq!(interpolate_nearest(1f32, 0f32, 8u32, 8u32), (7u32, 0u32));
-        assert_eq!(interpolate_nearest(1f32, 1f32, 8u32, 8u32), (7u32, 7u32));
+    fn test_detect_model() {
+        let skin_texture = image::open("sample.png").unwrap().into_rgba8();
+        assert_eq!(detect_model(&skin_texture), ModelType::Classic);
+
+        // Slim skins leave the outer columns of the right arm empty.
+        let mut slim_texture = skin_texture.clone();
+        for x in 54..56 {
+            for y in 16..32 {
+                slim_texture.put_pixel(x, y, Rgba([0u8, 0u8, 0u8, 0u8]));
+            }
+        }
+        assert_eq!(detect_model(&slim_texture), ModelType::Slim);
+
+        let legacy_texture = image::imageops::crop_imm(&skin_texture, 0, 0, 64, 32).to_image();
+        assert_eq!(detect_model(&legacy_texture), ModelType::Classic);
     }
 
     #[test]
-    fn test_interpolate_nearest_out_of_bounds() {
-        assert_eq!(interpolate_nearest(0f32, 1.5f32, 8u32, 8u32), (0u32, 7u32));
-        assert_eq!(interpolate_nearest(1.5f32, 0f32, 8u32, 8u32), (7u32, 0u32));
+    fn test_render_16_bit_skin() {
+        // A subtle gradient across the skin that rounds to only a few 8 bit values.
+        let skin_texture = ImageBuffer::from_fn(64, 64, |x, y| {
+            let value = 32768u16 + (y * 64 + x) as u16 * 2;
+            Rgba([value, value, value, 65535u16])
+        });
+        let context = RenderContext::new();
+        let options = RenderOptions::default();
+
+        let count_values = |render: &ImageBuffer<Rgba<u16>, Vec<u16>>| {
+            render
+                .pixels()
+                .filter(|p| p[3] == 65535u16)
+                .map(|p| p[0])
+                .collect::<std::collections::HashSet<_>>()
+                .len()
+        };
+        let render = context.render_hdr_16(&skin_texture, &options);
+        assert!(count_values(&render) > 256, "{}", count_values(&render));
+
+        // Converting the skin to 8 bits first loses the gradient.
+        let skin_texture_8 = DynamicImage::ImageRgba16(skin_texture.clone()).into_rgba8();
+        let render_8 = context.render_hdr(&skin_texture_8, &options);
+        assert!(count_values(&render_8) < count_values(&render));
+
+        // The 8 bit output matches rendering the 8 bit skin for colors that fit in 8 bits.
+        // Normalizing 8 and 16 bit values can round differently.
+        let skin_texture = expand_rgba8(&skin_texture_8);
+        let render = context.render_16(&skin_texture, &options);
+        let render_8 = context.render(&skin_texture_8, &options);
+        for (p1, p2) in render.pixels().zip(render_8.pixels()) {
+            for c in 0..4 {
+                assert!(
+                    (p1[c] as i16 - p2[c] as i16).abs() <= 1,
+                    "{:?} {:?}",
+                    p1,
+                    p2
+                );
+            }
+        }
+
+        // 8 bit skins are expanded to the full 16 bit range.
+        let skin_texture = load_skin_16("sample.png").unwrap();
         assert_eq!(
-            interpolate_nearest(1.5f32, 1.5f32, 8u32, 8u32),
-            (7u32, 7u32)
+            skin_texture,
+            expand_rgba8(&image::open("sample.png").unwrap().into_rgba8())
         );
+        assert!(skin_texture.pixels().any(|p| p[3] == 65535u16));
     }
 
     #[test]
-    fn test_normalize_u8() {
-        assert_eq!(
-            normalize_rgba_u8(&Rgba([0u8, 0u8, 0u8, 0u8])),
-            (0f32, 0f32, 0f32, 0f32)
-        );
-        assert_eq!(
-            normalize_rgba_u8(&Rgba([255u8, 255u8, 255u8, 255u8])),
-            (1f32, 1f32, 1f32, 1f32)
-        );
+    fn test_load_skin_palette() {
+        let skin_texture = load_skin("tests/fixtures/skins/palette.png").unwrap();
+        assert_eq!(skin_texture, load_skin("sample.png").unwrap());
+    }
+
+    #[test]
+    fn test_load_skin_grayscale() {
+        let skin_texture = load_skin("tests/fixtures/skins/grayscale.png").unwrap();
+        let sample = load_skin("sample.png").unwrap();
+        assert_eq!(skin_texture.dimensions(), (64, 64));
+        for (pixel, sample_pixel) in skin_texture.pixels().zip(sample.pixels()) {
+            assert!(pixel[0] == pixel[1] && pixel[1] == pixel[2]);
+            assert_eq!(pixel[3], sample_pixel[3]);
+        }
+    }
+
+    #[test]
+    fn test_validate_skin() {
+        let skin_texture = image::open("sample.png").unwrap().into_rgba8();
+        assert!(validate_skin(&skin_texture).is_ok());
+
+        let skin_texture = RgbaImage::from_pixel(64, 64, Rgba([255u8, 255u8, 255u8, 0u8]));
+        assert!(matches!(
+            validate_skin(&skin_texture),
+            Err(RenderError::EmptySkin)
+        ));
     }
 
     #[test]
@@ -391,24 +5194,128 @@ mod tests {
     }
 
     #[test]
-    fn test_normalize_u16() {
+    fn test_color_correct_image() {
+        let image = RgbaImage::from_fn(4, 4, |x, y| {
+            Rgba([(x * 64) as u8, (y * 64) as u8, 200u8, (x * y * 16) as u8])
+        });
+        let mut expected = image.clone();
+        for pixel in expected.pixels_mut() {
+            *pixel = color_correct(pixel);
+        }
+        assert_eq!(color_corrected(&image), expected);
+
+        let options = ColorCorrectOptions {
+            linear: true,
+            skip_transparent: true,
+            ..Default::default()
+        };
+        let mut corrected = image.clone();
+        color_correct_image_with_options(&mut corrected, &options);
+        for (pixel, corrected) in image.pixels().zip(corrected.pixels()) {
+            assert_eq!(*corrected, color_correct_with_options(pixel, &options));
+        }
+    }
+
+    #[test]
+    fn test_color_correct_linear() {
+        // The linear adjustment is brighter than the fast adjustment for mid gray.
+        assert_eq!(
+            color_correct(&Rgba([128u8, 128u8, 128u8, 13u8])),
+            Rgba([112u8, 112u8, 112u8, 13u8])
+        );
+        assert_eq!(
+            color_correct_linear(&Rgba([128u8, 128u8, 128u8, 13u8])),
+            Rgba([134u8, 134u8, 134u8, 13u8])
+        );
+
+        // A gamma and scale of 1.0 doesn't change the color.
+        assert_eq!(
+            color_correct_linear_with(&Rgba([64u8, 128u8, 192u8, 255u8]), 1.0f32, 1.0f32),
+            Rgba([64u8, 128u8, 192u8, 255u8])
+        );
+        assert_eq!(
+            color_correct_linear(&Rgba([0u8, 0u8, 0u8, 0u8])),
+            Rgba([0u8, 0u8, 0u8, 0u8])
+        );
+    }
+
+    #[test]
+    fn test_color_correct_inverse() {
+        for c in 0..=255u8 {
+            let color = Rgba([c, c, c, c]);
+            let round_trip = color_correct_inverse(&color_correct(&color));
+            assert!(
+                (round_trip[0] as i16 - c as i16).abs() <= 1,
+                "{} {:?}",
+                c,
+                round_trip
+            );
+            assert_eq!(round_trip[3], c);
+        }
+
+        // Brighter values than the corrected white are clamped.
+        assert_eq!(
+            color_correct_inverse(&Rgba([184u8, 184u8, 184u8, 255u8])),
+            Rgba([255u8, 255u8, 255u8, 255u8])
+        );
+        assert_eq!(
+            color_correct_inverse(&Rgba([255u8, 255u8, 255u8, 255u8])),
+            Rgba([255u8, 255u8, 255u8, 255u8])
+        );
+    }
+
+    #[test]
+    fn test_color_correct_skip_transparent() {
+        // Transparent pixels can still have colors from image editors.
+        let transparent = Rgba([255u8, 128u8, 64u8, 0u8]);
+        let opaque = Rgba([255u8, 128u8, 64u8, 255u8]);
+
+        let options = ColorCorrectOptions {
+            skip_transparent: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            color_correct_with_options(&transparent, &options),
+            transparent
+        );
         assert_eq!(
-            normalize_rgba_u16(&Rgba([0u16, 0u16, 0u16, 0u16])),
-            (0f32, 0f32, 0f32, 0f32)
+            color_correct_with_options(&opaque, &options),
+            color_correct(&opaque)
         );
+
+        // Transparent pixels are corrected by default.
         assert_eq!(
-            normalize_rgba_u16(&Rgba([65535u16, 65535u16, 65535u16, 65535u16])),
-            (1f32, 1f32, 1f32, 1f32)
+            color_correct_with_options(&transparent, &ColorCorrectOptions::default()),
+            color_correct(&transparent)
         );
+        assert_ne!(color_correct(&transparent), transparent);
     }
 
     #[test]
-    fn test_to_u8_clamped() {
-        assert_eq!(to_u8_clamped(0.999f32), 255u8);
-        assert_eq!(to_u8_clamped(-1.5f32), 0u8);
-        assert_eq!(to_u8_clamped(0f32), 0u8);
-        assert_eq!(to_u8_clamped(0.5f32), 128u8);
-        assert_eq!(to_u8_clamped(1f32), 255u8);
-        assert_eq!(to_u8_clamped(1.01f32), 255u8);
+    fn test_color_correct_with() {
+        for color in &[
+            Rgba([0u8, 0u8, 0u8, 0u8]),
+            Rgba([128u8, 128u8, 128u8, 13u8]),
+            Rgba([255u8, 255u8, 255u8, 255u8]),
+        ] {
+            assert_eq!(
+                color_correct_with(color, 0.72f32, 0.72f32),
+                color_correct(color)
+            );
+        }
+
+        // A gamma and scale of 1.0 doesn't change the color.
+        assert_eq!(
+            color_correct_with(&Rgba([64u8, 128u8, 192u8, 255u8]), 1.0f32, 1.0f32),
+            Rgba([64u8, 128u8, 192u8, 255u8])
+        );
+        assert_eq!(
+            color_correct_with(&Rgba([64u8, 128u8, 255u8, 255u8]), 0.5f32, 1.0f32),
+            Rgba([128u8, 181u8, 255u8, 255u8])
+        );
+        assert_eq!(
+            color_correct_with(&Rgba([64u8, 128u8, 255u8, 255u8]), 0.8f32, 0.8f32),
+            Rgba([68u8, 118u8, 204u8, 255u8])
+        );
     }
 }
@@ -1,21 +1,61 @@
 use clap::{App, Arg};
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::codecs::png::PngEncoder;
+use image::imageops::FilterType;
+use image::{
+    ColorType, Delay, DynamicImage, Frame, ImageBuffer, ImageEncoder, ImageResult, Rgba, RgbaImage,
+};
+use minecraft_render::compositing::make_contact_sheet;
+use minecraft_render::output::{
+    encode_image, save_image, ColorProfile, OutputFormat, OutputOptions,
+};
+use minecraft_render::{
+    CharaSlot, ColorCorrectOptions, Interpolation, ModelType, RenderContext, RenderError,
+    RenderManifest, RenderOptions, RenderStats,
+};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
 use std::time::Instant;
 
 fn main() {
     // TODO: Create better argument names.
-    let matches = App::new("minecraft_render")
+    let app = App::new("minecraft_render")
         .version("0.1")
         .author("SMG")
         .about("Create Smash Ultimate Steve UI from Minecraft skin textures")
+        .arg(
+            Arg::with_name("verbose")
+                .short("v")
+                .long("verbose")
+                .help("log each preprocessing step, overlay decision, and saved file")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("quiet")
+                .short("q")
+                .long("quiet")
+                .help("only print warnings and errors")
+                .conflicts_with("verbose")
+                .takes_value(false),
+        )
         .arg(
             Arg::with_name("skin")
                 .short("s")
                 .long("skin")
                 .value_name("sample.png")
                 .help("the Minecraft skin texture")
-                .required(true)
+                .required_unless_one(&["username", "pack", "batch", "stdin"])
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("fallback")
+                .long("fallback")
+                .help("render the default skin if the skin texture can't be loaded")
+                .conflicts_with_all(&["batch", "check"])
+                .takes_value(false),
+        )
         .arg(
             Arg::with_name("is_legacy")
                 .short("l")
@@ -23,6 +63,12 @@ fn main() {
                 .help("convert 2:1 skins (pre Minecraft v1.8) to 1:1 aspect ratio")
                 .takes_value(false),
         )
+        .arg(
+            Arg::with_name("auto_crop")
+                .long("auto-crop")
+                .help("remove transparent padding from skins exported with a larger canvas")
+                .takes_value(false),
+        )
         .arg(
             Arg::with_name("color_correct")
                 .short("c")
@@ -30,78 +76,1028 @@ fn main() {
                 .help("levels adjustment to match Smash Ultimate")
                 .takes_value(false),
         )
+        .arg(
+            Arg::with_name("contrast")
+                .long("contrast")
+                .value_name("0.72")
+                .help("the value for the levels adjustment (input ^ contrast) * contrast")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("correction")
+                .long("correction")
+                .value_name("fast")
+                .help("adjust the encoded colors (fast) or linear colors (linear) when color correcting")
+                .possible_values(&["fast", "linear"])
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("skip_transparent")
+                .long("skip-transparent")
+                .help("leave fully transparent pixels unchanged when color correcting")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("clean_overlays")
+                .long("clean-overlays")
+                .help("clear overlay regions filled with opaque black by older skin editors")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("unpremultiply_input")
+                .long("unpremultiply-input")
+                .help("convert skins exported with premultiplied alpha to straight alpha")
+                .takes_value(false),
+        )
         .arg(
             Arg::with_name("is_slim")
                 .long("slim")
-                .help("render as slim character")
-                .takes_value(false)
+                .help("render as slim character instead of detecting the model from the skin")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("is_classic")
+                .long("classic")
+                .help("render as classic character instead of detecting the model from the skin")
+                .conflicts_with("is_slim")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("cape")
+                .long("cape")
+                .value_name("cape.png")
+                .help("the Minecraft cape texture")
+                .required(false)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("no_overlays")
+                .long("no-overlays")
+                .help("skip the second skin layers like the hat and jacket")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("overlay_opacity")
+                .long("overlay-opacity")
+                .value_name("1.0")
+                .help("the factor for the alpha of the second skin layers")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("background")
+                .long("background")
+                .value_name("ffffff")
+                .help("fill the background with a hex color instead of transparency")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("premultiply")
+                .long("premultiply")
+                .help("output premultiplied alpha for programs that don't expect straight alpha")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("format")
+                .long("format")
+                .value_name("png")
+                .help("the output file format (png, jpeg, webp, or exr with the exr feature)")
+                .possible_values(&["png", "jpg", "jpeg", "webp", "exr"])
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("bit_depth")
+                .long("bit-depth")
+                .value_name("8")
+                .help("the bits per channel for the render (16 saves linear color as a png)")
+                .possible_values(&["8", "16"])
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("bgra")
+                .long("bgra")
+                .help("swap the red and blue channels of the saved images for engines that expect BGRA")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("icc")
+                .long("icc")
+                .value_name("FILE")
+                .help("the ICC profile to embed in saved PNG files instead of the sRGB chunk")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("quality")
+                .long("quality")
+                .value_name("90")
+                .help("the quality from 0 to 100 for lossy formats")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("gamma")
+                .long("gamma")
+                .value_name("2.2")
+                .help("the gamma for alpha blending or 1.0 for linear blending")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("supersample")
+                .long("supersample")
+                .value_name("1")
+                .help("render at a higher resolution and average down to reduce aliasing")
+                .takes_value(true),
         )
-        .get_matches();
+        .arg(
+            Arg::with_name("smooth_uvs")
+                .long("smooth-uvs")
+                .help("interpolate the UV maps to smooth edges for scaled or supersampled renders")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("debug_uv")
+                .long("debug-uv")
+                .help("draw the texture coordinates from the UV maps as red and green instead of the skin")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("chara_filter")
+                .long("chara-filter")
+                .value_name("bilinear")
+                .help("the interpolation for resizing the chara images (bilinear or nearest)")
+                .possible_values(&["bilinear", "nearest"])
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("chara_mask")
+                .long("chara-mask")
+                .value_name("chara_3=mask.png")
+                .help("replace the embedded mask for a chara image with an RGBA png file")
+                .multiple(true)
+                .number_of_values(1)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("trim")
+                .long("trim")
+                .help("crop the transparent margins from the output render but not the chara images")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("native_size")
+                .long("native-size")
+                .help("resize the chara images to the texture sizes used in game")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("contact_sheet")
+                .long("contact-sheet")
+                .help("also save the render and chara images side by side in a single image")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("scale")
+                .long("scale")
+                .value_name("1")
+                .help("the resolution multiplier for the output render")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("stdin")
+                .long("stdin")
+                .help("read the Minecraft skin texture from stdin")
+                .conflicts_with("skin")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("stdout")
+                .long("stdout")
+                .help("write only the render to stdout instead of saving files")
+                .conflicts_with_all(&["contact_sheet", "chara_filter"])
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("manifest")
+                .long("manifest")
+                .help("also save output.json describing the skin, model, overlays, and render")
+                .conflicts_with("stdout")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("uv_cache")
+                .long("uv-cache")
+                .value_name("uv_cache.bin")
+                .help("load the decoded UV maps from a cache file or create the file if missing")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("animate")
+                .long("animate")
+                .help("also save an animated gif that draws the layers one at a time")
+                .conflicts_with("stdout")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("check")
+                .long("check")
+                .help("report which skins are valid, legacy, slim, or invalid without rendering")
+                .conflicts_with("stdout")
+                .takes_value(false),
+        );
 
-    let texture_path = matches.value_of("skin").unwrap();
-    let mut skin_texture = image::open(texture_path).unwrap().into_rgba();
-    if matches.is_present("is_legacy") {
-        skin_texture = minecraft_render::modern_skin::convert_to_modern_skin(&skin_texture);
+    #[cfg(feature = "online")]
+    let app = app
+        .arg(
+            Arg::with_name("username")
+                .short("u")
+                .long("username")
+                .value_name("Notch")
+                .help("download the skin texture for a Minecraft username")
+                .conflicts_with_all(&["skin", "stdin", "check"])
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("cache_dir")
+                .long("cache-dir")
+                .value_name("skin_cache")
+                .help("the folder for saving downloaded skins")
+                .requires("username")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("no_cache")
+                .long("no-cache")
+                .help("always download the skin instead of using a saved skin")
+                .requires("username")
+                .conflicts_with("cache_dir")
+                .takes_value(false),
+        );
+
+    #[cfg(feature = "pack")]
+    let app = app
+        .arg(
+            Arg::with_name("pack")
+                .long("pack")
+                .value_name("pack.zip")
+                .help("read the skin texture from a ZIP resource pack with --entry")
+                .conflicts_with_all(&["skin", "stdin", "username", "batch", "check"])
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("entry")
+                .long("entry")
+                .value_name("path/in/pack.png")
+                .help("the path of the skin texture in the pack")
+                .requires("pack")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("list")
+                .long("list")
+                .help("list the png entries in the pack without rendering")
+                .requires("pack")
+                .conflicts_with("entry")
+                .takes_value(false),
+        );
+
+    let app = app.arg(
+        Arg::with_name("batch")
+            .long("batch")
+            .value_names(&["input_dir", "output_dir"])
+            .help("render all png skins in input_dir into output_dir")
+            .conflicts_with_all(&["skin", "stdin", "stdout"])
+            .takes_value(true)
+            .number_of_values(2),
+    );
+
+    let matches = app.get_matches();
+
+    // RUST_LOG still applies without --verbose for more specific filtering.
+    let mut logger = env_logger::Builder::from_default_env();
+    if matches.is_present("verbose") {
+        logger.filter_level(log::LevelFilter::Debug);
+    }
+    logger.init();
+    let quiet = matches.is_present("quiet");
+
+    let options = render_options(&matches);
+    let outputs = Outputs {
+        chara_slots: chara_slots(
+            match matches.value_of("chara_filter") {
+                Some("nearest") => Interpolation::Nearest,
+                _ => Interpolation::Bilinear,
+            },
+            matches.values_of("chara_mask").into_iter().flatten(),
+        ),
+        options: output_options(&matches, &options),
+        native_size: matches.is_present("native_size"),
+        trim: matches.is_present("trim"),
+        contact_sheet: matches.is_present("contact_sheet"),
+        hdr: matches.value_of("bit_depth") == Some("16"),
+        animate: matches.is_present("animate"),
+        manifest: matches.is_present("manifest"),
+        // Extend the background past the edges of the render for solid chara images.
+        chara_fill: options.background.unwrap_or(Rgba([0u8, 0u8, 0u8, 0u8])),
+        quiet,
+    };
+    if outputs.hdr && outputs.options.format != OutputFormat::Png {
+        panic!("A bit depth of 16 is only supported for png output");
+    }
+    if outputs.hdr && outputs.options.bgra {
+        panic!("BGRA output is only supported for a bit depth of 8");
     }
+    #[cfg(feature = "exr")]
+    if outputs.options.format == OutputFormat::Exr && (outputs.options.bgra || options.premultiply)
+    {
+        panic!("exr output always stores premultiplied RGBA");
+    }
+    let preprocess = Preprocess {
+        auto_crop: matches.is_present("auto_crop"),
+        legacy: matches.is_present("is_legacy"),
+        clean_overlays: matches.is_present("clean_overlays"),
+        unpremultiply_gamma: matches
+            .is_present("unpremultiply_input")
+            .then_some(options.blend_gamma),
+        color_correct: if matches.is_present("color_correct") || matches.is_present("contrast") {
+            let contrast = matches
+                .value_of("contrast")
+                .map(|c| c.parse().unwrap())
+                .unwrap_or(0.72f32);
+            Some(ColorCorrectOptions {
+                gamma: contrast,
+                scale: contrast,
+                linear: matches.value_of("correction") == Some("linear"),
+                skip_transparent: matches.is_present("skip_transparent"),
+            })
+        } else {
+            None
+        },
+    };
 
-    if matches.is_present("color_correct") {
-        for pixel in skin_texture.pixels_mut() {
-            *pixel = minecraft_render::color_correct(pixel);
+    let model = if matches.is_present("is_slim") {
+        Some(ModelType::Slim)
+    } else if matches.is_present("is_classic") {
+        Some(ModelType::Classic)
+    } else {
+        None
+    };
+
+    #[cfg(feature = "pack")]
+    if matches.is_present("list") {
+        let pack = std::fs::File::open(matches.value_of("pack").unwrap()).unwrap();
+        for entry in minecraft_render::pack::list_png_entries(pack).unwrap() {
+            println!("{}", entry);
         }
+        return;
     }
 
-    let start_time = Instant::now();
+    if matches.is_present("check") {
+        let all_valid = match matches.values_of("batch") {
+            Some(mut dirs) => check_batch(Path::new(dirs.next().unwrap()), quiet),
+            None => {
+                let skin_texture = read_skin_texture(&matches);
+                let name = matches.value_of("skin").unwrap_or("stdin");
+                check_skin(name, skin_texture)
+            }
+        };
+        std::process::exit(if all_valid { 0 } else { 1 });
+    }
 
-    let output = 
-        if matches.is_present("is_slim") {
-            minecraft_render::create_render_slim(&skin_texture)
+    // Decode the UV maps only once when rendering multiple skins.
+    let uv_cache = matches.value_of("uv_cache").map(Path::new);
+    let context = match uv_cache {
+        Some(path) if path.exists() => RenderContext::load_cache(path).unwrap_or_else(|e| {
+            eprintln!("Ignoring UV map cache: {}", e);
+            RenderContext::new()
+        }),
+        _ => RenderContext::new(),
+    };
+    // Save the cache after rendering to include the maps used for the render.
+    let save_uv_cache = |context: &RenderContext| {
+        if let Some(path) = uv_cache.filter(|path| !path.exists()) {
+            context.save_cache(path).unwrap();
+        }
+    };
+
+    if let Some(mut dirs) = matches.values_of("batch") {
+        let input_dir = Path::new(dirs.next().unwrap());
+        let output_dir = Path::new(dirs.next().unwrap());
+        render_batch(
+            input_dir,
+            output_dir,
+            &context,
+            &preprocess,
+            &options,
+            model,
+            &outputs,
+        );
+        save_uv_cache(&context);
+        return;
+    }
+
+    let (skin_texture, profile_model) = match load_skin_texture(&matches) {
+        Ok(skin) => skin,
+        Err(e) if matches.is_present("fallback") => {
+            eprintln!("Warning: {}. Rendering the default skin instead.", e);
+            let fallback_model = model.unwrap_or(ModelType::Classic);
+            (
+                minecraft_render::fallback_skin(fallback_model),
+                Some(fallback_model),
+            )
+        }
+        Err(e) => panic!("Failed to load the skin texture: {}", e),
+    };
+    let skin_dimensions = skin_texture.dimensions();
+    let skin_texture = preprocess.apply(skin_texture);
+    let options = skin_options(&options, model.or(profile_model), &skin_texture);
+    if let Err(e) = minecraft_render::validate_skin(&skin_texture) {
+        eprintln!("Warning: {}", e);
+    }
+
+    let (output, stats) = context.render_with_stats(&skin_texture, &options);
+    if !quiet {
+        eprintln!(
+            "Create Render: {:?} ({} layers)",
+            stats.elapsed, stats.layers_blended
+        );
+    }
+    save_uv_cache(&context);
+
+    // Only one image can be written to stdout, so skip the chara images.
+    if matches.is_present("stdout") {
+        let hdr_output = HdrOutput::render(&context, &skin_texture, &options, &outputs);
+        let (output, hdr_output) = if outputs.trim {
+            trim_output(&output, hdr_output.as_ref())
         } else {
-            minecraft_render::create_render(&skin_texture)
+            (output, hdr_output)
         };
+        let bytes = match &hdr_output {
+            Some(hdr_output) => hdr_output.encode(),
+            None => encode_image(&output, &outputs.options),
+        }
+        .unwrap();
+        std::io::stdout().lock().write_all(&bytes).unwrap();
+        return;
+    }
+
+    let hdr_output = HdrOutput::render(&context, &skin_texture, &options, &outputs);
+    let frames = outputs
+        .animate
+        .then(|| context.render_layer_frames(&skin_texture, &options));
+    let manifest = outputs
+        .manifest
+        .then(|| preprocess.manifest(skin_dimensions, &skin_texture, &output, &options, &stats));
+    save_outputs(
+        &output,
+        hdr_output.as_ref(),
+        frames.as_deref(),
+        manifest.as_ref(),
+        options.scale,
+        &outputs,
+        Path::new(""),
+    )
+    .unwrap();
+}
+
+struct Preprocess {
+    auto_crop: bool,
+    legacy: bool,
+    clean_overlays: bool,
+    unpremultiply_gamma: Option<f32>,
+    color_correct: Option<ColorCorrectOptions>,
+}
 
-    let elapsed = start_time.elapsed();
-    eprintln!("Create Render: {:?}", elapsed);
+impl Preprocess {
+    fn apply(&self, mut skin_texture: RgbaImage) -> RgbaImage {
+        log::info!(
+            "Loaded {}x{} skin",
+            skin_texture.width(),
+            skin_texture.height()
+        );
+
+        if self.auto_crop {
+            match minecraft_render::auto_crop_skin(&skin_texture) {
+                Ok(cropped) => skin_texture = cropped,
+                Err(e) => eprintln!("Skipping auto crop: {}", e),
+            }
+        }
+
+        if self.legacy {
+            skin_texture = minecraft_render::modern_skin::convert_to_modern_skin(&skin_texture);
+            log::info!("Converted legacy skin to the modern layout");
+        }
+
+        if self.clean_overlays {
+            let cleaned = minecraft_render::clean_opaque_overlay_noise(&mut skin_texture);
+            if !cleaned.is_empty() {
+                log::info!("Cleared opaque black overlays {:?}", cleaned);
+            }
+        }
+
+        if let Some(gamma) = self.unpremultiply_gamma {
+            minecraft_render::unpremultiply_alpha(&mut skin_texture, gamma);
+            log::info!("Converted premultiplied alpha to straight alpha");
+        }
+
+        if let Some(options) = &self.color_correct {
+            minecraft_render::color_correct_image_with_options(&mut skin_texture, options);
+            log::info!("Applied color correction with {:?}", options);
+        }
+        skin_texture
+    }
+
+    fn manifest(
+        &self,
+        skin_dimensions: (u32, u32),
+        skin_texture: &RgbaImage,
+        output: &RgbaImage,
+        options: &RenderOptions,
+        stats: &RenderStats,
+    ) -> RenderManifest {
+        // Describe the original skin before any preprocessing.
+        RenderManifest {
+            skin_width: skin_dimensions.0,
+            skin_height: skin_dimensions.1,
+            legacy_converted: self.legacy,
+            color_corrected: self.color_correct.is_some(),
+            ..RenderManifest::new(skin_texture, output, options, stats)
+        }
+    }
+}
+
+fn render_options(matches: &clap::ArgMatches) -> RenderOptions {
+    let cape = matches
+        .value_of("cape")
+        .map(|path| image::open(path).unwrap().into_rgba8());
+
+    let scale = matches
+        .value_of("scale")
+        .map(|s| s.parse().unwrap())
+        .unwrap_or(1u32);
+
+    RenderOptions {
+        cape,
+        scale,
+        supersample: matches
+            .value_of("supersample")
+            .map(|s| s.parse().unwrap())
+            .unwrap_or(1u32),
+        blend_gamma: matches
+            .value_of("gamma")
+            .map(|g| g.parse().unwrap())
+            .unwrap_or(2.2f32),
+        background: matches.value_of("background").map(parse_hex_color),
+        disable_overlays: matches.is_present("no_overlays"),
+        overlay_opacity: matches
+            .value_of("overlay_opacity")
+            .map(|o| o.parse().unwrap())
+            .unwrap_or(1.0f32),
+        premultiply: matches.is_present("premultiply"),
+        smooth_uvs: matches.is_present("smooth_uvs"),
+        debug_uvs: matches.is_present("debug_uv"),
+        ..Default::default()
+    }
+}
+
+fn skin_options(
+    options: &RenderOptions,
+    model: Option<ModelType>,
+    skin_texture: &RgbaImage,
+) -> RenderOptions {
+    // Detect the model from the skin if not specified.
+    if let Some(Err(e)) = model.map(|model| minecraft_render::check_model(skin_texture, model)) {
+        eprintln!("Warning: {}", e);
+    }
+    let model = model.unwrap_or_else(|| minecraft_render::detect_model(skin_texture));
+    log::debug!("Using the {:?} model", model);
+    RenderOptions {
+        slim: model == ModelType::Slim,
+        ..options.clone()
+    }
+}
+
+fn output_options(matches: &clap::ArgMatches, options: &RenderOptions) -> OutputOptions {
+    let default_output = OutputOptions::default();
+    OutputOptions {
+        format: matches
+            .value_of("format")
+            .map(|f| f.parse().unwrap())
+            .unwrap_or(default_output.format),
+        quality: matches
+            .value_of("quality")
+            .map(|q| q.parse().unwrap())
+            .unwrap_or(default_output.quality),
+        // Formats without alpha use the render's background if present.
+        background: options.background.unwrap_or(default_output.background),
+        bgra: matches.is_present("bgra"),
+        color_profile: matches
+            .value_of("icc")
+            .map(|path| ColorProfile::Icc(std::fs::read(path).unwrap()))
+            .unwrap_or(default_output.color_profile),
+    }
+}
+
+struct Outputs {
+    chara_slots: Vec<CharaSlot>,
+    options: OutputOptions,
+    native_size: bool,
+    trim: bool,
+    contact_sheet: bool,
+    hdr: bool,
+    animate: bool,
+    manifest: bool,
+    chara_fill: Rgba<u8>,
+    quiet: bool,
+}
+
+fn chara_slots<'a>(
+    interpolation: Interpolation,
+    mask_overrides: impl Iterator<Item = &'a str>,
+) -> Vec<CharaSlot> {
+    let mut slots = minecraft_render::chara_slots(interpolation);
+
+    // Custom masks only change the shape and size of the chara image, not the transformation.
+    for mask_override in mask_overrides {
+        let (name, path) = mask_override.split_once('=').unwrap_or_else(|| {
+            panic!("Expected <slot>=<path> for --chara-mask: {}", mask_override)
+        });
+        let slot = slots
+            .iter_mut()
+            .find(|slot| slot.name.strip_suffix("_custom") == Some(name))
+            .unwrap_or_else(|| panic!("Unknown chara slot {:?}", name));
+        slot.mask = load_chara_mask(Path::new(path));
+        // Assume custom masks already match the size of the custom UI frame.
+        slot.native_size = slot.mask.dimensions();
+    }
+    slots
+}
+
+fn load_chara_mask(path: &Path) -> RgbaImage {
+    let mask = image::open(path).unwrap();
+    // The alpha defines the shape of the chara image, so masks without alpha aren't useful.
+    match mask.color() {
+        ColorType::Rgba8 | ColorType::Rgba16 => mask.into_rgba8(),
+        color => panic!(
+            "Expected an RGBA chara mask for {:?} but found {:?}",
+            path, color
+        ),
+    }
+}
+
+fn save_outputs(
+    output: &RgbaImage,
+    hdr_output: Option<&HdrOutput>,
+    frames: Option<&[RgbaImage]>,
+    manifest: Option<&RenderManifest>,
+    render_scale: u32,
+    outputs: &Outputs,
+    output_dir: &Path,
+) -> ImageResult<()> {
+    let extension = outputs.options.format.extension();
+    let save = |image: &RgbaImage, name: &str| {
+        let path = output_dir.join(format!("{}.{}", name, extension));
+        save_image(image, &path, &outputs.options)?;
+        log::info!("Saved {:?}", path);
+        Ok(())
+    };
 
     // Create UI renders from the output render.
-    // The transformations are hardcoded based on the output render resolution.
-    // The final render is scaled down to match the appropriate sizes.
-    let chara_3 = image::load_from_memory(include_bytes!("../images/masks/chara_3_mask.png"))
-        .unwrap()
-        .into_rgba();
+    // Account for the render resolution, since the scale is relative to the default resolution.
+    // The slots only read the render, so they can be warped and saved independently.
+    let create_chara = |slot: &CharaSlot| {
+        let mut chara = slot.create(output, render_scale, outputs.chara_fill);
+        // Resize after masking, so the mask edges are also filtered.
+        let (width, height) = slot.native_size;
+        if outputs.native_size && chara.dimensions() != slot.native_size {
+            chara = image::imageops::resize(&chara, width, height, FilterType::Lanczos3);
+        }
+        save(&chara, slot.name).map(|_| (slot.name, chara))
+    };
+    #[cfg(feature = "rayon")]
+    let charas = outputs
+        .chara_slots
+        .par_iter()
+        .map(create_chara)
+        .collect::<ImageResult<Vec<_>>>()?;
+    #[cfg(not(feature = "rayon"))]
+    let charas = outputs
+        .chara_slots
+        .iter()
+        .map(create_chara)
+        .collect::<ImageResult<Vec<_>>>()?;
 
-    let chara_3_custom = minecraft_render::create_chara_image(
-        &output,
-        &chara_3,
-        1.28451252f32,
-        -456.55612f32,
-        11.757321f32,
-    );
+    // The chara transforms assume the full render, so only trim the saved render.
+    let trimmed = outputs.trim.then(|| trim_output(output, hdr_output));
+    let (output, hdr_output) = match &trimmed {
+        Some((output, hdr_output)) => (output, hdr_output.as_ref()),
+        None => (output, hdr_output),
+    };
 
-    let chara_4 = image::load_from_memory(include_bytes!("../images/masks/chara_4_mask.png"))
-        .unwrap()
-        .into_rgba();
-    let chara_4_custom = minecraft_render::create_chara_image(
-        &output,
-        &chara_4,
-        0.232882008f32,
-        -90.16959f32,
-        9.084564f32,
-    );
+    if outputs.contact_sheet {
+        let mut images = vec![("output", output)];
+        images.extend(charas.iter().map(|(name, chara)| (*name, chara)));
+        save(&make_contact_sheet(&images), "contact_sheet")?;
+    }
+
+    if let Some(frames) = frames {
+        let path = output_dir.join("layers.gif");
+        encode_layer_animation(frames, std::fs::File::create(&path)?)?;
+        log::info!("Saved {:?}", path);
+    }
 
-    let chara_6 = image::load_from_memory(include_bytes!("../images/masks/chara_6_mask.png"))
+    if let Some(manifest) = manifest {
+        let json = serde_json::to_string_pretty(manifest).unwrap();
+        std::fs::write(output_dir.join("output.json"), json)?;
+    }
+
+    // The chara images and contact sheet still use 8 bits per channel.
+    match hdr_output {
+        Some(hdr_output) => std::fs::write(
+            output_dir.join(format!("output.{}", extension)),
+            hdr_output.encode()?,
+        )
+        .map_err(Into::into),
+        None => save(output, "output"),
+    }
+}
+
+// The render with more precision than 8 bits per channel.
+enum HdrOutput {
+    // Linear color saved as a 16 bit png.
+    Png16(ImageBuffer<Rgba<u16>, Vec<u16>>),
+    // Unclamped linear float color for compositing programs.
+    #[cfg(feature = "exr")]
+    Exr(ImageBuffer<Rgba<f32>, Vec<f32>>),
+}
+
+impl HdrOutput {
+    fn render(
+        context: &RenderContext,
+        skin_texture: &RgbaImage,
+        options: &RenderOptions,
+        outputs: &Outputs,
+    ) -> Option<Self> {
+        #[cfg(feature = "exr")]
+        if outputs.options.format == OutputFormat::Exr {
+            return Some(HdrOutput::Exr(context.render_linear(skin_texture, options)));
+        }
+        outputs
+            .hdr
+            .then(|| HdrOutput::Png16(context.render_hdr(skin_texture, options)))
+    }
+
+    fn crop(&self, rect: image::math::Rect) -> Self {
+        match self {
+            HdrOutput::Png16(image) => HdrOutput::Png16(
+                image::imageops::crop_imm(image, rect.x, rect.y, rect.width, rect.height)
+                    .to_image(),
+            ),
+            #[cfg(feature = "exr")]
+            HdrOutput::Exr(image) => HdrOutput::Exr(
+                image::imageops::crop_imm(image, rect.x, rect.y, rect.width, rect.height)
+                    .to_image(),
+            ),
+        }
+    }
+
+    fn encode(&self) -> ImageResult<Vec<u8>> {
+        match self {
+            HdrOutput::Png16(image) => encode_png_16(image),
+            #[cfg(feature = "exr")]
+            HdrOutput::Exr(image) => minecraft_render::output::encode_exr(image),
+        }
+    }
+}
+
+fn trim_output(
+    output: &RgbaImage,
+    hdr_output: Option<&HdrOutput>,
+) -> (RgbaImage, Option<HdrOutput>) {
+    // Crop both bit depths to the same rectangle to keep the outputs aligned.
+    let (output, rect) = minecraft_render::trim_transparent(output);
+    let hdr_output = hdr_output.map(|hdr_output| hdr_output.crop(rect));
+    (output, hdr_output)
+}
+
+fn encode_layer_animation<W: Write>(frames: &[RgbaImage], writer: W) -> ImageResult<()> {
+    // Use the fastest color quantization since the frames are full size renders.
+    let mut encoder = GifEncoder::new_with_speed(writer, 30);
+    encoder.set_repeat(Repeat::Infinite)?;
+    // Hold the final frame longer to show the finished render.
+    let frame_count = frames.len();
+    encoder.encode_frames(frames.iter().enumerate().map(|(i, frame)| {
+        let delay_ms = if i + 1 == frame_count { 2000 } else { 500 };
+        Frame::from_parts(frame.clone(), 0, 0, Delay::from_numer_denom_ms(delay_ms, 1))
+    }))
+}
+
+fn encode_png_16(image: &ImageBuffer<Rgba<u16>, Vec<u16>>) -> ImageResult<Vec<u8>> {
+    // DynamicImage::write_to doesn't convert 16 bit channels to big endian,
+    // so use the encoder directly.
+    let mut bytes = Vec::new();
+    let (width, height) = image.dimensions();
+    PngEncoder::new(&mut bytes).write_image(
+        DynamicImage::ImageRgba16(image.clone()).as_bytes(),
+        width,
+        height,
+        ColorType::Rgba16,
+    )?;
+    Ok(bytes)
+}
+
+fn render_batch(
+    input_dir: &Path,
+    output_dir: &Path,
+    context: &RenderContext,
+    preprocess: &Preprocess,
+    options: &RenderOptions,
+    model: Option<ModelType>,
+    outputs: &Outputs,
+) {
+    let start_time = Instant::now();
+
+    let paths = skin_paths(input_dir);
+
+    // Each skin gets its own folder to avoid overwriting the chara outputs.
+    let render_file = |path: &PathBuf| {
+        let result = image::open(path).and_then(|skin| {
+            let skin_texture = skin.into_rgba8();
+            let skin_dimensions = skin_texture.dimensions();
+            let skin_texture = preprocess.apply(skin_texture);
+            if let Err(e) = minecraft_render::validate_skin(&skin_texture) {
+                eprintln!("Warning for {:?}: {}", path, e);
+            }
+            let options = skin_options(options, model, &skin_texture);
+            let (output, stats) = context.render_with_stats(&skin_texture, &options);
+            let hdr_output = HdrOutput::render(context, &skin_texture, &options, outputs);
+            let frames = outputs
+                .animate
+                .then(|| context.render_layer_frames(&skin_texture, &options));
+            let manifest = outputs.manifest.then(|| {
+                preprocess.manifest(skin_dimensions, &skin_texture, &output, &options, &stats)
+            });
+
+            let skin_output_dir = output_dir.join(path.file_stem().unwrap());
+            std::fs::create_dir_all(&skin_output_dir)?;
+            save_outputs(
+                &output,
+                hdr_output.as_ref(),
+                frames.as_deref(),
+                manifest.as_ref(),
+                options.scale,
+                outputs,
+                &skin_output_dir,
+            )
+        });
+
+        // Skip invalid files instead of stopping the entire batch.
+        if let Err(e) = &result {
+            eprintln!("Skipping {:?}: {}", path, e);
+        }
+        result.is_ok()
+    };
+
+    #[cfg(feature = "rayon")]
+    let rendered_count = paths.par_iter().filter(|p| render_file(p)).count();
+    #[cfg(not(feature = "rayon"))]
+    let rendered_count = paths.iter().filter(|p| render_file(p)).count();
+
+    if !outputs.quiet {
+        eprintln!(
+            "Rendered {} of {} skins in {:?}",
+            rendered_count,
+            paths.len(),
+            start_time.elapsed()
+        );
+    }
+}
+
+fn skin_paths(input_dir: &Path) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(input_dir)
         .unwrap()
-        .into_rgba();
-    let chara_6_custom = minecraft_render::create_chara_image(
-        &output,
-        &chara_6,
-        0.938028f32,
-        -480.87906f32,
-        -96.13269f32,
-    );
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            path.extension()
+                .map(|e| e.eq_ignore_ascii_case("png"))
+                .unwrap_or(false)
+        })
+        .collect();
+    paths.sort();
+    paths
+}
 
-    chara_3_custom.save("chara_3_custom.png").unwrap();
-    chara_4_custom.save("chara_4_custom.png").unwrap();
-    chara_6_custom.save("chara_6_custom.png").unwrap();
+enum SkinCheck {
+    Valid,
+    Legacy,
+    Slim,
+    Invalid(String),
+}
 
-    output.save("output.png").unwrap();
+impl std::fmt::Display for SkinCheck {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SkinCheck::Valid => write!(f, "valid"),
+            SkinCheck::Legacy => write!(f, "legacy (render with --legacy)"),
+            SkinCheck::Slim => write!(f, "slim"),
+            SkinCheck::Invalid(reason) => write!(f, "invalid ({})", reason),
+        }
+    }
+}
+
+fn check_skin_texture(skin_texture: &RgbaImage) -> SkinCheck {
+    // HD skins use multiples of the 64x64 or legacy 64x32 layout.
+    let (width, height) = skin_texture.dimensions();
+    if width % 64 != 0 || (height != width && height * 2 != width) {
+        return SkinCheck::Invalid(format!(
+            "{}x{} is not a 64x64 or 64x32 skin layout",
+            width, height
+        ));
+    }
+    if let Err(e) = minecraft_render::validate_skin(skin_texture) {
+        return SkinCheck::Invalid(e.to_string());
+    }
+
+    if height * 2 == width {
+        SkinCheck::Legacy
+    } else if minecraft_render::detect_model(skin_texture) == ModelType::Slim {
+        SkinCheck::Slim
+    } else {
+        SkinCheck::Valid
+    }
+}
+
+fn check_skin(name: &str, skin_texture: Result<RgbaImage, RenderError>) -> bool {
+    // Report every file instead of stopping at the first invalid skin.
+    let check = match skin_texture {
+        Ok(skin_texture) => check_skin_texture(&skin_texture),
+        Err(e) => SkinCheck::Invalid(e.to_string()),
+    };
+    println!("{}: {}", name, check);
+    !matches!(check, SkinCheck::Invalid(_))
+}
+
+fn check_batch(input_dir: &Path, quiet: bool) -> bool {
+    let paths = skin_paths(input_dir);
+    let valid_count = paths
+        .iter()
+        .filter(|path| {
+            check_skin(
+                &path.display().to_string(),
+                minecraft_render::load_skin(path),
+            )
+        })
+        .count();
+    if !quiet {
+        eprintln!("{} of {} skins can be rendered", valid_count, paths.len());
+    }
+    valid_count == paths.len()
+}
+
+fn read_skin_texture(matches: &clap::ArgMatches) -> Result<RgbaImage, RenderError> {
+    if matches.is_present("stdin") {
+        let mut bytes = Vec::new();
+        std::io::stdin().lock().read_to_end(&mut bytes).unwrap();
+        return Ok(image::load_from_memory(&bytes)?.into_rgba8());
+    }
+
+    minecraft_render::load_skin(matches.value_of("skin").unwrap())
+}
+
+fn load_skin_texture(
+    matches: &clap::ArgMatches,
+) -> Result<(RgbaImage, Option<ModelType>), Box<dyn std::error::Error>> {
+    // Prefer the model from the player's profile over detecting the model.
+    #[cfg(feature = "online")]
+    if let Some(username) = matches.value_of("username") {
+        use minecraft_render::online::{SkinCache, DEFAULT_CACHE_TTL};
+
+        let cache_dir = (!matches.is_present("no_cache")).then(|| {
+            matches
+                .value_of("cache_dir")
+                .map(PathBuf::from)
+                .unwrap_or_else(|| std::env::temp_dir().join("minecraft_render_skins"))
+        });
+        let (skin_texture, model) =
+            SkinCache::new(cache_dir, DEFAULT_CACHE_TTL).get_or_fetch(username)?;
+        return Ok((skin_texture, Some(model)));
+    }
+
+    #[cfg(feature = "pack")]
+    if let Some(pack) = matches.value_of("pack") {
+        let entry = matches
+            .value_of("entry")
+            .expect("--pack requires --entry or --list");
+        let pack = std::fs::File::open(pack)?;
+        return Ok((minecraft_render::pack::read_skin(pack, entry)?, None));
+    }
+
+    Ok((read_skin_texture(matches)?, None))
+}
+
+fn parse_hex_color(hex: &str) -> Rgba<u8> {
+    let hex = hex.trim_start_matches('#');
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).unwrap();
+    match hex.len() {
+        6 => Rgba([channel(0), channel(2), channel(4), 255u8]),
+        8 => Rgba([channel(0), channel(2), channel(4), channel(6)]),
+        _ => panic!("Expected a hex color like ffffff or ffffffff"),
+    }
 }
@@ -51,7 +51,7 @@ fn copy_rotated_right_flipped(
     );
     image.copy_within(
         image::math::Rect {
-            x: (width - shift),
+            x: x + width - shift,
             y,
             width: shift,
             height,
@@ -63,64 +63,282 @@ fn copy_rotated_right_flipped(
     image::imageops::flip_horizontal_in_place(&mut image.sub_image(to_x, to_y, width, height));
 }
 
+/// Settings for how [convert_to_modern_skin_with_options] fills the new regions of the skin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConvertOptions {
+    /// Mirror the right arm and leg to create the left arm and leg like Minecraft.
+    /// Otherwise, the limbs are copied without flipping to preserve asymmetric shading.
+    pub mirror_limbs: bool,
+    /// Clear the second layer regions for the body, arms, and legs to transparent.
+    /// This only affects skins that already have pixels in the bottom half like a legacy skin on a 64x64 canvas.
+    pub clear_overlays: bool,
+}
+
+impl Default for ConvertOptions {
+    fn default() -> Self {
+        Self {
+            mirror_limbs: true,
+            clear_overlays: false,
+        }
+    }
+}
+
+/// A region of a legacy 64x32 skin copied to the bottom half of the modern 64x64 layout.
+/// Positions and sizes are in pixels for a 64 pixel wide skin and are multiplied for higher resolution skins.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegionMapping {
+    /// The top left corner of the region in the legacy skin.
+    pub from: (u32, u32),
+    /// The width and height of the region.
+    pub size: (u32, u32),
+    /// The top left corner of the copied region in the modern skin.
+    pub to: (u32, u32),
+    /// Flip the copied region horizontally.
+    pub flip: bool,
+    /// The number of columns to shift the copied region right with wrapping before flipping.
+    pub shift: u32,
+}
+
+const fn mirrored(from: (u32, u32), size: (u32, u32), to: (u32, u32), shift: u32) -> RegionMapping {
+    RegionMapping {
+        from,
+        size,
+        to,
+        flip: true,
+        shift,
+    }
+}
+
+/// The regions copied by [convert_to_modern_skin] to mirror the right leg and arm like Minecraft.
+///
+/// The top and bottom faces are flipped in place.
+/// The four side faces are shifted by one face before flipping,
+/// so the outer side of the right limb becomes the outer side of the left limb.
+pub const MIRRORED_REGIONS: [RegionMapping; 6] = [
+    // The top and bottom of the leg.
+    mirrored((4, 16), (4, 4), (20, 48), 0),
+    mirrored((8, 16), (4, 4), (24, 48), 0),
+    // The top and bottom of the arm.
+    mirrored((44, 16), (4, 4), (36, 48), 0),
+    mirrored((48, 16), (4, 4), (40, 48), 0),
+    // The sides of the leg and arm.
+    mirrored((0, 20), (16, 12), (16, 52), 4),
+    mirrored((40, 20), (16, 12), (32, 52), 4),
+];
+
+/// The regions copied when [ConvertOptions::mirror_limbs] is disabled.
+/// The right leg and arm are copied to the left leg and arm without changing the layout of the faces.
+pub const COPIED_REGIONS: [RegionMapping; 2] = [
+    RegionMapping {
+        from: (0, 16),
+        size: (16, 16),
+        to: (16, 48),
+        flip: false,
+        shift: 0,
+    },
+    RegionMapping {
+        from: (40, 16),
+        size: (16, 16),
+        to: (32, 48),
+        flip: false,
+        shift: 0,
+    },
+];
+
+/// The regions copied to the bottom half of the skin by [convert_to_modern_skin_with_options].
+/// The top half of the legacy skin is always preserved unchanged.
+pub fn region_mappings(options: &ConvertOptions) -> &'static [RegionMapping] {
+    if options.mirror_limbs {
+        &MIRRORED_REGIONS
+    } else {
+        &COPIED_REGIONS
+    }
+}
+
+/// Converts a legacy 64x32 skin to the modern 64x64 layout by mirroring the right arm and leg.
 pub fn convert_to_modern_skin(skin_data: &image::RgbaImage) -> image::RgbaImage {
+    convert_to_modern_skin_with_options(skin_data, &ConvertOptions::default())
+}
+
+/// Converts a legacy 64x32 skin to the modern 64x64 layout using the settings in `options`.
+pub fn convert_to_modern_skin_with_options(
+    skin_data: &image::RgbaImage,
+    options: &ConvertOptions,
+) -> image::RgbaImage {
     let scale = skin_data.width() / 64;
 
     let mut new_skin = image::RgbaImage::new(64 * scale, 64 * scale);
 
     new_skin.copy_from(skin_data, 0, 0).unwrap();
 
-    let arm_size: (u32, u32) = (4 * scale, 4 * scale);
+    if options.clear_overlays {
+        let overlay_regions = [(0, 32, 64, 16), (0, 48, 16, 16), (48, 48, 16, 16)];
+        for (x, y, width, height) in overlay_regions.iter() {
+            for pixel_x in x * scale..(x + width) * scale {
+                for pixel_y in y * scale..(y + height) * scale {
+                    new_skin.put_pixel(pixel_x, pixel_y, image::Rgba([0u8, 0u8, 0u8, 0u8]));
+                }
+            }
+        }
+    }
 
-    // copy and flip the top of leg
-    copy_flipped(
-        &mut new_skin,
-        (4 * scale, 16 * scale),
-        arm_size,
-        (20 * scale, 48 * scale),
-    );
+    for mapping in region_mappings(options) {
+        copy_region(&mut new_skin, mapping, scale);
+    }
 
-    // copy and flip the bottom of leg
-    copy_flipped(
-        &mut new_skin,
-        (8 * scale, 16 * scale),
-        arm_size,
-        (24 * scale, 48 * scale),
-    );
+    new_skin
+}
 
-    // copy and flip the top of arm
-    copy_flipped(
-        &mut new_skin,
-        (44 * scale, 16 * scale),
-        arm_size,
-        (36 * scale, 48 * scale),
-    );
+fn copy_region(image: &mut image::RgbaImage, mapping: &RegionMapping, scale: u32) {
+    let (x, y) = mapping.from;
+    let (width, height) = mapping.size;
+    let (to_x, to_y) = mapping.to;
+    let from_pos = (x * scale, y * scale);
+    let size = (width * scale, height * scale);
+    let to_pos = (to_x * scale, to_y * scale);
+    if !mapping.flip {
+        image.copy_within(
+            image::math::Rect {
+                x: from_pos.0,
+                y: from_pos.1,
+                width: size.0,
+                height: size.1,
+            },
+            to_pos.0,
+            to_pos.1,
+        );
+    } else if mapping.shift == 0 {
+        copy_flipped(image, from_pos, size, to_pos);
+    } else {
+        copy_rotated_right_flipped(image, from_pos, size, to_pos, mapping.shift * scale);
+    }
+}
 
-    // copy and flip the bottom of arm
-    copy_flipped(
-        &mut new_skin,
-        (48 * scale, 16 * scale),
-        arm_size,
-        (40 * scale, 48 * scale),
-    );
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    // copy the leg sides
-    copy_rotated_right_flipped(
-        &mut new_skin,
-        (0, 20 * scale),
-        (16 * scale, 12 * scale),
-        (16 * scale, 52 * scale),
-        4 * scale,
-    );
+    #[test]
+    fn test_convert_mirror_vs_copy() {
+        // Mark the front face of the right arm.
+        let red = image::Rgba([255u8, 0u8, 0u8, 255u8]);
+        let mut skin = image::RgbaImage::new(64, 32);
+        skin.put_pixel(44, 20, red);
 
-    // copy the arm sides
-    copy_rotated_right_flipped(
-        &mut new_skin,
-        (40 * scale, 20 * scale),
-        (16 * scale, 12 * scale),
-        (32 * scale, 52 * scale),
-        4 * scale,
-    );
+        // The front face of the left arm is at x=36..40.
+        let mirrored = convert_to_modern_skin(&skin);
+        assert_eq!(mirrored.dimensions(), (64, 64));
+        assert_eq!(mirrored.get_pixel(39, 52), &red);
+        assert_ne!(mirrored.get_pixel(36, 52), &red);
 
-    new_skin
+        let copied = convert_to_modern_skin_with_options(
+            &skin,
+            &ConvertOptions {
+                mirror_limbs: false,
+                ..Default::default()
+            },
+        );
+        assert_eq!(copied.get_pixel(36, 52), &red);
+        assert_ne!(copied.get_pixel(39, 52), &red);
+    }
+
+    #[test]
+    fn test_convert_region_destinations() {
+        // Each legacy pixel and the pixel of the modern skin it should be mirrored to.
+        let markers = [
+            // The top and bottom of the leg.
+            ((4, 16), (23, 48)),
+            ((8, 16), (27, 48)),
+            // The top and bottom of the arm.
+            ((44, 16), (39, 48)),
+            ((48, 16), (43, 48)),
+            // The outer and back sides of the leg.
+            ((0, 20), (27, 52)),
+            ((13, 31), (30, 63)),
+            // The front and inner sides of the arm.
+            ((44, 20), (39, 52)),
+            ((50, 25), (33, 57)),
+        ];
+        let mut skin = image::RgbaImage::new(64, 32);
+        for (i, ((x, y), _)) in markers.iter().enumerate() {
+            skin.put_pixel(*x, *y, image::Rgba([i as u8 + 1, 0u8, 0u8, 255u8]));
+        }
+        for ((x, y), _) in markers.iter() {
+            assert!(MIRRORED_REGIONS
+                .iter()
+                .any(|m| (m.from.0..m.from.0 + m.size.0).contains(x)
+                    && (m.from.1..m.from.1 + m.size.1).contains(y)));
+        }
+
+        let converted = convert_to_modern_skin(&skin);
+        for (i, (_, (x, y))) in markers.iter().enumerate() {
+            assert_eq!(
+                converted.get_pixel(*x, *y),
+                &image::Rgba([i as u8 + 1, 0u8, 0u8, 255u8]),
+                "{:?}",
+                markers[i]
+            );
+        }
+        // Only the marked pixels are copied.
+        let bottom_pixels = converted
+            .enumerate_pixels()
+            .filter(|(_, y, p)| *y >= 32 && p[3] > 0u8)
+            .count();
+        assert_eq!(bottom_pixels, markers.len());
+
+        // Higher resolution skins scale the regions.
+        let hd_skin = image::imageops::resize(&skin, 128, 64, image::imageops::FilterType::Nearest);
+        let hd_converted = convert_to_modern_skin(&hd_skin);
+        assert_eq!(
+            hd_converted,
+            image::imageops::resize(&converted, 128, 128, image::imageops::FilterType::Nearest)
+        );
+    }
+
+    #[test]
+    fn test_convert_preserves_top_half() {
+        let skin = image::RgbaImage::from_fn(64, 32, |x, y| {
+            image::Rgba([x as u8 * 4, y as u8 * 8, 128u8, 255u8])
+        });
+        for options in [
+            ConvertOptions::default(),
+            ConvertOptions {
+                mirror_limbs: false,
+                clear_overlays: true,
+            },
+        ] {
+            let converted = convert_to_modern_skin_with_options(&skin, &options);
+            assert_eq!(
+                image::imageops::crop_imm(&converted, 0, 0, 64, 32).to_image(),
+                skin
+            );
+            // The copies cover the left leg and arm without gaps.
+            let covered: u32 = region_mappings(&options)
+                .iter()
+                .map(|m| m.size.0 * m.size.1)
+                .sum();
+            let opaque = converted
+                .enumerate_pixels()
+                .filter(|(_, y, p)| *y >= 32 && p[3] > 0u8)
+                .count() as u32;
+            assert_eq!(opaque, covered);
+        }
+    }
+
+    #[test]
+    fn test_convert_clear_overlays() {
+        let skin = image::RgbaImage::from_pixel(64, 64, image::Rgba([255u8, 255u8, 255u8, 255u8]));
+        let converted = convert_to_modern_skin_with_options(
+            &skin,
+            &ConvertOptions {
+                clear_overlays: true,
+                ..Default::default()
+            },
+        );
+        // The jacket and left sleeve are cleared, but the base layers are kept.
+        assert_eq!(converted.get_pixel(20, 36)[3], 0u8);
+        assert_eq!(converted.get_pixel(52, 52)[3], 0u8);
+        assert_eq!(converted.get_pixel(20, 20)[3], 255u8);
+        assert_eq!(converted.get_pixel(36, 52)[3], 255u8);
+    }
 }
@@ -0,0 +1,491 @@
+//! Download skin textures from the Mojang API.
+//! This requires the `online` feature.
+use image::RgbaImage;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use crate::ModelType;
+
+const PROFILE_URL: &str = "https://api.mojang.com/users/profiles/minecraft/";
+const SESSION_URL: &str = "https://sessionserver.mojang.com/session/minecraft/profile/";
+
+/// Errors while downloading a skin texture.
+#[derive(Debug)]
+pub enum FetchError {
+    /// The request failed or returned an unexpected status code.
+    Http(reqwest::Error),
+    /// No player exists with the given username.
+    UserNotFound(String),
+    /// The profile response did not have the expected format.
+    InvalidProfile(String),
+    /// The downloaded skin texture could not be decoded.
+    Image(image::ImageError),
+    /// The skin could not be saved to the cache directory.
+    Cache(std::io::Error),
+}
+
+impl fmt::Display for FetchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FetchError::Http(e) => write!(f, "request failed: {}", e),
+            FetchError::UserNotFound(name) => write!(f, "no player with username {:?}", name),
+            FetchError::InvalidProfile(reason) => write!(f, "invalid profile: {}", reason),
+            FetchError::Image(e) => write!(f, "invalid skin texture: {}", e),
+            FetchError::Cache(e) => write!(f, "failed to cache skin: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for FetchError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FetchError::Http(e) => Some(e),
+            FetchError::Image(e) => Some(e),
+            FetchError::Cache(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for FetchError {
+    fn from(e: reqwest::Error) -> Self {
+        FetchError::Http(e)
+    }
+}
+
+impl From<image::ImageError> for FetchError {
+    fn from(e: image::ImageError) -> Self {
+        FetchError::Image(e)
+    }
+}
+
+/// Downloads the skin texture for the player with the given username.
+/// Players without a custom skin use the default Steve skin.
+/// Legacy 64x32 skins are converted to the modern 64x64 layout.
+pub fn fetch_skin_by_username(name: &str) -> Result<RgbaImage, FetchError> {
+    fetch_skin_and_model_by_username(name).map(|(skin, _)| skin)
+}
+
+/// Downloads the skin texture and the model selected in the player's profile.
+/// This is more reliable than [crate::detect_model] for skins that use the classic layout.
+pub fn fetch_skin_and_model_by_username(name: &str) -> Result<(RgbaImage, ModelType), FetchError> {
+    let uuid = fetch_uuid(name)?;
+    fetch_skin_and_model_by_uuid(&uuid)
+}
+
+fn fetch_skin_and_model_by_uuid(uuid: &str) -> Result<(RgbaImage, ModelType), FetchError> {
+    let profile: serde_json::Value = reqwest::blocking::get(format!("{}{}", SESSION_URL, uuid))?
+        .error_for_status()?
+        .json()?;
+
+    match skin_url(&profile)? {
+        Some(url) => {
+            let bytes = reqwest::blocking::get(url.as_str())?
+                .error_for_status()?
+                .bytes()?;
+            let skin = image::load_from_memory(&bytes)?.into_rgba8();
+            let model = skin_model(&profile)?;
+            if skin.width() == skin.height() * 2 {
+                Ok((crate::modern_skin::convert_to_modern_skin(&skin), model))
+            } else {
+                Ok((skin, model))
+            }
+        }
+        None => {
+            let model = default_model(uuid);
+            Ok((crate::fallback_skin(model), model))
+        }
+    }
+}
+
+// Players without a custom skin get Steve or Alex based on the parity of Java's UUID hash code.
+fn default_model(uuid: &str) -> ModelType {
+    let uuid = u128::from_str_radix(&uuid.replace('-', ""), 16).unwrap_or_default();
+    let bits = (uuid >> 64) as u64 ^ uuid as u64;
+    if ((bits >> 32) as u32 ^ bits as u32) & 1 == 1 {
+        ModelType::Slim
+    } else {
+        ModelType::Classic
+    }
+}
+
+fn fetch_uuid(name: &str) -> Result<String, FetchError> {
+    if !is_valid_username(name) {
+        return Err(FetchError::UserNotFound(name.to_string()));
+    }
+    let response = reqwest::blocking::get(format!("{}{}", PROFILE_URL, name))?;
+
+    // Unknown usernames return an empty response or not found depending on the endpoint version.
+    let status = response.status();
+    if status == reqwest::StatusCode::NO_CONTENT || status == reqwest::StatusCode::NOT_FOUND {
+        return Err(FetchError::UserNotFound(name.to_string()));
+    }
+
+    let profile: serde_json::Value = response.error_for_status()?.json()?;
+    profile["id"]
+        .as_str()
+        .map(|id| id.to_string())
+        .ok_or_else(|| FetchError::InvalidProfile("missing id".to_string()))
+}
+
+// Usernames are 1 to 16 ASCII letters, digits, or underscores.
+// Checking the name first keeps it from changing the request URL or cache paths.
+fn is_valid_username(name: &str) -> bool {
+    (1..=16).contains(&name.len()) && name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_')
+}
+
+/// A way to look up players and download their skins for [SkinCache].
+pub trait SkinSource {
+    /// Looks up the UUID for the player with the given username.
+    fn fetch_uuid(&self, name: &str) -> Result<String, FetchError>;
+    /// Downloads the skin texture and model for the player with the given UUID.
+    fn fetch_skin(&self, uuid: &str) -> Result<(RgbaImage, ModelType), FetchError>;
+}
+
+/// Downloads skins from the Mojang API like [fetch_skin_and_model_by_username].
+#[derive(Debug, Default)]
+pub struct MojangApi;
+
+impl SkinSource for MojangApi {
+    fn fetch_uuid(&self, name: &str) -> Result<String, FetchError> {
+        fetch_uuid(name)
+    }
+
+    fn fetch_skin(&self, uuid: &str) -> Result<(RgbaImage, ModelType), FetchError> {
+        fetch_skin_and_model_by_uuid(uuid)
+    }
+}
+
+/// How long [SkinCache] uses skins saved to disk before downloading them again.
+pub const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(60 * 60);
+
+// Each username has its own lock, so concurrent lookups for the same player wait for a single fetch.
+type SkinEntry = Arc<Mutex<Option<(RgbaImage, ModelType)>>>;
+
+/// Caches downloaded skins in memory and optionally on disk to avoid downloading the same skin again.
+/// The cache can be shared between threads, and concurrent lookups for the same player only fetch the skin once.
+pub struct SkinCache<S = MojangApi> {
+    source: S,
+    dir: Option<PathBuf>,
+    ttl: Duration,
+    skins: Mutex<HashMap<String, SkinEntry>>,
+}
+
+impl SkinCache<MojangApi> {
+    /// Creates a cache that downloads skins from the Mojang API.
+    /// Skins are saved to `dir` by UUID and reused until they are older than `ttl`.
+    /// `None` only caches skins in memory.
+    pub fn new(dir: Option<PathBuf>, ttl: Duration) -> Self {
+        Self::with_source(MojangApi, dir, ttl)
+    }
+}
+
+impl<S: SkinSource> SkinCache<S> {
+    /// Creates a cache like [SkinCache::new] that downloads skins from `source`.
+    pub fn with_source(source: S, dir: Option<PathBuf>, ttl: Duration) -> Self {
+        Self {
+            source,
+            dir,
+            ttl,
+            skins: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the skin texture and model for the player with the given username.
+    /// Usernames are case insensitive, so lookups for "notch" and "Notch" share the same entry.
+    /// Names that aren't valid usernames return [FetchError::UserNotFound] without a lookup.
+    /// Failed fetches aren't cached, so the next lookup tries again.
+    pub fn get_or_fetch(&self, name: &str) -> Result<(RgbaImage, ModelType), FetchError> {
+        if !is_valid_username(name) {
+            return Err(FetchError::UserNotFound(name.to_string()));
+        }
+        let name = name.to_lowercase();
+        let entry = self
+            .skins
+            .lock()
+            .unwrap()
+            .entry(name.clone())
+            .or_default()
+            .clone();
+
+        // Only hold the lock for the map long enough to find the entry for this player.
+        let mut skin = entry.lock().unwrap();
+        if let Some(skin) = skin.as_ref() {
+            return Ok(skin.clone());
+        }
+        let fetched = match &self.dir {
+            Some(dir) => self.fetch_cached(dir, &name)?,
+            None => self.source.fetch_skin(&self.source.fetch_uuid(&name)?)?,
+        };
+        *skin = Some(fetched.clone());
+        Ok(fetched)
+    }
+
+    fn fetch_cached(&self, dir: &Path, name: &str) -> Result<(RgbaImage, ModelType), FetchError> {
+        // Usernames can change owners, so the name lookup expires like the skins.
+        let uuid_path = dir.join("names").join(name);
+        let uuid = match self.read_fresh(&uuid_path, |path| std::fs::read_to_string(path).ok()) {
+            Some(uuid) => uuid,
+            None => {
+                let uuid = self.source.fetch_uuid(name)?;
+                write_cache_file(&uuid_path, uuid.as_bytes())?;
+                uuid
+            }
+        };
+
+        // Unreadable cache files are downloaded again instead of failing the lookup.
+        let skin_path = dir.join(format!("{}.png", uuid));
+        let model_path = dir.join(format!("{}.model", uuid));
+        let cached = self
+            .read_fresh(&skin_path, |path| image::open(path).ok())
+            .zip(self.read_fresh(&model_path, |path| {
+                match std::fs::read_to_string(path).ok()?.as_str() {
+                    "slim" => Some(ModelType::Slim),
+                    "classic" => Some(ModelType::Classic),
+                    _ => None,
+                }
+            }));
+        match cached {
+            Some((skin, model)) => Ok((skin.into_rgba8(), model)),
+            None => {
+                let (skin, model) = self.source.fetch_skin(&uuid)?;
+                std::fs::create_dir_all(dir).map_err(FetchError::Cache)?;
+                skin.save(&skin_path)?;
+                let model_name = match model {
+                    ModelType::Slim => "slim",
+                    ModelType::Classic => "classic",
+                };
+                write_cache_file(&model_path, model_name.as_bytes())?;
+                Ok((skin, model))
+            }
+        }
+    }
+
+    fn read_fresh<T>(&self, path: &Path, read: impl Fn(&Path) -> Option<T>) -> Option<T> {
+        let age = std::fs::metadata(path)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok())?;
+        (age < self.ttl).then(|| read(path)).flatten()
+    }
+}
+
+fn write_cache_file(path: &Path, contents: &[u8]) -> Result<(), FetchError> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(FetchError::Cache)?;
+    }
+    std::fs::write(path, contents).map_err(FetchError::Cache)
+}
+
+fn skin_url(profile: &serde_json::Value) -> Result<Option<String>, FetchError> {
+    Ok(textures(profile)?["textures"]["SKIN"]["url"]
+        .as_str()
+        .map(|url| url.to_string()))
+}
+
+fn skin_model(profile: &serde_json::Value) -> Result<ModelType, FetchError> {
+    // The model is only included for slim skins.
+    match textures(profile)?["textures"]["SKIN"]["metadata"]["model"].as_str() {
+        Some("slim") => Ok(ModelType::Slim),
+        _ => Ok(ModelType::Classic),
+    }
+}
+
+fn textures(profile: &serde_json::Value) -> Result<serde_json::Value, FetchError> {
+    // The textures are stored as a base64 encoded JSON property.
+    let encoded = profile["properties"]
+        .as_array()
+        .and_then(|properties| {
+            properties
+                .iter()
+                .find(|p| p["name"].as_str() == Some("textures"))
+        })
+        .and_then(|p| p["value"].as_str())
+        .ok_or_else(|| FetchError::InvalidProfile("missing textures property".to_string()))?;
+
+    let decoded = base64::decode(encoded)
+        .map_err(|e| FetchError::InvalidProfile(format!("invalid textures property: {}", e)))?;
+    serde_json::from_slice(&decoded)
+        .map_err(|e| FetchError::InvalidProfile(format!("invalid textures property: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(textures: &str) -> serde_json::Value {
+        serde_json::json!({
+            "id": "069a79f444e94726a5befca90e38aaf5",
+            "name": "Notch",
+            "properties": [{ "name": "textures", "value": base64::encode(textures) }]
+        })
+    }
+
+    #[test]
+    fn test_skin_url() {
+        let profile =
+            profile(r#"{"textures":{"SKIN":{"url":"http://textures.minecraft.net/texture/abc"}}}"#);
+        assert_eq!(
+            skin_url(&profile).unwrap(),
+            Some("http://textures.minecraft.net/texture/abc".to_string())
+        );
+    }
+
+    #[test]
+    fn test_skin_url_no_custom_skin() {
+        let profile = profile(r#"{"textures":{}}"#);
+        assert_eq!(skin_url(&profile).unwrap(), None);
+    }
+
+    #[test]
+    fn test_skin_model() {
+        let slim_profile = profile(
+            r#"{"textures":{"SKIN":{"url":"http://textures.minecraft.net/texture/abc","metadata":{"model":"slim"}}}}"#,
+        );
+        assert_eq!(skin_model(&slim_profile).unwrap(), ModelType::Slim);
+
+        let classic_profile =
+            profile(r#"{"textures":{"SKIN":{"url":"http://textures.minecraft.net/texture/abc"}}}"#);
+        assert_eq!(skin_model(&classic_profile).unwrap(), ModelType::Classic);
+    }
+
+    #[test]
+    fn test_default_model() {
+        assert_eq!(
+            default_model("00000000000000000000000000000000"),
+            ModelType::Classic
+        );
+        assert_eq!(
+            default_model("00000000-0000-0000-0000-000000000001"),
+            ModelType::Slim
+        );
+        // The hash code combines the high and low bits of both halves.
+        assert_eq!(
+            default_model("00000001000000000000000000000001"),
+            ModelType::Classic
+        );
+        assert_eq!(
+            default_model("00000001000000000000000000000000"),
+            ModelType::Slim
+        );
+    }
+
+    struct CountingSource {
+        uuid_fetches: std::sync::atomic::AtomicUsize,
+        skin_fetches: std::sync::atomic::AtomicUsize,
+    }
+
+    impl SkinSource for CountingSource {
+        fn fetch_uuid(&self, name: &str) -> Result<String, FetchError> {
+            self.uuid_fetches
+                .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            match name {
+                "notch" => Ok("069a79f444e94726a5befca90e38aaf5".to_string()),
+                _ => Err(FetchError::UserNotFound(name.to_string())),
+            }
+        }
+
+        fn fetch_skin(&self, _uuid: &str) -> Result<(RgbaImage, ModelType), FetchError> {
+            self.skin_fetches
+                .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            // Give the other lookups time to start while the skin is downloading.
+            std::thread::sleep(Duration::from_millis(50));
+            Ok((crate::fallback_skin(ModelType::Classic), ModelType::Slim))
+        }
+    }
+
+    #[test]
+    fn test_skin_cache_concurrent_lookups() {
+        // Use a different directory for each process, so concurrent test runs don't share files.
+        let dir = std::env::temp_dir().join(format!(
+            "minecraft_render_test_skin_cache_{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        let source = || CountingSource {
+            uuid_fetches: Default::default(),
+            skin_fetches: Default::default(),
+        };
+
+        let cache = SkinCache::with_source(source(), Some(dir.clone()), DEFAULT_CACHE_TTL);
+        let shared_cache = &cache;
+        std::thread::scope(|s| {
+            let lookups: Vec<_> = ["Notch", "notch", "NOTCH"]
+                .iter()
+                .map(|name| s.spawn(move || shared_cache.get_or_fetch(name).unwrap()))
+                .collect();
+            for lookup in lookups {
+                assert_eq!(
+                    lookup.join().unwrap(),
+                    (crate::fallback_skin(ModelType::Classic), ModelType::Slim)
+                );
+            }
+        });
+        assert_eq!(cache.source.uuid_fetches.into_inner(), 1);
+        assert_eq!(cache.source.skin_fetches.into_inner(), 1);
+
+        // A new cache reuses the skins saved to disk.
+        let cache = SkinCache::with_source(source(), Some(dir.clone()), DEFAULT_CACHE_TTL);
+        assert_eq!(
+            cache.get_or_fetch("Notch").unwrap(),
+            (crate::fallback_skin(ModelType::Classic), ModelType::Slim)
+        );
+        assert!(matches!(
+            cache.get_or_fetch("missing"),
+            Err(FetchError::UserNotFound(_))
+        ));
+        assert_eq!(cache.source.skin_fetches.into_inner(), 0);
+
+        // Expired skins are downloaded again.
+        let cache = SkinCache::with_source(source(), Some(dir.clone()), Duration::ZERO);
+        cache.get_or_fetch("Notch").unwrap();
+        assert_eq!(cache.source.skin_fetches.into_inner(), 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_skin_cache_invalid_usernames() {
+        let dir = std::env::temp_dir().join(format!(
+            "minecraft_render_test_skin_cache_invalid_{}",
+            std::process::id()
+        ));
+        let source = CountingSource {
+            uuid_fetches: Default::default(),
+            skin_fetches: Default::default(),
+        };
+        let cache = SkinCache::with_source(source, Some(dir.clone()), DEFAULT_CACHE_TTL);
+        for name in [
+            "",
+            "../notch",
+            "notch/..",
+            "no tch",
+            "n\u{f6}tch",
+            "abcdefghijklmnopq",
+        ] {
+            assert!(
+                matches!(cache.get_or_fetch(name), Err(FetchError::UserNotFound(_))),
+                "{:?}",
+                name
+            );
+        }
+        assert_eq!(cache.source.uuid_fetches.into_inner(), 0);
+        assert!(!dir.exists());
+
+        assert!(is_valid_username("Notch_1234567890"));
+        assert!(matches!(
+            fetch_uuid("?name=notch"),
+            Err(FetchError::UserNotFound(_))
+        ));
+    }
+
+    #[test]
+    #[ignore]
+    fn test_fetch_skin_by_username() {
+        let skin = fetch_skin_by_username("Notch").unwrap();
+        assert_eq!(skin.width(), 64);
+    }
+}
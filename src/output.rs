@@ -0,0 +1,438 @@
+//! Encoding renders to image files.
+use image::{ImageOutputFormat, ImageResult, Rgb, RgbImage, Rgba, RgbaImage};
+use std::io::Write;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::compositing::alpha_blend;
+use crate::sampling::{normalize_rgba_u8, to_u8_clamped};
+use crate::DEFAULT_GAMMA;
+
+/// The file format for saving renders.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Png,
+    /// Lossy without alpha, so the image is flattened onto a background color.
+    Jpeg,
+    /// Lossy with alpha. This requires the `webp` feature.
+    #[cfg(feature = "webp")]
+    WebP,
+    /// Linear 32 bit float color with premultiplied alpha. This requires the `exr` feature.
+    #[cfg(feature = "exr")]
+    Exr,
+}
+
+impl OutputFormat {
+    /// The file extension for this format without the leading `.`.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Png => "png",
+            OutputFormat::Jpeg => "jpg",
+            #[cfg(feature = "webp")]
+            OutputFormat::WebP => "webp",
+            #[cfg(feature = "exr")]
+            OutputFormat::Exr => "exr",
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "png" => Ok(OutputFormat::Png),
+            "jpg" | "jpeg" => Ok(OutputFormat::Jpeg),
+            #[cfg(feature = "webp")]
+            "webp" => Ok(OutputFormat::WebP),
+            #[cfg(feature = "exr")]
+            "exr" => Ok(OutputFormat::Exr),
+            _ => Err(format!("unsupported output format {:?}", s)),
+        }
+    }
+}
+
+/// The color space information embedded in PNG files, so color managed programs display the colors correctly.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ColorProfile {
+    /// Don't embed any color space information.
+    None,
+    /// Embed an sRGB chunk. Renders use sRGB colors unless the blending gamma is changed.
+    Srgb,
+    /// Embed the bytes of an ICC profile file in an iCCP chunk.
+    Icc(Vec<u8>),
+}
+
+/// Settings for encoding renders with [encode_image] or [save_image].
+#[derive(Debug, Clone)]
+pub struct OutputOptions {
+    pub format: OutputFormat,
+    /// The quality from 0 to 100 for lossy formats.
+    pub quality: u8,
+    /// The color behind transparent pixels for formats without alpha.
+    pub background: Rgba<u8>,
+    /// Swap the red and blue channels with [swizzle_to_bgra] for engines that expect BGRA textures.
+    pub bgra: bool,
+    /// The color space information for PNG files. Other formats ignore this setting.
+    pub color_profile: ColorProfile,
+}
+
+impl Default for OutputOptions {
+    fn default() -> Self {
+        Self {
+            format: OutputFormat::Png,
+            quality: 90,
+            background: Rgba([255u8, 255u8, 255u8, 255u8]),
+            bgra: false,
+            color_profile: ColorProfile::Srgb,
+        }
+    }
+}
+
+/// Encodes `image` to the file format in `options`.
+pub fn encode_image(image: &RgbaImage, options: &OutputOptions) -> ImageResult<Vec<u8>> {
+    let mut bytes = Vec::new();
+    write_image(image, &mut bytes, options)?;
+    Ok(bytes)
+}
+
+/// Returns the size in bytes of `image` encoded to `format` with `quality` without writing a file.
+/// This is useful for choosing a format and quality that fits a size budget.
+pub fn estimate_encoded_size(
+    image: &RgbaImage,
+    format: OutputFormat,
+    quality: u8,
+) -> ImageResult<usize> {
+    let options = OutputOptions {
+        format,
+        quality,
+        ..Default::default()
+    };
+    encode_image(image, &options).map(|bytes| bytes.len())
+}
+
+/// Encodes `image` to the file format in `options` and saves the result to `path`.
+pub fn save_image<P: AsRef<Path>>(
+    image: &RgbaImage,
+    path: P,
+    options: &OutputOptions,
+) -> ImageResult<()> {
+    let mut writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+    write_image(image, &mut writer, options)
+}
+
+/// Encodes a linear render like [crate::RenderContext::render_linear] as an OpenEXR file.
+/// Colors are stored as 32 bit floats without clamping and multiplied by alpha,
+/// since compositing programs expect premultiplied alpha for EXR files.
+/// This requires the `exr` feature.
+#[cfg(feature = "exr")]
+pub fn encode_exr(image: &image::ImageBuffer<Rgba<f32>, Vec<f32>>) -> ImageResult<Vec<u8>> {
+    use exr::prelude::{Image, SpecificChannels, Vec2, WritableImage};
+
+    let channels = SpecificChannels::rgba(|Vec2(x, y)| {
+        let pixel = image.get_pixel(x as u32, y as u32);
+        let a = pixel[3];
+        (pixel[0] * a, pixel[1] * a, pixel[2] * a, a)
+    });
+    let mut bytes = std::io::Cursor::new(Vec::new());
+    Image::from_channels((image.width() as usize, image.height() as usize), channels)
+        .write()
+        .to_buffered(&mut bytes)
+        .map_err(|e| {
+            image::ImageError::Encoding(image::error::EncodingError::new(
+                image::error::ImageFormatHint::Name("exr".to_string()),
+                e,
+            ))
+        })?;
+    Ok(bytes.into_inner())
+}
+
+/// Swaps the red and blue channels of `image` to store the pixels in BGRA order.
+/// The green and alpha channels are unchanged.
+/// Applying this twice restores the original RGBA image.
+pub fn swizzle_to_bgra(image: &mut RgbaImage) {
+    for pixel in image.pixels_mut() {
+        pixel.0.swap(0, 2);
+    }
+}
+
+fn write_image<W: Write>(
+    image: &RgbaImage,
+    writer: &mut W,
+    options: &OutputOptions,
+) -> ImageResult<()> {
+    if options.bgra {
+        // Swap the background as well, so flattened pixels match the rest of the image.
+        let mut image = image.clone();
+        swizzle_to_bgra(&mut image);
+        let mut background = options.background;
+        background.0.swap(0, 2);
+        let options = OutputOptions {
+            background,
+            bgra: false,
+            ..options.clone()
+        };
+        return write_image(&image, writer, &options);
+    }
+
+    match options.format {
+        OutputFormat::Png => write_png(image, writer, &options.color_profile),
+        OutputFormat::Jpeg => {
+            let flattened = flatten(image, &options.background);
+            image::DynamicImage::ImageRgb8(flattened)
+                .write_to(writer, ImageOutputFormat::Jpeg(options.quality))
+        }
+        #[cfg(feature = "webp")]
+        OutputFormat::WebP => {
+            let encoder = webp::Encoder::from_rgba(image.as_raw(), image.width(), image.height());
+            let bytes = encoder.encode(options.quality as f32);
+            writer.write_all(&bytes)?;
+            Ok(())
+        }
+        #[cfg(feature = "exr")]
+        OutputFormat::Exr => {
+            // Decode the 8 bit colors, so all EXR outputs store linear color.
+            let linear = image::ImageBuffer::from_fn(image.width(), image.height(), |x, y| {
+                let (r, g, b, a) = normalize_rgba_u8(image.get_pixel(x, y));
+                let decode = |c: f32| c.powf(DEFAULT_GAMMA);
+                Rgba([decode(r), decode(g), decode(b), a])
+            });
+            writer.write_all(&encode_exr(&linear)?)?;
+            Ok(())
+        }
+    }
+}
+
+fn write_png<W: Write>(
+    image: &RgbaImage,
+    writer: W,
+    color_profile: &ColorProfile,
+) -> ImageResult<()> {
+    // Use the same settings as the image crate, since its encoder can't write color chunks.
+    let mut encoder = png::Encoder::new(writer, image.width(), image.height());
+    encoder.set_color(png::ColorType::RGBA);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_compression(png::Compression::Fast);
+    encoder.set_filter(png::FilterType::Sub);
+    let mut writer = encoder
+        .write_header()
+        .map_err(|e| image::ImageError::IoError(e.into()))?;
+
+    // The color chunks must come before the image data.
+    match color_profile {
+        ColorProfile::None => (),
+        // Use the perceptual rendering intent.
+        ColorProfile::Srgb => writer
+            .write_chunk(*b"sRGB", &[0u8])
+            .map_err(|e| image::ImageError::IoError(e.into()))?,
+        ColorProfile::Icc(profile) => {
+            // The profile name, a null separator, zlib compression, and the compressed profile.
+            let mut data = b"ICC profile\0\0".to_vec();
+            data.extend(deflate::deflate_bytes_zlib(profile));
+            writer
+                .write_chunk(*b"iCCP", &data)
+                .map_err(|e| image::ImageError::IoError(e.into()))?
+        }
+    }
+
+    writer
+        .write_image_data(image.as_raw())
+        .map_err(|e| image::ImageError::IoError(e.into()))
+}
+
+fn flatten(image: &RgbaImage, background: &Rgba<u8>) -> RgbImage {
+    let (background_r, background_g, background_b, _) = normalize_rgba_u8(background);
+    RgbImage::from_fn(image.width(), image.height(), |x, y| {
+        let (r, g, b, a) = normalize_rgba_u8(image.get_pixel(x, y));
+        Rgb([
+            to_u8_clamped(alpha_blend(background_r, r, a, DEFAULT_GAMMA)),
+            to_u8_clamped(alpha_blend(background_g, g, a, DEFAULT_GAMMA)),
+            to_u8_clamped(alpha_blend(background_b, b, a, DEFAULT_GAMMA)),
+        ])
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryInto;
+
+    #[test]
+    fn test_flatten() {
+        let image = RgbaImage::from_fn(2, 1, |x, _| {
+            if x == 0 {
+                Rgba([255u8, 0u8, 0u8, 255u8])
+            } else {
+                Rgba([255u8, 0u8, 0u8, 0u8])
+            }
+        });
+        let flattened = flatten(&image, &Rgba([0u8, 0u8, 255u8, 255u8]));
+        assert_eq!(flattened.get_pixel(0, 0), &Rgb([255u8, 0u8, 0u8]));
+        assert_eq!(flattened.get_pixel(1, 0), &Rgb([0u8, 0u8, 255u8]));
+    }
+
+    #[test]
+    fn test_swizzle_to_bgra() {
+        let mut image = RgbaImage::from_pixel(2, 2, Rgba([10u8, 20u8, 30u8, 40u8]));
+        swizzle_to_bgra(&mut image);
+        assert!(image.pixels().all(|p| *p == Rgba([30u8, 20u8, 10u8, 40u8])));
+
+        let bytes = encode_image(
+            &RgbaImage::from_pixel(2, 2, Rgba([10u8, 20u8, 30u8, 40u8])),
+            &OutputOptions {
+                bgra: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(image::load_from_memory(&bytes).unwrap().into_rgba8(), image);
+    }
+
+    // The type and data of each chunk after the PNG signature.
+    fn png_chunks(bytes: &[u8]) -> Vec<([u8; 4], &[u8])> {
+        let mut chunks = Vec::new();
+        let mut offset = 8;
+        while offset < bytes.len() {
+            let len = u32::from_be_bytes(bytes[offset..offset + 4].try_into().unwrap()) as usize;
+            let name = bytes[offset + 4..offset + 8].try_into().unwrap();
+            chunks.push((name, &bytes[offset + 8..offset + 8 + len]));
+            // Skip the length, type, data, and CRC.
+            offset += 12 + len;
+        }
+        chunks
+    }
+
+    #[test]
+    fn test_png_color_profile() {
+        let image = RgbaImage::from_pixel(2, 2, Rgba([10u8, 20u8, 30u8, 40u8]));
+        let path = std::env::temp_dir().join("minecraft_render_test_srgb.png");
+        save_image(&image, &path, &OutputOptions::default()).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        // The sRGB chunk must come before the image data.
+        let names: Vec<_> = png_chunks(&bytes).into_iter().map(|(n, _)| n).collect();
+        assert_eq!(names, vec![*b"IHDR", *b"sRGB", *b"IDAT", *b"IEND"]);
+        assert_eq!(image::load_from_memory(&bytes).unwrap().into_rgba8(), image);
+
+        let profile = b"not a real profile".to_vec();
+        let bytes = encode_image(
+            &image,
+            &OutputOptions {
+                color_profile: ColorProfile::Icc(profile.clone()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let (_, data) = png_chunks(&bytes)
+            .into_iter()
+            .find(|(name, _)| name == b"iCCP")
+            .unwrap();
+        assert!(data.starts_with(b"ICC profile\0\0"));
+        assert_eq!(
+            miniz_oxide::inflate::decompress_to_vec_zlib(&data[13..]).unwrap(),
+            profile
+        );
+
+        let bytes = encode_image(
+            &image,
+            &OutputOptions {
+                color_profile: ColorProfile::None,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let names: Vec<_> = png_chunks(&bytes).into_iter().map(|(n, _)| n).collect();
+        assert_eq!(names, vec![*b"IHDR", *b"IDAT", *b"IEND"]);
+    }
+
+    #[cfg(feature = "exr")]
+    #[test]
+    fn test_exr_round_trip_unclamped() {
+        use exr::prelude::{read, ReadChannels, ReadLayers};
+        use image::ImageBuffer;
+
+        let skin_texture = image::open("sample.png").unwrap().into_rgba8();
+        let render = crate::RenderContext::new()
+            .render_linear(&skin_texture, &crate::RenderOptions::default());
+        let bytes = encode_exr(&render).unwrap();
+
+        let decoded = read()
+            .no_deep_data()
+            .largest_resolution_level()
+            .rgba_channels(
+                |resolution, _| {
+                    ImageBuffer::new(resolution.width() as u32, resolution.height() as u32)
+                },
+                |image: &mut ImageBuffer<Rgba<f32>, Vec<f32>>,
+                 position,
+                 (r, g, b, a): (f32, f32, f32, f32)| {
+                    image.put_pixel(position.x() as u32, position.y() as u32, Rgba([r, g, b, a]))
+                },
+            )
+            .first_valid_layer()
+            .all_attributes()
+            .from_buffered(std::io::Cursor::new(bytes))
+            .unwrap()
+            .layer_data
+            .channel_data
+            .pixels;
+        assert_eq!(decoded.dimensions(), render.dimensions());
+
+        // Opaque pixels aren't changed by premultiplying.
+        let (x, y, brightest) = render
+            .enumerate_pixels()
+            .filter(|(_, _, p)| p[3] == 1f32)
+            .max_by(|(_, _, a), (_, _, b)| a[0].partial_cmp(&b[0]).unwrap())
+            .unwrap();
+        assert_eq!(decoded.get_pixel(x, y), brightest);
+        // Lighting brightens some pixels past what an 8 bit render can store.
+        assert!(brightest[0] > 1f32, "{:?}", brightest);
+    }
+
+    #[cfg(feature = "webp")]
+    #[test]
+    fn test_estimate_encoded_size() {
+        let skin_texture = image::open("sample.png").unwrap().into_rgba8();
+        let render = crate::create_render(&skin_texture);
+
+        let png_size = estimate_encoded_size(&render, OutputFormat::Png, 90).unwrap();
+        assert_eq!(
+            png_size,
+            encode_image(&render, &OutputOptions::default())
+                .unwrap()
+                .len()
+        );
+        let webp_size = estimate_encoded_size(&render, OutputFormat::WebP, 90).unwrap();
+        assert_ne!(png_size, webp_size);
+        // Lower quality settings should produce smaller lossy files.
+        assert!(estimate_encoded_size(&render, OutputFormat::WebP, 10).unwrap() < webp_size);
+    }
+
+    #[cfg(feature = "webp")]
+    #[test]
+    fn test_webp_preserves_alpha() {
+        let image = RgbaImage::from_fn(16, 16, |x, _| {
+            if x < 8 {
+                Rgba([255u8, 0u8, 0u8, 255u8])
+            } else {
+                Rgba([0u8, 0u8, 0u8, 0u8])
+            }
+        });
+        let bytes = encode_image(
+            &image,
+            &OutputOptions {
+                format: OutputFormat::WebP,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let decoded = webp::Decoder::new(&bytes).decode().unwrap();
+        assert!(decoded.is_alpha());
+        assert_eq!((decoded.width(), decoded.height()), (16, 16));
+        // Check the alpha of the first and last pixel.
+        assert_eq!(decoded[3], 255u8);
+        assert_eq!(decoded[decoded.len() - 1], 0u8);
+    }
+}
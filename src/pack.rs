@@ -0,0 +1,155 @@
+//! Read skin textures from ZIP archives like resource packs and `.mcpack` files.
+//! This requires the `pack` feature.
+use image::RgbaImage;
+use std::fmt;
+use std::io::{Read, Seek};
+use zip::result::ZipError;
+use zip::ZipArchive;
+
+/// Errors while reading a skin texture from a pack.
+#[derive(Debug)]
+pub enum PackError {
+    /// The archive could not be read.
+    Zip(ZipError),
+    /// The archive has no entry with the given path.
+    EntryNotFound(String),
+    /// The entry with the given path isn't a PNG file.
+    NotPng(String),
+    /// The entry could not be decoded.
+    Image(image::ImageError),
+}
+
+impl fmt::Display for PackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PackError::Zip(e) => write!(f, "invalid pack: {}", e),
+            PackError::EntryNotFound(name) => write!(f, "no entry {:?} in the pack", name),
+            PackError::NotPng(name) => write!(f, "entry {:?} is not a PNG file", name),
+            PackError::Image(e) => write!(f, "invalid skin texture: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for PackError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PackError::Zip(e) => Some(e),
+            PackError::Image(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<ZipError> for PackError {
+    fn from(e: ZipError) -> Self {
+        PackError::Zip(e)
+    }
+}
+
+impl From<image::ImageError> for PackError {
+    fn from(e: image::ImageError) -> Self {
+        PackError::Image(e)
+    }
+}
+
+/// Lists the paths of the PNG entries in the pack in archive order.
+pub fn list_png_entries<R: Read + Seek>(reader: R) -> Result<Vec<String>, PackError> {
+    let mut archive = ZipArchive::new(reader)?;
+    let mut names = Vec::new();
+    for i in 0..archive.len() {
+        // Only the names are needed, so skip decompressing the entries.
+        let name = archive.by_index_raw(i)?.name().to_string();
+        if is_png(&name) {
+            names.push(name);
+        }
+    }
+    Ok(names)
+}
+
+/// Reads and decodes the PNG entry at `entry` in the pack.
+pub fn read_skin<R: Read + Seek>(reader: R, entry: &str) -> Result<RgbaImage, PackError> {
+    if !is_png(entry) {
+        return Err(PackError::NotPng(entry.to_string()));
+    }
+
+    let mut archive = ZipArchive::new(reader)?;
+    let mut file = match archive.by_name(entry) {
+        Ok(file) => file,
+        Err(ZipError::FileNotFound) => return Err(PackError::EntryNotFound(entry.to_string())),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes).map_err(ZipError::from)?;
+    // Check the contents as well since the extension may not match the data.
+    if image::guess_format(&bytes).ok() != Some(image::ImageFormat::Png) {
+        return Err(PackError::NotPng(entry.to_string()));
+    }
+    Ok(image::load_from_memory(&bytes)?.into_rgba8())
+}
+
+fn is_png(name: &str) -> bool {
+    name.to_ascii_lowercase().ends_with(".png")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+    use zip::write::FileOptions;
+
+    fn pack(skin_texture: &RgbaImage) -> Cursor<Vec<u8>> {
+        let mut png = Vec::new();
+        image::DynamicImage::ImageRgba8(skin_texture.clone())
+            .write_to(&mut png, image::ImageOutputFormat::Png)
+            .unwrap();
+
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .start_file(
+                "assets/minecraft/textures/entity/steve.png",
+                FileOptions::default(),
+            )
+            .unwrap();
+        writer.write_all(&png).unwrap();
+        writer
+            .start_file("pack.mcmeta", FileOptions::default())
+            .unwrap();
+        writer.write_all(b"{}").unwrap();
+        writer
+            .start_file("fake.png", FileOptions::default())
+            .unwrap();
+        writer.write_all(b"not a png").unwrap();
+        Cursor::new(writer.finish().unwrap().into_inner())
+    }
+
+    #[test]
+    fn test_read_skin_from_pack() {
+        let skin_texture = image::open("sample.png").unwrap().into_rgba8();
+        let mut pack = pack(&skin_texture);
+
+        assert_eq!(
+            list_png_entries(&mut pack).unwrap(),
+            vec![
+                "assets/minecraft/textures/entity/steve.png".to_string(),
+                "fake.png".to_string()
+            ]
+        );
+        assert_eq!(
+            read_skin(&mut pack, "assets/minecraft/textures/entity/steve.png").unwrap(),
+            skin_texture
+        );
+        assert!(matches!(
+            read_skin(&mut pack, "missing.png"),
+            Err(PackError::EntryNotFound(_))
+        ));
+        assert!(matches!(
+            read_skin(&mut pack, "pack.mcmeta"),
+            Err(PackError::NotPng(_))
+        ));
+        assert!(matches!(
+            read_skin(&mut pack, "fake.png"),
+            Err(PackError::NotPng(_))
+        ));
+    }
+}
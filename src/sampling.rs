@@ -0,0 +1,312 @@
+//! Functions for sampling skin textures and UV maps.
+use image::{ImageBuffer, Pixel, Rgba};
+use std::cmp::min;
+
+pub use minecraft_render_core::{
+    interpolate_nearest, interpolate_nearest_with, interpolate_nearest_with_offset, WrapMode,
+    DEFAULT_TEXEL_OFFSET,
+};
+pub(crate) use minecraft_render_core::{to_u16_clamped, to_u8_clamped};
+
+/// Returns the texel of `image` nearest to the texture coordinates `u` and `v`.
+///
+/// The coordinates use an origin at the bottom left like OpenGL, so `v` is flipped
+/// to index the image with its origin at the top left.
+/// This means `(0.0, 0.0)` samples the bottom left pixel rather than the first pixel in memory.
+/// Coordinates outside 0.0 to 1.0 are clamped to the edges of the image.
+/// This works for any pixel format like 8 or 16 bit RGBA skin textures.
+pub fn sample_texture<P: Pixel + 'static>(
+    image: &ImageBuffer<P, Vec<P::Subpixel>>,
+    u: f32,
+    v: f32,
+) -> &P {
+    sample_texture_with(image, u, v, WrapMode::Clamp, DEFAULT_TEXEL_OFFSET)
+}
+
+/// Returns the texel of `image` nearest to the texture coordinates `u` and `v` like [sample_texture]
+/// using `wrap` for coordinates outside 0.0 to 1.0.
+/// See [interpolate_nearest_with_offset] for `texel_offset`, which is usually [DEFAULT_TEXEL_OFFSET].
+pub fn sample_texture_with<P: Pixel + 'static>(
+    image: &ImageBuffer<P, Vec<P::Subpixel>>,
+    u: f32,
+    v: f32,
+    wrap: WrapMode,
+    texel_offset: f32,
+) -> &P {
+    sample_texture_with_flip(image, u, v, wrap, true, texel_offset)
+}
+
+/// Returns the texel of `image` nearest to the texture coordinates `u` and `v` like [sample_texture_with].
+/// Disabling `flip_v` uses an origin at the top left like the image instead of the bottom left,
+/// so `(0.0, 0.0)` samples the first pixel in memory.
+pub fn sample_texture_with_flip<P: Pixel + 'static>(
+    image: &ImageBuffer<P, Vec<P::Subpixel>>,
+    u: f32,
+    v: f32,
+    wrap: WrapMode,
+    flip_v: bool,
+    texel_offset: f32,
+) -> &P {
+    // Flip v to transform from an origin at the bottom left (OpenGL) to top left (image).
+    let v = if flip_v { 1f32 - v } else { v };
+    let (width, height) = image.dimensions();
+    let (x, y) = interpolate_nearest_with_offset(u, v, width, height, wrap, texel_offset);
+    image.get_pixel(x, y)
+}
+
+/// Returns the u, v, and lighting of the UV map `map` bilinearly interpolated at the pixel coordinates `x` and `y`.
+/// The pixel coordinates use the top left corner of the image as the origin like [interpolate_nearest].
+///
+/// Texels that are transparent or have UVs more than `max_difference` from the nearest texel are skipped,
+/// so the UVs from different faces of the model aren't blended together.
+pub fn sample_uv_map_bilinear(
+    map: &ImageBuffer<Rgba<u16>, Vec<u16>>,
+    x: f32,
+    y: f32,
+    max_difference: f32,
+) -> (f32, f32, f32) {
+    let (width, height) = map.dimensions();
+    let clamp = |f: f32, max_val: u32| min(f.max(0f32) as u32, max_val - 1);
+    let (nearest_u, nearest_v, nearest_lighting, _) =
+        normalize_rgba_u16(map.get_pixel(clamp(x, width), clamp(y, height)));
+
+    // Offset by half a texel, so integer coordinates are the texel centers.
+    let x = x - 0.5f32;
+    let y = y - 0.5f32;
+    let x0 = x.floor();
+    let y0 = y.floor();
+    let tx = x - x0;
+    let ty = y - y0;
+
+    let mut sum = [0f32; 3];
+    let mut total_weight = 0f32;
+    for (dx, dy, weight) in [
+        (0f32, 0f32, (1f32 - tx) * (1f32 - ty)),
+        (1f32, 0f32, tx * (1f32 - ty)),
+        (0f32, 1f32, (1f32 - tx) * ty),
+        (1f32, 1f32, tx * ty),
+    ] {
+        let texel = map.get_pixel(clamp(x0 + dx, width), clamp(y0 + dy, height));
+        let (u, v, lighting, alpha) = normalize_rgba_u16(texel);
+        if alpha == 0f32
+            || (u - nearest_u).abs() > max_difference
+            || (v - nearest_v).abs() > max_difference
+        {
+            continue;
+        }
+        sum[0] += u * weight;
+        sum[1] += v * weight;
+        sum[2] += lighting * weight;
+        total_weight += weight;
+    }
+
+    if total_weight == 0f32 {
+        (nearest_u, nearest_v, nearest_lighting)
+    } else {
+        (
+            sum[0] / total_weight,
+            sum[1] / total_weight,
+            sum[2] / total_weight,
+        )
+    }
+}
+
+/// Converts the channels of `pixel` to the range 0.0 to 1.0.
+pub fn normalize_rgba_u8(pixel: &Rgba<u8>) -> (f32, f32, f32, f32) {
+    minecraft_render_core::normalize_rgba_u8(pixel.0)
+}
+
+/// Converts the channels of `pixel` to the range 0.0 to 1.0.
+pub fn normalize_rgba_u16(pixel: &Rgba<u16>) -> (f32, f32, f32, f32) {
+    minecraft_render_core::normalize_rgba_u16(pixel.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::RgbaImage;
+
+    #[test]
+    fn test_interpolate_nearest_8x8_edges() {
+        assert_eq!(interpolate_nearest(0f32, 0f32, 8u32, 8u32), (0u32, 0u32));
+        assert_eq!(interpolate_nearest(0f32, 1f32, 8u32, 8u32), (0u32, 7u32));
+        assert_eq!(interpolate_nearest(1f32, 0f32, 8u32, 8u32), (7u32, 0u32));
+        assert_eq!(interpolate_nearest(1f32, 1f32, 8u32, 8u32), (7u32, 7u32));
+    }
+
+    #[test]
+    fn test_interpolate_nearest_out_of_bounds() {
+        assert_eq!(interpolate_nearest(0f32, 1.5f32, 8u32, 8u32), (0u32, 7u32));
+        assert_eq!(interpolate_nearest(1.5f32, 0f32, 8u32, 8u32), (7u32, 0u32));
+        assert_eq!(
+            interpolate_nearest(1.5f32, 1.5f32, 8u32, 8u32),
+            (7u32, 7u32)
+        );
+    }
+
+    #[test]
+    fn test_interpolate_nearest_wrap_modes() {
+        assert_eq!(
+            interpolate_nearest_with(1.25f32, 0f32, 8u32, 8u32, WrapMode::Clamp),
+            (7u32, 0u32)
+        );
+        // 1.25 wraps to 0.25, which is the edge between the second and third pixels.
+        assert_eq!(
+            interpolate_nearest_with(1.25f32, 0f32, 8u32, 8u32, WrapMode::Repeat),
+            (2u32, 0u32)
+        );
+        assert_eq!(
+            interpolate_nearest_with(-0.75f32, 1.25f32, 8u32, 8u32, WrapMode::Repeat),
+            (2u32, 2u32)
+        );
+    }
+
+    #[test]
+    fn test_interpolate_nearest_texel_offset() {
+        // A coordinate just inside the second texel of an 8 pixel wide region.
+        let x = 1.2f32 / 8f32;
+        assert_eq!(
+            interpolate_nearest_with_offset(x, 0f32, 8u32, 8u32, WrapMode::Clamp, 0.5f32),
+            (1u32, 0u32)
+        );
+        assert_eq!(
+            interpolate_nearest_with_offset(x, 0f32, 8u32, 8u32, WrapMode::Clamp, 0.5f32),
+            interpolate_nearest(x, 0f32, 8u32, 8u32)
+        );
+
+        // Without an offset, coordinates round to the nearest texel corner instead.
+        let x = 0.6f32 / 8f32;
+        assert_eq!(
+            interpolate_nearest_with_offset(x, 0f32, 8u32, 8u32, WrapMode::Clamp, 0.5f32),
+            (0u32, 0u32)
+        );
+        assert_eq!(
+            interpolate_nearest_with_offset(x, 0f32, 8u32, 8u32, WrapMode::Clamp, 0f32),
+            (1u32, 0u32)
+        );
+        // The last texel is still clamped for either offset.
+        assert_eq!(
+            interpolate_nearest_with_offset(1f32, 1f32, 8u32, 8u32, WrapMode::Clamp, 0f32),
+            (7u32, 7u32)
+        );
+    }
+
+    #[test]
+    fn test_sample_texture_wrap_modes() {
+        let image = RgbaImage::from_fn(4, 4, |x, y| Rgba([x as u8, y as u8, 0u8, 255u8]));
+        assert_eq!(
+            sample_texture_with(
+                &image,
+                1.25f32,
+                0.5f32,
+                WrapMode::Clamp,
+                DEFAULT_TEXEL_OFFSET
+            ),
+            &Rgba([3u8, 2u8, 0u8, 255u8])
+        );
+        assert_eq!(
+            sample_texture_with(
+                &image,
+                1.25f32,
+                0.5f32,
+                WrapMode::Repeat,
+                DEFAULT_TEXEL_OFFSET
+            ),
+            &Rgba([1u8, 2u8, 0u8, 255u8])
+        );
+        assert_eq!(
+            sample_texture(&image, 1.25f32, 0.5f32),
+            sample_texture_with(
+                &image,
+                1.25f32,
+                0.5f32,
+                WrapMode::Clamp,
+                DEFAULT_TEXEL_OFFSET
+            )
+        );
+    }
+
+    #[test]
+    fn test_sample_texture_flip_v() {
+        // The top half is red and the bottom half is blue.
+        let image = RgbaImage::from_fn(4, 4, |_, y| {
+            if y < 2 {
+                Rgba([255u8, 0u8, 0u8, 255u8])
+            } else {
+                Rgba([0u8, 0u8, 255u8, 255u8])
+            }
+        });
+        let sample = |v, flip_v| {
+            *sample_texture_with_flip(
+                &image,
+                0.5f32,
+                v,
+                WrapMode::Clamp,
+                flip_v,
+                DEFAULT_TEXEL_OFFSET,
+            )
+        };
+
+        assert_eq!(sample(0.1f32, true), Rgba([0u8, 0u8, 255u8, 255u8]));
+        assert_eq!(sample(0.9f32, true), Rgba([255u8, 0u8, 0u8, 255u8]));
+        assert_eq!(sample(0.1f32, false), Rgba([255u8, 0u8, 0u8, 255u8]));
+        assert_eq!(sample(0.9f32, false), Rgba([0u8, 0u8, 255u8, 255u8]));
+        assert_eq!(
+            &sample(0.1f32, true),
+            sample_texture(&image, 0.5f32, 0.1f32)
+        );
+    }
+
+    #[test]
+    fn test_sample_uv_map_bilinear() {
+        let map = ImageBuffer::from_fn(3, 1, |x, _| match x {
+            0 => Rgba([16384u16, 16384u16, 32768u16, 65535u16]),
+            1 => Rgba([16640u16, 16384u16, 32768u16, 65535u16]),
+            _ => Rgba([49152u16, 16384u16, 32768u16, 65535u16]),
+        });
+
+        // The second output pixel at 2x scale is a quarter of the way to the next texel.
+        let (u, v, _) = sample_uv_map_bilinear(&map, 1.5f32 / 2f32, 0.5f32, 1f32 / 64f32);
+        let expected = (16384f32 * 0.75f32 + 16640f32 * 0.25f32) / 65535f32;
+        assert!((u - expected).abs() < 1e-6f32);
+        assert!((v - 0.25f32).abs() < 1e-4f32);
+
+        // The last texel is on a different face, so it isn't blended.
+        let (u, _, _) = sample_uv_map_bilinear(&map, 3.5f32 / 2f32, 0.5f32, 1f32 / 64f32);
+        assert_eq!(u, 16640f32 / 65535f32);
+    }
+
+    #[test]
+    fn test_normalize_u8() {
+        assert_eq!(
+            normalize_rgba_u8(&Rgba([0u8, 0u8, 0u8, 0u8])),
+            (0f32, 0f32, 0f32, 0f32)
+        );
+        assert_eq!(
+            normalize_rgba_u8(&Rgba([255u8, 255u8, 255u8, 255u8])),
+            (1f32, 1f32, 1f32, 1f32)
+        );
+    }
+
+    #[test]
+    fn test_normalize_u16() {
+        assert_eq!(
+            normalize_rgba_u16(&Rgba([0u16, 0u16, 0u16, 0u16])),
+            (0f32, 0f32, 0f32, 0f32)
+        );
+        assert_eq!(
+            normalize_rgba_u16(&Rgba([65535u16, 65535u16, 65535u16, 65535u16])),
+            (1f32, 1f32, 1f32, 1f32)
+        );
+    }
+
+    #[test]
+    fn test_to_u8_clamped() {
+        assert_eq!(to_u8_clamped(0.999f32), 255u8);
+        assert_eq!(to_u8_clamped(-1.5f32), 0u8);
+        assert_eq!(to_u8_clamped(0f32), 0u8);
+        assert_eq!(to_u8_clamped(0.5f32), 128u8);
+        assert_eq!(to_u8_clamped(1f32), 255u8);
+        assert_eq!(to_u8_clamped(1.01f32), 255u8);
+    }
+}
@@ -0,0 +1,40 @@
+//! Bindings for rendering skins in the browser.
+//! This requires the `wasm` feature.
+use wasm_bindgen::prelude::*;
+
+use crate::{RenderContext, RenderOptions};
+
+thread_local! {
+    // Reuse the decoded UV maps across calls since decoding is the most expensive step.
+    static CONTEXT: RenderContext = RenderContext::new();
+}
+
+/// Creates a render from the PNG bytes of a Minecraft skin texture
+/// and returns the PNG bytes of the render.
+#[wasm_bindgen]
+pub fn render_skin(bytes: &[u8]) -> Result<Vec<u8>, JsValue> {
+    let skin_texture = image::load_from_memory(bytes)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?
+        .into_rgba8();
+
+    let render = CONTEXT.with(|context| context.render(&skin_texture, &RenderOptions::default()));
+
+    let mut png = Vec::new();
+    image::DynamicImage::ImageRgba8(render)
+        .write_to(&mut png, image::ImageOutputFormat::Png)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    Ok(png)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_skin_reuses_context() {
+        let bytes = include_bytes!("../sample.png");
+        let png1 = render_skin(bytes).unwrap();
+        let png2 = render_skin(bytes).unwrap();
+        assert_eq!(png1, png2);
+    }
+}
@@ -0,0 +1,414 @@
+// Run with: cargo test --features cli
+#![cfg(feature = "cli")]
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+fn minecraft_render() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_minecraft_render"))
+}
+
+#[test]
+fn stdin_to_stdout() {
+    let skin_bytes = std::fs::read("sample.png").unwrap();
+
+    let mut child = minecraft_render()
+        .args(["--stdin", "--stdout"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(&skin_bytes).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+
+    let skin_texture = image::load_from_memory(&skin_bytes).unwrap().into_rgba8();
+    let render = image::load_from_memory(&output.stdout)
+        .unwrap()
+        .into_rgba8();
+    assert_eq!(render, minecraft_render::create_render(&skin_texture));
+}
+
+#[test]
+fn stdout_with_contact_sheet() {
+    // The contact sheet can't be written to stdout with the render.
+    let output = minecraft_render()
+        .args(["--skin", "sample.png", "--stdout", "--contact-sheet"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(!output.success());
+}
+
+#[test]
+fn check_mixed_batch() {
+    let input_dir = std::env::temp_dir().join("minecraft_render_test_check");
+    let output_dir = input_dir.join("renders");
+    let _ = std::fs::remove_dir_all(&input_dir);
+    std::fs::create_dir_all(&input_dir).unwrap();
+
+    let skin_texture = image::open("sample.png").unwrap().into_rgba8();
+    skin_texture.save(input_dir.join("classic.png")).unwrap();
+    std::fs::copy(
+        "tests/fixtures/skins/blocks_slim.png",
+        input_dir.join("slim.png"),
+    )
+    .unwrap();
+    image::imageops::crop_imm(&skin_texture, 0, 0, 64, 32)
+        .to_image()
+        .save(input_dir.join("legacy.png"))
+        .unwrap();
+    image::RgbaImage::new(64, 64)
+        .save(input_dir.join("empty.png"))
+        .unwrap();
+    image::imageops::crop_imm(&skin_texture, 0, 0, 48, 64)
+        .to_image()
+        .save(input_dir.join("cropped.png"))
+        .unwrap();
+    std::fs::write(input_dir.join("corrupt.png"), b"not a png").unwrap();
+
+    let output = minecraft_render()
+        .arg("--check")
+        .arg("--batch")
+        .args([&input_dir, &output_dir])
+        .stderr(Stdio::null())
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(!output_dir.exists());
+
+    let report = String::from_utf8(output.stdout).unwrap();
+    let status = |name: &str| {
+        report
+            .lines()
+            .find(|l| l.contains(name))
+            .and_then(|l| l.split(": ").nth(1))
+            .map(|s| s.split(' ').next().unwrap().to_string())
+            .unwrap()
+    };
+    assert_eq!(status("classic.png"), "valid");
+    assert_eq!(status("slim.png"), "slim");
+    assert_eq!(status("legacy.png"), "legacy");
+    assert_eq!(status("empty.png"), "invalid");
+    assert_eq!(status("cropped.png"), "invalid");
+    assert_eq!(status("corrupt.png"), "invalid");
+}
+
+#[test]
+fn quiet_has_no_stderr() {
+    let output = minecraft_render()
+        .args(["--skin", "sample.png", "--stdout", "--quiet"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(!output.stdout.is_empty());
+    assert!(output.stderr.is_empty());
+
+    let input_dir = std::env::temp_dir().join("minecraft_render_test_quiet");
+    let output_dir = input_dir.join("renders");
+    let _ = std::fs::remove_dir_all(&input_dir);
+    std::fs::create_dir_all(&input_dir).unwrap();
+    std::fs::copy("sample.png", input_dir.join("sample.png")).unwrap();
+
+    let output = minecraft_render()
+        .args(["--quiet", "--batch"])
+        .args([&input_dir, &output_dir])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(output.stderr.is_empty());
+    assert!(output_dir.join("sample").join("output.png").exists());
+    std::fs::remove_dir_all(&input_dir).unwrap();
+}
+
+#[test]
+fn stdout_bit_depth_16() {
+    let output = minecraft_render()
+        .args(["--skin", "sample.png", "--stdout", "--bit-depth", "16"])
+        .stderr(Stdio::null())
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let skin_texture = image::open("sample.png").unwrap().into_rgba8();
+    match image::load_from_memory(&output.stdout).unwrap() {
+        image::DynamicImage::ImageRgba16(render) => {
+            assert_eq!(render, minecraft_render::create_render_hdr(&skin_texture))
+        }
+        _ => panic!("expected a 16 bit RGBA render"),
+    }
+}
+
+#[test]
+fn chara_outputs_match_library() {
+    let input_dir = std::env::temp_dir().join("minecraft_render_test_chara");
+    let output_dir = input_dir.join("renders");
+    let _ = std::fs::remove_dir_all(&input_dir);
+    std::fs::create_dir_all(&input_dir).unwrap();
+    std::fs::copy("sample.png", input_dir.join("sample.png")).unwrap();
+
+    let status = minecraft_render()
+        .arg("--batch")
+        .args([&input_dir, &output_dir])
+        .stderr(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    // Charas may be created in parallel, so compare with creating each chara in order.
+    let skin_texture = image::open("sample.png").unwrap().into_rgba8();
+    let render = minecraft_render::create_render(&skin_texture);
+    let slots = [
+        ("chara_3", 1.2845125f32, -456.55612f32, 11.757321f32),
+        ("chara_4", 0.23288201f32, -90.16959f32, 9.084564f32),
+        ("chara_6", 0.938028f32, -480.87906f32, -96.13269f32),
+    ];
+    for (name, scale, translate_x, translate_y) in slots {
+        let mask = image::open(format!("images/masks/{}_mask.png", name))
+            .unwrap()
+            .into_rgba8();
+        let expected = minecraft_render::create_chara_image_affine(
+            &render,
+            &mask,
+            scale,
+            0f32,
+            (translate_x, translate_y),
+            minecraft_render::Interpolation::Bilinear,
+            image::Rgba([0u8, 0u8, 0u8, 0u8]),
+        );
+        let chara = image::open(
+            output_dir
+                .join("sample")
+                .join(format!("{}_custom.png", name)),
+        )
+        .unwrap()
+        .into_rgba8();
+        assert_eq!(chara, expected, "{}", name);
+    }
+    std::fs::remove_dir_all(&input_dir).unwrap();
+}
+
+#[test]
+fn batch_matches_render_all() {
+    let input_dir = std::env::temp_dir().join("minecraft_render_test_render_all");
+    let output_dir = input_dir.join("renders");
+    let _ = std::fs::remove_dir_all(&input_dir);
+    std::fs::create_dir_all(&input_dir).unwrap();
+    std::fs::copy(
+        "tests/fixtures/skins/blocks_slim.png",
+        input_dir.join("blocks_slim.png"),
+    )
+    .unwrap();
+
+    let status = minecraft_render()
+        .arg("--batch")
+        .args([&input_dir, &output_dir])
+        .stderr(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let skin_texture = image::open("tests/fixtures/skins/blocks_slim.png")
+        .unwrap()
+        .into_rgba8();
+    let options = minecraft_render::RenderOptions {
+        slim: true,
+        ..Default::default()
+    };
+    let outputs = minecraft_render::render_all(&skin_texture, &options);
+    let open = |name: &str| {
+        image::open(output_dir.join("blocks_slim").join(format!("{}.png", name)))
+            .unwrap()
+            .into_rgba8()
+    };
+    assert_eq!(open("output"), outputs.render);
+    assert_eq!(outputs.charas.len(), 3);
+    for (name, chara) in &outputs.charas {
+        assert_eq!(&open(name), chara, "{}", name);
+    }
+    std::fs::remove_dir_all(&input_dir).unwrap();
+}
+
+#[test]
+fn custom_chara_mask() {
+    let input_dir = std::env::temp_dir().join("minecraft_render_test_chara_mask");
+    let output_dir = input_dir.join("renders");
+    let _ = std::fs::remove_dir_all(&input_dir);
+    std::fs::create_dir_all(&input_dir).unwrap();
+    std::fs::copy("sample.png", input_dir.join("sample.png")).unwrap();
+
+    // Only keep the left half of the portrait using a custom frame size.
+    let mask = image::RgbaImage::from_fn(64, 48, |x, _| {
+        image::Rgba([255u8, 255u8, 255u8, if x < 32 { 255u8 } else { 0u8 }])
+    });
+    let mask_path = std::env::temp_dir().join("minecraft_render_test_mask.png");
+    mask.save(&mask_path).unwrap();
+
+    let status = minecraft_render()
+        .arg("--chara-mask")
+        .arg(format!("chara_4={}", mask_path.display()))
+        .arg("--batch")
+        .args([&input_dir, &output_dir])
+        .stderr(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let skin_texture = image::open("sample.png").unwrap().into_rgba8();
+    let render = minecraft_render::create_render(&skin_texture);
+    let expected = minecraft_render::create_chara_image_affine(
+        &render,
+        &mask,
+        0.23288201f32,
+        0f32,
+        (-90.16959f32, 9.084564f32),
+        minecraft_render::Interpolation::Bilinear,
+        image::Rgba([0u8, 0u8, 0u8, 0u8]),
+    );
+    let chara = image::open(output_dir.join("sample").join("chara_4_custom.png"))
+        .unwrap()
+        .into_rgba8();
+    assert_eq!(chara, expected);
+    assert!(chara
+        .enumerate_pixels()
+        .all(|(x, _, p)| x < 32 || p[3] == 0u8));
+    assert!(chara.enumerate_pixels().any(|(_, _, p)| p[3] > 0u8));
+
+    // Masks without alpha can't define the shape of the chara image.
+    image::RgbImage::new(64, 48).save(&mask_path).unwrap();
+    let status = minecraft_render()
+        .arg("--chara-mask")
+        .arg(format!("chara_4={}", mask_path.display()))
+        .arg("--batch")
+        .args([&input_dir, &output_dir])
+        .stderr(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(!status.success());
+
+    std::fs::remove_file(&mask_path).unwrap();
+    std::fs::remove_dir_all(&input_dir).unwrap();
+}
+
+#[test]
+fn native_size_charas() {
+    let input_dir = std::env::temp_dir().join("minecraft_render_test_native_size");
+    let output_dir = input_dir.join("renders");
+    let _ = std::fs::remove_dir_all(&input_dir);
+    std::fs::create_dir_all(&input_dir).unwrap();
+    std::fs::copy("sample.png", input_dir.join("sample.png")).unwrap();
+
+    let status = minecraft_render()
+        .arg("--native-size")
+        .arg("--batch")
+        .args([&input_dir, &output_dir])
+        .stderr(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let dimensions = |name: &str| {
+        image::image_dimensions(output_dir.join("sample").join(format!("{}.png", name))).unwrap()
+    };
+    assert_eq!(dimensions("chara_4_custom"), (160, 160));
+    assert_eq!(dimensions("chara_3_custom"), (968, 1864));
+    assert_eq!(dimensions("chara_6_custom"), (512, 256));
+    std::fs::remove_dir_all(&input_dir).unwrap();
+}
+
+#[test]
+fn stdout_trim() {
+    let output = minecraft_render()
+        .args(["--skin", "sample.png", "--stdout", "--trim"])
+        .stderr(Stdio::null())
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let skin_texture = image::open("sample.png").unwrap().into_rgba8();
+    let (expected, _) =
+        minecraft_render::trim_transparent(&minecraft_render::create_render(&skin_texture));
+    let render = image::load_from_memory(&output.stdout)
+        .unwrap()
+        .into_rgba8();
+    assert_eq!(render, expected);
+}
+
+#[test]
+fn fallback_for_invalid_skin() {
+    let path = std::env::temp_dir().join("minecraft_render_test_fallback.png");
+    std::fs::write(&path, b"not a png").unwrap();
+
+    let output = minecraft_render()
+        .arg("--skin")
+        .arg(&path)
+        .args(["--stdout", "--fallback"])
+        .stderr(Stdio::null())
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let render = image::load_from_memory(&output.stdout)
+        .unwrap()
+        .into_rgba8();
+    let skin_texture = minecraft_render::fallback_skin(minecraft_render::ModelType::Classic);
+    assert_eq!(render, minecraft_render::create_render(&skin_texture));
+
+    // Invalid skins are still an error without --fallback.
+    let status = minecraft_render()
+        .arg("--skin")
+        .arg(&path)
+        .arg("--stdout")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(!status.success());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[cfg(feature = "exr")]
+#[test]
+fn stdout_exr() {
+    let output = minecraft_render()
+        .args(["--skin", "sample.png", "--stdout", "--format", "exr"])
+        .stderr(Stdio::null())
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let skin_texture = image::open("sample.png").unwrap().into_rgba8();
+    let render = minecraft_render::RenderContext::new()
+        .render_linear(&skin_texture, &minecraft_render::RenderOptions::default());
+    assert_eq!(
+        output.stdout,
+        minecraft_render::output::encode_exr(&render).unwrap()
+    );
+}
+
+#[test]
+fn stdout_icc() {
+    let path = std::env::temp_dir().join("minecraft_render_test_profile.icc");
+    std::fs::write(&path, b"not a real profile").unwrap();
+    let output = minecraft_render()
+        .args(["--skin", "sample.png", "--stdout", "--icc"])
+        .arg(&path)
+        .stderr(Stdio::null())
+        .output()
+        .unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(output.status.success());
+
+    let skin_texture = image::open("sample.png").unwrap().into_rgba8();
+    let expected = minecraft_render::output::encode_image(
+        &minecraft_render::create_render(&skin_texture),
+        &minecraft_render::output::OutputOptions {
+            color_profile: minecraft_render::output::ColorProfile::Icc(
+                b"not a real profile".to_vec(),
+            ),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(output.stdout, expected);
+}
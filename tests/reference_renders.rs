@@ -0,0 +1,66 @@
+// Compare renders to the expected renders in tests/fixtures/expected.
+// Run with UPDATE_REFERENCE=1 to overwrite the expected renders after an intentional change.
+use image::RgbaImage;
+use minecraft_render::{create_render_with_options, RenderOptions};
+use std::path::Path;
+
+// The maximum difference for any channel.
+// Increase this only after reviewing the changes to the expected renders.
+const TOLERANCE: u8 = 2;
+
+fn assert_images_close(actual: &RgbaImage, expected: &RgbaImage, tolerance: u8) {
+    assert_eq!(actual.dimensions(), expected.dimensions());
+
+    let mut max_difference = 0u8;
+    let mut max_position = (0, 0);
+    for (x, y, actual_pixel) in actual.enumerate_pixels() {
+        let expected_pixel = expected.get_pixel(x, y);
+        for c in 0..4 {
+            let difference =
+                (actual_pixel[c] as i16 - expected_pixel[c] as i16).unsigned_abs() as u8;
+            if difference > max_difference {
+                max_difference = difference;
+                max_position = (x, y);
+            }
+        }
+    }
+
+    assert!(
+        max_difference <= tolerance,
+        "max difference {} at {:?} exceeds tolerance {}",
+        max_difference,
+        max_position,
+        tolerance
+    );
+}
+
+fn check_reference_render(name: &str, skin_path: &str, options: &RenderOptions) {
+    let skin_texture = image::open(skin_path).unwrap().into_rgba8();
+    let render = create_render_with_options(&skin_texture, options);
+
+    let expected_path = Path::new("tests/fixtures/expected").join(format!("{}.png", name));
+    if std::env::var("UPDATE_REFERENCE").is_ok() {
+        render.save(&expected_path).unwrap();
+        return;
+    }
+
+    let expected = image::open(&expected_path).unwrap().into_rgba8();
+    assert_images_close(&render, &expected, TOLERANCE);
+}
+
+#[test]
+fn reference_sample() {
+    check_reference_render("sample", "sample.png", &RenderOptions::default());
+}
+
+#[test]
+fn reference_blocks_slim() {
+    check_reference_render(
+        "blocks_slim",
+        "tests/fixtures/skins/blocks_slim.png",
+        &RenderOptions {
+            slim: true,
+            ..Default::default()
+        },
+    );
+}
@@ -0,0 +1,11 @@
+// Run with: wasm-pack test --node -- --features wasm
+#![cfg(all(target_arch = "wasm32", feature = "wasm"))]
+use wasm_bindgen_test::*;
+
+#[wasm_bindgen_test]
+fn render_skin_png() {
+    let png = minecraft_render::wasm::render_skin(include_bytes!("../sample.png")).unwrap();
+    let render = image::load_from_memory(&png).unwrap();
+    assert_eq!(render.width(), 1097);
+    assert_eq!(render.height(), 1497);
+}